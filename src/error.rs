@@ -21,6 +21,141 @@ pub enum TokenError {
     Notstarted,
     #[error("Token Finished")]
     TokenFinished,
+    // 5
+    #[error("Metadata account doesn't match the NFT mint")]
+    InvalidMetadataAccount,
+    #[error("Invalid whitelist account")]
+    InvalidWhitelistAccount,
+    #[error("Collection is not whitelisted")]
+    CollectionNotWhitelisted,
+    #[error("Invalid associated token account")]
+    InvalidTokenAccount,
+    #[error("Invalid vault address")]
+    InvalidVaultAddress,
+    // 10
+    #[error("Invalid NFT details account")]
+    InvalidDataAccount,
+    #[error("NFT owner doesn't match")]
+    OwnerMismatch,
+    #[error("NFT mint doesn't match")]
+    MintMismatch,
+    #[error("Lender doesn't match")]
+    LenderMismatch,
+    #[error("NFT is not in the vault")]
+    NftNotInVault,
+    // 15
+    #[error("Loan is already taken")]
+    LoanAlreadyTaken,
+    #[error("Loan is not taken")]
+    LoanNotTaken,
+    #[error("Loan is not offered")]
+    LoanNotOffered,
+    #[error("Loan is canceled")]
+    LoanCanceled,
+    #[error("Loan is still active")]
+    LoanStillActive,
+    // 20
+    #[error("Loan is expired")]
+    LoanExpired,
+    #[error("Loan is not expired")]
+    LoanNotExpired,
+    #[error("Invalid loan duration")]
+    InvalidLoanDuration,
+    #[error("Offer is too low")]
+    OfferTooLow,
+    #[error("Invalid offer account")]
+    InvalidOfferAccount,
+    // 25
+    #[error("Offer already exists")]
+    OfferAlreadyExists,
+    #[error("Offer is already accepted")]
+    OfferAlreadyAccepted,
+    #[error("Repayment doesn't cover the debt")]
+    InsufficientRepayment,
+    #[error("Invalid protocol config account")]
+    InvalidConfigAccount,
+    #[error("Signer is not the admin")]
+    Unauthorized,
+    // 30
+    #[error("Signer is not the pending admin")]
+    NotPendingAdmin,
+    #[error("Fee exceeds 10000 basis points")]
+    InvalidFee,
+    #[error("Protocol is paused")]
+    ProtocolPaused,
+    #[error("Invalid program data account")]
+    InvalidProgramDataAccount,
+    #[error("Invalid price account")]
+    InvalidPriceAccount,
+    // 35
+    #[error("Price is stale")]
+    StalePrice,
+    #[error("Price confidence interval is too wide")]
+    PriceConfidenceTooWide,
+    #[error("Invalid price")]
+    InvalidPrice,
+    #[error("Offer exceeds the loan-to-value limit")]
+    OfferTooHigh,
+    #[error("Loan-to-value exceeds 10000 basis points")]
+    InvalidLtv,
+    // 40
+    #[error("Currency isn't allowed for the collection")]
+    CurrencyNotAllowed,
+    #[error("Currency mint doesn't match")]
+    CurrencyMismatch,
+    #[error("Too many loan currencies")]
+    TooManyLoanMints,
+    #[error("Invalid treasury account")]
+    InvalidTreasuryAccount,
+    #[error("Treasury balance is too low")]
+    InsufficientTreasury,
+    // 45
+    #[error("Creator doesn't match the metadata")]
+    CreatorMismatch,
+    #[error("Royalty exceeds 10000 basis points")]
+    InvalidRoyalty,
+    #[error("Creator isn't verified")]
+    UnverifiedCreator,
+    #[error("Metadata has no collection or creators")]
+    MissingCreators,
+    #[error("NFT mint isn't in the whitelist merkle tree")]
+    InvalidMerkleProof,
+    // 50
+    #[error("Merkle proof is too long")]
+    MerkleProofTooLong,
+    #[error("Collection has no liquidation auction")]
+    AuctionNotEnabled,
+    #[error("Auction already started")]
+    AuctionAlreadyStarted,
+    #[error("Auction isn't running")]
+    AuctionNotActive,
+    #[error("Auction is still running")]
+    AuctionActive,
+    // 55
+    #[error("Bid is below the auction price")]
+    BidBelowPrice,
+    #[error("Invalid auction config")]
+    InvalidAuctionConfig,
+    #[error("Invalid lending pool account")]
+    InvalidPoolAccount,
+    #[error("Pool doesn't have enough idle lamports")]
+    InsufficientLiquidity,
+    #[error("Lender doesn't own enough pool shares")]
+    InsufficientShares,
+    // 60
+    #[error("Amount is too small for a pool share")]
+    PoolAmountTooSmall,
+    #[error("Collection offer has no fills left")]
+    OfferFilled,
+    #[error("Bundle holds no NFTs or more than the maximum")]
+    InvalidBundleSize,
+    #[error("Escrowless loans can't be auctioned or lent by pools")]
+    EscrowlessNotSupported,
+    #[error("Interest rate exceeds the maximum")]
+    InvalidInterestRate,
+    // 65
+    #[error("Pool auction has no rounds left")]
+    AuctionRoundsExhausted,
 }
 impl From<TokenError> for ProgramError {
     fn from(e: TokenError) -> Self {
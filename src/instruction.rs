@@ -1,85 +1,1847 @@
 //! Instruction types
+use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
+    instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
-    msg
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
 };
-
+use spl_associated_token_account::get_associated_token_address;
 
 use crate::{
     error::TokenError,
+    state::{AuctionConfig, LoanCurrency, PriceConfig},
+    utils::{
+        derive_config_address, derive_manual_price_address, derive_metadata_address,
+        derive_edition_address, derive_pool_address, derive_program_data_address, derive_treasury_address,
+        derive_whitelist_address, generate_pda_and_bump_seed,
+    },
+    COLLECTION_OFFER, NFTPREFIX, OFFERPREFIX, POOL,
 };
-use std::convert::TryInto;
 
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessDeposit{
+    /// Requested loan term in seconds.
+    pub duration: u64,
+    /// SPL mint the loan is requested in, `None` for lamports.
+    pub currency_mint: Option<Pubkey>,
+    /// Merkle proof of the NFT mint, empty unless the whitelist has a merkle root.
+    pub proof: Vec<[u8; 32]>,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub struct ProcessOffer{
     pub amount: u64,
+    /// Loan term in seconds, `0` keeps the term requested by the borrower.
+    pub duration: u64,
 }
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub struct ProcessInterest{
     pub amount: u64,
 }
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessPartialRepay{
+    pub amount: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessPoolDeposit{
+    pub amount: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessPoolWithdraw{
+    pub shares: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessPoolBorrow{
+    pub amount: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessCollectionOffer{
+    pub amount: u64,
+    pub duration: u64,
+    /// Number of loans the offer funds
+    pub fills: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessAcceptCollectionOffer{
+    /// Merkle proof of the NFT mint when the whitelist has a merkle root
+    pub proof: Vec<[u8; 32]>,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessDepositAndBorrow{
+    /// Requested loan term in seconds.
+    pub duration: u64,
+    /// Lamports borrowed from the pool.
+    pub amount: u64,
+    /// Merkle proof of the NFT mint when the whitelist has a merkle root
+    pub proof: Vec<[u8; 32]>,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessBundleDeposit{
+    /// Lamports requested against the whole bundle.
+    pub amount: u64,
+    /// Requested loan term in seconds.
+    pub duration: u64,
+    /// Merkle proof of each NFT mint, empty unless its whitelist has a merkle root.
+    pub proofs: Vec<Vec<[u8; 32]>>,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessBundleRepay{
+    pub amount: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub struct ProcessWhitelist{
     pub number: u64,
+    pub price: PriceConfig,
+    /// Largest loan as a share of the collection price in basis points
+    pub max_ltv_bps: u64,
+    /// Keys the whitelist on a verified Metaplex collection mint instead of
+    /// the creators
+    pub collection: bool,
 }
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub struct ProcessUpdate{
+    /// Annual interest rate in basis points
     pub interest: u64,
 }
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessInitConfig{
+    pub fee_bps: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessUpdateConfig{
+    pub fee_bps: u64,
+    pub deposits_paused: bool,
+    pub loans_paused: bool,
+    /// Proposes a new admin which has to accept with `ProcessAcceptAdmin`
+    pub new_admin: Option<Pubkey>,
+    /// Withdraws the pending nomination, can't be combined with `new_admin`
+    pub clear_pending_admin: bool,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessUpdatePrice{
+    pub price: PriceConfig,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessPostPrice{
+    /// Collection price in the smallest unit of the currency
+    pub price: u64,
+    /// SPL mint the price is quoted in, `None` for lamports
+    pub currency_mint: Option<Pubkey>,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessUpdateLtv{
+    pub max_ltv_bps: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessUpdateLoanMints{
+    pub loan_mints: Vec<LoanCurrency>,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessExtend{
+    /// New loan term in seconds counted from the extension, at most `MAX_LOAN_DURATION`.
+    pub duration: u64,
+    /// New annual interest rate in basis points, at most `MAX_INTEREST_RATE_BPS`.
+    pub interest_rate_bps: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessUpdateGrace{
+    pub grace_period: u64,
+    pub late_fee_bps: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessUpdateAuction{
+    pub auction: Option<AuctionConfig>,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessBid{
+    /// Most the bidder pays, the auction price keeps falling until the bid lands
+    pub max_price: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessUpdateMerkleRoot{
+    pub merkle_root: Option<[u8; 32]>,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessUpdateRoyalty{
+    pub royalty_bps: u64,
+}
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProcessWithdrawFees{
+    pub amount: u64,
+    /// SPL mint of the fees to withdraw, `None` for lamports
+    pub currency_mint: Option<Pubkey>,
+}
 
+/// Instructions supported by the borrow-lend program.
+///
+/// The Borsh encoding is a one byte variant tag followed by the fields of the
+/// payload, so the tags below are part of the wire format.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub enum TokenInstruction {
-    ProcessDeposit, ///0
-    ProcessOffer(ProcessOffer), ///1
-    ProcessSelection, ///2
-    ProcessCancel,///3
-    ProcessInterest(ProcessInterest),///4
+    /// 0. Moves the NFT into the vault and creates its `NftDetails` account.
+    ///
+    ///   0. `[signer, writable]` NFT owner
+    ///   1. `[]` NFT mint
+    ///   2. `[writable]` NFT owner associated token account
+    ///   3. `[]` Token program
+    ///   4. `[signer, writable]` `NftDetails` data account
+    ///   5. `[]` NFT vault PDA
+    ///   6. `[writable]` NFT vault associated token account
+    ///   7. `[]` Associated token program
+    ///   8. `[]` Rent sysvar
+    ///   9. `[]` System program
+    ///   10. `[]` Metaplex metadata account
+    ///   11. `[]` Whitelist account of the collection
+    ///   12. `[]` Protocol config
+    ProcessDeposit(ProcessDeposit),
+    /// 1. Creates the lender's offer account, escrowing a loan of up to the
+    /// collection's loan-to-value against a deposited NFT and optionally
+    /// proposing a different loan term.
+    ///
+    ///   0. `[signer, writable]` Lender
+    ///   1. `[]` NFT owner
+    ///   2. `[]` NFT vault PDA
+    ///   3. `[]` NFT mint
+    ///   4. `[]` NFT vault associated token account
+    ///   5. `[]` System program
+    ///   6. `[]` Whitelist account of the collection
+    ///   7. `[writable]` `NftDetails` data account
+    ///   8. `[]` Protocol config
+    ///   9. `[writable]` Offer PDA of the lender
+    ///   10. `[]` Price account of the whitelist's price source
+    ///
+    ///   SPL loans also take:
+    ///
+    ///   11. `[]` Currency mint
+    ///   12. `[writable]` Lender token account of the currency
+    ///   13. `[writable]` Offer associated token account of the currency
+    ///   14. `[]` Token program
+    ///   15. `[]` Associated token program
+    ///   16. `[]` Rent sysvar
+    ProcessOffer(ProcessOffer),
+    /// 2. Accepts one offer and pays its escrowed loan to the NFT owner.
+    ///
+    ///   0. `[]` Lender of the selected offer
+    ///   1. `[signer, writable]` NFT owner
+    ///   2. `[]` NFT vault PDA
+    ///   3. `[]` NFT mint
+    ///   4. `[]` NFT vault associated token account
+    ///   5. `[]` System program
+    ///   6. `[writable]` `NftDetails` data account
+    ///   7. `[]` Protocol config
+    ///   8. `[writable]` Offer PDA of the lender
+    ///
+    ///   SPL loans also take:
+    ///
+    ///   9. `[writable]` Offer associated token account of the currency
+    ///   10. `[writable]` NFT owner token account of the currency
+    ///   11. `[]` Token program
+    ProcessSelection,
+    /// 3. Returns the NFT to its owner before a loan is taken.
+    ///
+    ///   0. `[signer, writable]` NFT owner
+    ///   1. `[]` NFT mint
+    ///   2. `[writable]` NFT owner associated token account
+    ///   3. `[]` Token program
+    ///   4. `[writable]` `NftDetails` data account
+    ///   5. `[writable]` NFT vault PDA
+    ///   6. `[writable]` NFT vault associated token account
+    ///   7. `[]` System program
+    ProcessCancel,
+    /// 4. Repays the loan with interest and releases the NFT to its owner.
+    ///
+    ///   0. `[signer, writable]` NFT owner
+    ///   1. `[]` NFT mint
+    ///   2. `[writable]` NFT owner associated token account
+    ///   3. `[]` Token program
+    ///   4. `[writable]` `NftDetails` data account
+    ///   5. `[writable]` NFT vault PDA
+    ///   6. `[writable]` NFT vault associated token account
+    ///   7. `[]` System program
+    ///   8. `[]` Whitelist account of the collection
+    ///   9. `[writable]` Lender
+    ///   10. `[]` Protocol config
+    ///   11. `[writable]` Treasury PDA
+    ///   12. `[]` NFT metadata account
+    ///
+    ///   SPL loans also take:
+    ///
+    ///   13. `[writable]` NFT owner token account of the currency
+    ///   14. `[writable]` Lender associated token account of the currency
+    ///   15. `[writable]` Treasury associated token account of the currency
+    ///   16. `[]` Currency mint
+    ///   17. `[]` Associated token program
+    ///   18. `[]` Rent sysvar
+    ///
+    ///   When the collection takes a royalty, the verified metadata creators
+    ///   follow in order, each as `[writable]` wallet and, for SPL loans, `[writable]`
+    ///   associated token account of the currency.
+    ProcessInterest(ProcessInterest),
+    /// 5. Transfers the NFT of a loan to the lender once its term and the
+    /// collection's grace period have passed.
+    ///
+    ///   0. `[]` NFT owner
+    ///   1. `[]` NFT mint
+    ///   2. `[]` Token program
+    ///   3. `[writable]` `NftDetails` data account
+    ///   4. `[]` NFT vault PDA
+    ///   5. `[writable]` NFT vault associated token account
+    ///   6. `[]` System program
+    ///   7. `[signer, writable]` Lender
+    ///   8. `[writable]` Lender associated token account
+    ///   9. `[]` Associated token program
+    ///   10. `[]` Rent sysvar
+    ///   11. `[]` Whitelist account of the collection
     ProcessLender,
+    /// 6. Creates the whitelist account of a collection, keyed on its verified
+    /// collection mint or on its first creator. Instructions below which take
+    /// the first creator take the collection mint for collection whitelists.
+    ///
+    ///   0. `[signer, writable]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[]` System program
+    ///   3. `[writable]` Whitelist account
+    ///   4. `[]` Collection mint, or first creator
+    ///   5. .. `[]` Remaining `number - 1` creators
     ProcessWhitelist(ProcessWhitelist),
+    /// 7. Turns whitelisting of a collection off.
+    ///
+    ///   0. `[signer]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[writable]` Whitelist account
+    ///   3. `[]` First creator
     ProcessRemoveWhitelist,
+    /// 8. Updates the annual interest rate of a collection in basis points.
+    ///
+    ///   0. `[signer]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[writable]` Whitelist account
+    ///   3. `[]` First creator
     ProcessUpdate(ProcessUpdate),
+    /// 9. Creates the protocol config with the signer as admin, and the
+    /// treasury which collects the protocol fee. Only the upgrade authority
+    /// of the program may sign.
+    ///
+    ///   0. `[signer, writable]` Admin
+    ///   1. `[writable]` Protocol config
+    ///   2. `[]` System program
+    ///   3. `[writable]` Treasury PDA
+    ///   4. `[]` Program data account of the upgradeable loader
+    ProcessInitConfig(ProcessInitConfig),
+    /// 10. Updates the fee and pause flags and optionally proposes a new admin
+    /// or withdraws the pending nomination.
+    ///
+    ///   0. `[signer]` Admin
+    ///   1. `[writable]` Protocol config
+    ProcessUpdateConfig(ProcessUpdateConfig),
+    /// 11. Makes the pending admin the admin of the protocol config.
+    ///
+    ///   0. `[signer]` Pending admin
+    ///   1. `[writable]` Protocol config
+    ProcessAcceptAdmin,
+    /// 12. Withdraws an offer which hasn't been accepted, or closes an accepted
+    /// one after its loan is settled, refunding the escrow and rent to the lender.
+    ///
+    ///   0. `[signer, writable]` Lender
+    ///   1. `[writable]` `NftDetails` data account
+    ///   2. `[writable]` Offer PDA of the lender
+    ///
+    ///   SPL loans also take:
+    ///
+    ///   3. `[writable]` Offer associated token account of the currency
+    ///   4. `[writable]` Lender token account of the currency
+    ///   5. `[]` Token program
+    ProcessWithdrawOffer,
+    /// 13. Changes the price source of a collection and its staleness and
+    /// confidence limits.
+    ///
+    ///   0. `[signer]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[writable]` Whitelist account
+    ///   3. `[]` First creator
+    ProcessUpdatePrice(ProcessUpdatePrice),
+    /// 14. Posts the price of a collection priced by `PriceSource::Manual`.
+    ///
+    ///   0. `[signer, writable]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[]` System program
+    ///   3. `[]` Whitelist account
+    ///   4. `[writable]` Manual price PDA of the whitelist
+    ///   5. `[]` First creator
+    ProcessPostPrice(ProcessPostPrice),
+    /// 15. Updates the loan-to-value limit of a collection in basis points.
+    ///
+    ///   0. `[signer]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[writable]` Whitelist account
+    ///   3. `[]` First creator
+    ProcessUpdateLtv(ProcessUpdateLtv),
+    /// 16. Replaces the SPL mints a collection can be borrowed against.
+    ///
+    ///   0. `[signer]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[writable]` Whitelist account
+    ///   3. `[]` First creator
+    ProcessUpdateLoanMints(ProcessUpdateLoanMints),
+    /// 17. Withdraws collected protocol fees from the treasury.
+    ///
+    ///   0. `[signer]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[writable]` Treasury PDA
+    ///   3. `[writable]` Receiver of lamport fees
+    ///
+    ///   SPL fees also take:
+    ///
+    ///   4. `[writable]` Treasury associated token account of the currency
+    ///   5. `[writable]` Receiver token account of the currency
+    ///   6. `[]` Token program
+    ProcessWithdrawFees(ProcessWithdrawFees),
+    /// 18. Updates the share of the interest paid to the creators of a
+    /// collection in basis points, taken after the protocol fee.
+    ///
+    ///   0. `[signer]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[writable]` Whitelist account
+    ///   3. `[]` First creator
+    ProcessUpdateRoyalty(ProcessUpdateRoyalty),
+    /// 19. Sets or clears the merkle root of the mints a collection accepts.
+    ///
+    ///   0. `[signer]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[writable]` Whitelist account
+    ///   3. `[]` First creator
+    ProcessUpdateMerkleRoot(ProcessUpdateMerkleRoot),
+    /// 20. Sets or disables the liquidation auction of a collection.
+    ///
+    ///   0. `[signer]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[writable]` Whitelist account
+    ///   3. `[]` First creator
+    ProcessUpdateAuction(ProcessUpdateAuction),
+    /// 21. Puts the NFT of an expired lamport loan up for auction instead of
+    /// foreclosing it. The lender may still foreclose once the auction ends unsold.
+    /// A pool writes the principal off and restarts unsold auctions for up to
+    /// `MAX_POOL_AUCTION_ROUNDS` rounds, the last one descending to zero.
+    ///
+    ///   0. `[signer]` Lender
+    ///   1. `[writable]` `NftDetails` data account
+    ///   2. `[]` Whitelist account of the collection
+    ProcessStartAuction,
+    /// 22. Buys an auctioned NFT at the current price, repaying the lender and
+    /// sending any surplus to the borrower. The interest part of the proceeds
+    /// pays the protocol fee and the creator royalty like a repayment.
+    ///
+    ///   0. `[signer, writable]` Bidder
+    ///   1. `[writable]` NFT owner
+    ///   2. `[]` NFT mint
+    ///   3. `[]` Token program
+    ///   4. `[writable]` `NftDetails` data account
+    ///   5. `[]` NFT vault PDA
+    ///   6. `[writable]` NFT vault associated token account
+    ///   7. `[]` System program
+    ///   8. `[writable]` Lender
+    ///   9. `[writable]` Bidder associated token account
+    ///   10. `[]` Associated token program
+    ///   11. `[]` Rent sysvar
+    ///   12. `[]` Whitelist account of the collection
+    ///   13. `[]` Protocol config
+    ///   14. `[writable]` Treasury PDA
+    ///   15. `[]` NFT metadata account
+    ///
+    ///   When the collection takes a royalty, the verified metadata creators
+    ///   follow in order as `[writable]` wallets.
+    ProcessBid(ProcessBid),
+    /// 23. Updates the grace period after the loan term of a collection and
+    /// the late fee charged for repaying during it.
+    ///
+    ///   0. `[signer]` Admin
+    ///   1. `[]` Protocol config
+    ///   2. `[writable]` Whitelist account
+    ///   3. `[]` First creator
+    ProcessUpdateGrace(ProcessUpdateGrace),
+    /// 24. Extends a running loan on terms agreed by the borrower and the
+    /// lender. The interest accrued so far is paid like a repayment, then the
+    /// loan restarts with the new term and rate while the NFT stays in the vault.
+    ///
+    ///   0. `[signer, writable]` NFT owner
+    ///   1. `[signer, writable]` Lender
+    ///   2. `[writable]` `NftDetails` data account
+    ///   3. `[]` Whitelist account of the collection
+    ///   4. `[]` NFT mint
+    ///   5. `[]` Token program
+    ///   6. `[]` System program
+    ///   7. `[]` Protocol config
+    ///   8. `[writable]` Treasury PDA
+    ///   9. `[]` NFT metadata account
+    ///
+    ///   SPL loans and royalty creators follow as in `ProcessInterest`.
+    ProcessExtend(ProcessExtend),
+    /// 25. Moves a running loan to the lender of an open offer. The escrow of
+    /// the offer pays off the principal and interest of the current lender,
+    /// the rest goes to the borrower and the loan restarts on the offer's terms.
+    ///
+    ///   0. `[signer, writable]` NFT owner
+    ///   1. `[]` New lender
+    ///   2. `[writable]` Current lender
+    ///   3. `[writable]` `NftDetails` data account
+    ///   4. `[writable]` Offer of the new lender
+    ///   5. `[]` Whitelist account of the collection
+    ///   6. `[]` NFT mint
+    ///   7. `[]` Token program
+    ///   8. `[]` System program
+    ///   9. `[]` Protocol config
+    ///   10. `[writable]` Treasury PDA
+    ///   11. `[]` NFT metadata account
+    ///
+    ///   SPL loans and royalty creators follow as in `ProcessInterest` with the
+    ///   offer paying, then for SPL loans:
+    ///   * `[writable]` Offer's associated token account
+    ///   * `[writable]` Owner's token account of the loan currency
+    ProcessRefinance,
+    /// 26. Pays `amount` towards a running loan before the end of its term.
+    /// The accrued interest is paid first and the rest lowers the outstanding
+    /// principal. Paying the whole balance repays the loan and releases the NFT.
+    ///
+    ///   Accounts as in `ProcessInterest`.
+    ProcessPartialRepay(ProcessPartialRepay),
+    /// 27. Deposits lamports into the lending pool of a collection for pool
+    /// shares, creating the pool and the lender's share account if needed.
+    ///
+    ///   0. `[signer, writable]` Lender
+    ///   1. `[writable]` Lending pool PDA
+    ///   2. `[writable]` Pool share PDA of the lender
+    ///   3. `[]` Whitelist account of the collection
+    ///   4. `[]` Protocol config
+    ///   5. `[]` System program
+    ProcessPoolDeposit(ProcessPoolDeposit),
+    /// 28. Redeems pool shares for their part of the pool's idle lamports.
+    ///
+    ///   0. `[signer, writable]` Lender
+    ///   1. `[writable]` Lending pool PDA
+    ///   2. `[writable]` Pool share PDA of the lender
+    ProcessPoolWithdraw(ProcessPoolWithdraw),
+    /// 29. Borrows lamports from the lending pool of the collection against a
+    /// deposited NFT at the collection's loan-to-value limit and rate, for a
+    /// term of at most `MAX_POOL_LOAN_DURATION`. The loan is repaid like any
+    /// other with the pool as the lender.
+    ///
+    ///   0. `[signer, writable]` NFT owner
+    ///   1. `[writable]` Lending pool PDA
+    ///   2. `[writable]` `NftDetails` data account
+    ///   3. `[]` NFT vault PDA
+    ///   4. `[]` NFT mint
+    ///   5. `[]` NFT vault associated token account
+    ///   6. `[]` Whitelist account of the collection
+    ///   7. `[]` Protocol config
+    ///   8. `[]` Price account of the whitelist's price source
+    ProcessPoolBorrow(ProcessPoolBorrow),
+    /// 30. Offers lamport loans on any NFT of a whitelisted collection. The
+    /// offer escrows `amount` for each of its `fills`.
+    ///
+    ///   0. `[signer, writable]` Lender
+    ///   1. `[writable]` Collection offer PDA
+    ///   2. `[]` Whitelist account of the collection
+    ///   3. `[]` Protocol config
+    ///   4. `[]` Price account of the whitelist's price source
+    ///   5. `[]` System program
+    ProcessCollectionOffer(ProcessCollectionOffer),
+    /// 31. Deposits an NFT and takes a loan from a collection offer on its
+    /// collection in one go.
+    ///
+    ///   0-12. Accounts as in `ProcessDeposit`
+    ///   13. `[writable]` Collection offer PDA
+    ProcessAcceptCollectionOffer(ProcessAcceptCollectionOffer),
+    /// 32. Closes a collection offer, refunding its unfilled loans and rent.
+    ///
+    ///   0. `[signer, writable]` Lender
+    ///   1. `[writable]` Collection offer PDA
+    ProcessWithdrawCollectionOffer,
+    /// 33. Deposits an NFT and borrows lamports from the collection's pool, or
+    /// from a collection offer lending `amount` for `duration`, in one go.
+    ///
+    ///   0-12. Accounts as in `ProcessDeposit`
+    ///   13. `[writable]` Lending pool PDA or collection offer PDA
+    ///   14. `[]` Price account of the whitelist's price source, when borrowing from the pool
+    ProcessDepositAndBorrow(ProcessDepositAndBorrow),
+    /// 34. Deposits up to `MAX_BUNDLE_NFTS` whitelisted NFTs, possibly of
+    /// different collections, as collateral of a single lamport loan of `amount`.
+    /// The bundle takes the highest interest rate and the shortest grace period
+    /// of its collections.
+    ///
+    ///   0. `[signer, writable]` NFT owner
+    ///   1. `[signer, writable]` Bundle loan account
+    ///   2. `[]` NFT vault PDA of the bundle
+    ///   3. `[]` Token program
+    ///   4. `[]` Associated token account program
+    ///   5. `[]` Rent sysvar
+    ///   6. `[]` System program
+    ///   7. `[]` Protocol config
+    ///   8.. For each NFT: `[]` mint, `[writable]` owner's token account, `[writable]` vault's associated token account, `[]` metadata and `[]` whitelist
+    ProcessBundleDeposit(ProcessBundleDeposit),
+    /// 35. Lends the amount a bundle asks for. The bundle may borrow the sum of
+    /// what its NFTs could borrow one by one.
+    ///
+    ///   0. `[signer, writable]` Lender
+    ///   1. `[writable]` NFT owner
+    ///   2. `[writable]` Bundle loan account
+    ///   3. `[]` Protocol config
+    ///   4. `[]` System program
+    ///   5.. For each NFT: `[]` whitelist and `[]` price account of the whitelist's price source
+    ProcessBundleLend,
+    /// 36. Returns the NFTs of a bundle nobody lent against.
+    ///
+    ///   0. `[signer, writable]` NFT owner
+    ///   1. `[writable]` Bundle loan account
+    ///   2. `[]` NFT vault PDA of the bundle
+    ///   3. `[]` Token program
+    ///   4. `[]` Associated token account program
+    ///   5. `[]` Rent sysvar
+    ///   6. `[]` System program
+    ///   7.. For each NFT: `[]` mint, `[writable]` vault's and `[writable]` owner's associated token accounts
+    ProcessBundleCancel,
+    /// 37. Repays a bundle loan with its interest and returns all its NFTs. The
+    /// protocol fee is taken, bundles pay no creator royalty or late fee.
+    ///
+    ///   0. `[signer, writable]` NFT owner
+    ///   1. `[writable]` Lender
+    ///   2. `[writable]` Bundle loan account
+    ///   3. `[]` NFT vault PDA of the bundle
+    ///   4. `[]` Token program
+    ///   5. `[]` Associated token account program
+    ///   6. `[]` Rent sysvar
+    ///   7. `[]` System program
+    ///   8. `[]` Protocol config
+    ///   9. `[writable]` Treasury PDA
+    ///   10. `[]` Metadata account of the first NFT
+    ///   11.. For each NFT: `[]` mint, `[writable]` vault's and `[writable]` owner's associated token accounts
+    ProcessBundleRepay(ProcessBundleRepay),
+    /// 38. Hands every NFT of a defaulted bundle to the lender once the term and
+    /// grace period have passed.
+    ///
+    ///   0. `[signer, writable]` Lender
+    ///   1. `[]` NFT owner
+    ///   2. `[writable]` Bundle loan account
+    ///   3. `[]` NFT vault PDA of the bundle
+    ///   4. `[]` Token program
+    ///   5. `[]` Associated token account program
+    ///   6. `[]` Rent sysvar
+    ///   7. `[]` System program
+    ///   8.. For each NFT: `[]` mint, `[writable]` vault's and `[writable]` lender's associated token accounts
+    ProcessBundleForeclose,
+    /// 39. Like `ProcessDeposit` but the NFT stays in the owner's token account.
+    /// The vault PDA is approved as its delegate and freezes it through the
+    /// token metadata program until the loan is repaid, canceled or foreclosed.
+    /// Escrowless loans can't be auctioned or lent by pools.
+    ///
+    ///   0. `[signer, writable]` NFT owner
+    ///   1. `[]` NFT mint
+    ///   2. `[writable]` Owner's NFT token account
+    ///   3. `[]` Token program
+    ///   4. `[signer, writable]` NFT details account
+    ///   5. `[]` NFT vault PDA
+    ///   6. `[]` Master edition of the NFT
+    ///   7. `[]` Token metadata program
+    ///   8. `[]` System program
+    ///   9. `[]` Metadata account of the NFT
+    ///   10. `[]` Whitelist account of the collection
+    ///   11. `[]` Protocol config
+    ProcessDepositEscrowless(ProcessDeposit),
 }
 impl TokenInstruction {
     /// Unpacks a byte buffer into a [TokenInstruction](enum.TokenInstruction.html).
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        use TokenError::InvalidInstruction;
-        let (&tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
-        msg!("{:?}",input);
-        Ok(match tag {
-            // Initialize deposit NFT instruction 
-            0 => {
-                Self::ProcessDeposit
-            }
-            1 => {
-                let (amount, _rest) = rest.split_at(8);
-                let amount = amount.try_into().map(u64::from_le_bytes).or(Err(InvalidInstruction))?;
-                Self::ProcessOffer(ProcessOffer{amount})
-            }
-            2 => {
-                Self::ProcessSelection
-            }
-            3 => {
-                msg!("{:?}",rest);
-                Self::ProcessCancel
-            }
-            4 => {
-                let (amount, _rest) = rest.split_at(8);
-                let amount = amount.try_into().map(u64::from_le_bytes).or(Err(InvalidInstruction))?;
-                Self::ProcessInterest(ProcessInterest{amount})
-            }
-            5 => {
-                Self::ProcessLender
-            }
-            6 =>{
-                let (number, _rest) = rest.split_at(8);
-                let number = number.try_into().map(u64::from_le_bytes).or(Err(InvalidInstruction))?;
-                Self::ProcessWhitelist(ProcessWhitelist{number})
-            }
-            7 => {
-                Self::ProcessRemoveWhitelist
-            }
-            8 =>{
-                let (interest, _rest) = rest.split_at(8);
-                let interest = interest.try_into().map(u64::from_le_bytes).or(Err(InvalidInstruction))?;
-                Self::ProcessUpdate(ProcessUpdate{interest})
-            }
-
-            _ => return Err(TokenError::InvalidInstruction.into()),
+        Self::try_from_slice(input).map_err(|_| TokenError::InvalidInstruction.into())
+    }
+    /// Packs a [TokenInstruction](enum.TokenInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Result<Vec<u8>, ProgramError> {
+        self.try_to_vec().map_err(|_| TokenError::InvalidInstruction.into())
+    }
+}
+
+/// Creates a `ProcessDeposit` instruction.
+pub fn deposit(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    first_creator: &Pubkey,
+    duration: u64,
+    currency_mint: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    deposit_with_proof(
+        program_id,
+        nft_owner,
+        nft_mint,
+        data_account,
+        first_creator,
+        duration,
+        currency_mint,
+        Vec::new(),
+    )
+}
+
+/// Creates a `ProcessDeposit` instruction for a collection with a merkle root.
+pub fn deposit_with_proof(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    first_creator: &Pubkey,
+    duration: u64,
+    currency_mint: Option<&Pubkey>,
+    proof: Vec<[u8; 32]>,
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, data_account, program_id);
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*nft_owner, true),
+            AccountMeta::new_readonly(*nft_mint, false),
+            AccountMeta::new(get_associated_token_address(nft_owner, nft_mint), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*data_account, true),
+            AccountMeta::new_readonly(nft_vault, false),
+            AccountMeta::new(get_associated_token_address(&nft_vault, nft_mint), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(derive_metadata_address(nft_mint).0, false),
+            AccountMeta::new_readonly(whitelist, false),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+        ],
+        data: TokenInstruction::ProcessDeposit(ProcessDeposit {
+            duration,
+            currency_mint: currency_mint.copied(),
+            proof,
+        })
+        .pack()?,
+    })
+}
+
+/// Creates a `ProcessOffer` instruction.
+pub fn offer(
+    program_id: &Pubkey,
+    lender: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    first_creator: &Pubkey,
+    price_account: &Pubkey,
+    amount: u64,
+    duration: u64,
+    currency_mint: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, data_account, program_id);
+    let (offer, _) = generate_pda_and_bump_seed(OFFERPREFIX, lender, data_account, program_id);
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*lender, true),
+        AccountMeta::new_readonly(*nft_owner, false),
+        AccountMeta::new_readonly(nft_vault, false),
+        AccountMeta::new_readonly(*nft_mint, false),
+        AccountMeta::new_readonly(get_associated_token_address(&nft_vault, nft_mint), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(whitelist, false),
+        AccountMeta::new(*data_account, false),
+        AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+        AccountMeta::new(offer, false),
+        AccountMeta::new_readonly(*price_account, false),
+    ];
+    if let Some(mint) = currency_mint {
+        accounts.extend(vec![
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(get_associated_token_address(lender, mint), false),
+            AccountMeta::new(get_associated_token_address(&offer, mint), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ]);
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessOffer(ProcessOffer { amount, duration }).pack()?,
+    })
+}
+
+/// Creates a `ProcessSelection` instruction.
+pub fn select(
+    program_id: &Pubkey,
+    lender: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    currency_mint: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, data_account, program_id);
+    let (offer, _) = generate_pda_and_bump_seed(OFFERPREFIX, lender, data_account, program_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*lender, false),
+        AccountMeta::new(*nft_owner, true),
+        AccountMeta::new_readonly(nft_vault, false),
+        AccountMeta::new_readonly(*nft_mint, false),
+        AccountMeta::new_readonly(get_associated_token_address(&nft_vault, nft_mint), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(*data_account, false),
+        AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+        AccountMeta::new(offer, false),
+    ];
+    if let Some(mint) = currency_mint {
+        accounts.extend(vec![
+            AccountMeta::new(get_associated_token_address(&offer, mint), false),
+            AccountMeta::new(get_associated_token_address(nft_owner, mint), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ]);
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessSelection.pack()?,
+    })
+}
+
+/// Creates a `ProcessCancel` instruction.
+pub fn cancel(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, data_account, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*nft_owner, true),
+            AccountMeta::new_readonly(*nft_mint, false),
+            AccountMeta::new(get_associated_token_address(nft_owner, nft_mint), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*data_account, false),
+            AccountMeta::new(nft_vault, false),
+            AccountMeta::new(get_associated_token_address(&nft_vault, nft_mint), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: TokenInstruction::ProcessCancel.pack()?,
+    })
+}
+
+/// Creates a `ProcessInterest` instruction which repays the loan. `whitelist_key`
+/// is the collection mint or first creator keying the whitelist, `creators` are
+/// the verified metadata creators in order.
+pub fn repay(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    whitelist_key: &Pubkey,
+    creators: &[Pubkey],
+    lender: &Pubkey,
+    amount: u64,
+    currency_mint: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, data_account, program_id);
+    let (whitelist, _) = derive_whitelist_address(whitelist_key, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*nft_owner, true),
+        AccountMeta::new_readonly(*nft_mint, false),
+        AccountMeta::new(get_associated_token_address(nft_owner, nft_mint), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*data_account, false),
+        AccountMeta::new(nft_vault, false),
+        AccountMeta::new(get_associated_token_address(&nft_vault, nft_mint), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(whitelist, false),
+        AccountMeta::new(*lender, false),
+    ];
+    accounts.extend(interest_accounts(program_id, nft_owner, lender, nft_mint, creators, currency_mint));
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessInterest(ProcessInterest { amount }).pack()?,
+    })
+}
+
+/// Creates a `ProcessLender` instruction which forecloses an expired loan.
+pub fn foreclose(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    lender: &Pubkey,
+    whitelist: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, data_account, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*nft_owner, false),
+            AccountMeta::new_readonly(*nft_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*data_account, false),
+            AccountMeta::new_readonly(nft_vault, false),
+            AccountMeta::new(get_associated_token_address(&nft_vault, nft_mint), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(*lender, true),
+            AccountMeta::new(get_associated_token_address(lender, nft_mint), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(*whitelist, false),
+        ],
+        data: TokenInstruction::ProcessLender.pack()?,
+    })
+}
+
+/// Creates a `ProcessWhitelist` instruction for the collection made by `creators`.
+pub fn whitelist(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    creators: &[Pubkey],
+    price: PriceConfig,
+    max_ltv_bps: u64,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(&creators[0], program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(whitelist, false),
+    ];
+    accounts.extend(creators.iter().map(|c| AccountMeta::new_readonly(*c, false)));
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessWhitelist(ProcessWhitelist {
+            number: creators.len() as u64,
+            price,
+            max_ltv_bps,
+            collection: false,
+        })
+        .pack()?,
+    })
+}
+
+/// Creates a `ProcessWhitelist` instruction keyed on a verified collection mint.
+pub fn whitelist_collection(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    collection_mint: &Pubkey,
+    price: PriceConfig,
+    max_ltv_bps: u64,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(collection_mint, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new_readonly(*collection_mint, false),
+        ],
+        data: TokenInstruction::ProcessWhitelist(ProcessWhitelist {
+            number: 1,
+            price,
+            max_ltv_bps,
+            collection: true,
+        })
+        .pack()?,
+    })
+}
+
+/// Creates a `ProcessRemoveWhitelist` instruction.
+pub fn remove_whitelist(program_id: &Pubkey, admin: &Pubkey, first_creator: &Pubkey) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new_readonly(*first_creator, false),
+        ],
+        data: TokenInstruction::ProcessRemoveWhitelist.pack()?,
+    })
+}
+
+/// Creates a `ProcessUpdate` instruction which sets the annual interest rate in basis points.
+pub fn update_interest(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    first_creator: &Pubkey,
+    interest: u64,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new_readonly(*first_creator, false),
+        ],
+        data: TokenInstruction::ProcessUpdate(ProcessUpdate { interest }).pack()?,
+    })
+}
+
+/// Creates a `ProcessInitConfig` instruction making `admin`, the upgrade
+/// authority of the program, the protocol admin.
+pub fn initialize_config(program_id: &Pubkey, admin: &Pubkey, fee_bps: u64) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new(derive_config_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(derive_treasury_address(program_id).0, false),
+            AccountMeta::new_readonly(derive_program_data_address(program_id).0, false),
+        ],
+        data: TokenInstruction::ProcessInitConfig(ProcessInitConfig { fee_bps }).pack()?,
+    })
+}
+
+/// Creates a `ProcessUpdateConfig` instruction.
+pub fn update_config(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    update: ProcessUpdateConfig,
+) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(derive_config_address(program_id).0, false),
+        ],
+        data: TokenInstruction::ProcessUpdateConfig(update).pack()?,
+    })
+}
+
+/// Creates a `ProcessAcceptAdmin` instruction signed by the pending admin.
+pub fn accept_admin(program_id: &Pubkey, new_admin: &Pubkey) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*new_admin, true),
+            AccountMeta::new(derive_config_address(program_id).0, false),
+        ],
+        data: TokenInstruction::ProcessAcceptAdmin.pack()?,
+    })
+}
+
+/// Creates a `ProcessWithdrawOffer` instruction.
+pub fn withdraw_offer(
+    program_id: &Pubkey,
+    lender: &Pubkey,
+    data_account: &Pubkey,
+    currency_mint: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let (offer, _) = generate_pda_and_bump_seed(OFFERPREFIX, lender, data_account, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*lender, true),
+        AccountMeta::new(*data_account, false),
+        AccountMeta::new(offer, false),
+    ];
+    if let Some(mint) = currency_mint {
+        accounts.extend(vec![
+            AccountMeta::new(get_associated_token_address(&offer, mint), false),
+            AccountMeta::new(get_associated_token_address(lender, mint), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ]);
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessWithdrawOffer.pack()?,
+    })
+}
+
+/// Creates a `ProcessUpdatePrice` instruction.
+pub fn update_price_source(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    first_creator: &Pubkey,
+    price: PriceConfig,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new_readonly(*first_creator, false),
+        ],
+        data: TokenInstruction::ProcessUpdatePrice(ProcessUpdatePrice { price }).pack()?,
+    })
+}
+
+/// Creates a `ProcessPostPrice` instruction which posts the collection price
+/// in `currency_mint`, or in lamports for `None`.
+pub fn post_price(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    first_creator: &Pubkey,
+    price: u64,
+    currency_mint: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(whitelist, false),
+            AccountMeta::new(derive_manual_price_address(&whitelist, currency_mint, program_id).0, false),
+            AccountMeta::new_readonly(*first_creator, false),
+        ],
+        data: TokenInstruction::ProcessPostPrice(ProcessPostPrice {
+            price,
+            currency_mint: currency_mint.copied(),
+        })
+        .pack()?,
+    })
+}
+
+/// Creates a `ProcessUpdateLtv` instruction.
+pub fn update_ltv(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    first_creator: &Pubkey,
+    max_ltv_bps: u64,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new_readonly(*first_creator, false),
+        ],
+        data: TokenInstruction::ProcessUpdateLtv(ProcessUpdateLtv { max_ltv_bps }).pack()?,
+    })
+}
+
+/// Creates a `ProcessUpdateLoanMints` instruction.
+pub fn update_loan_mints(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    first_creator: &Pubkey,
+    loan_mints: Vec<LoanCurrency>,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new_readonly(*first_creator, false),
+        ],
+        data: TokenInstruction::ProcessUpdateLoanMints(ProcessUpdateLoanMints { loan_mints }).pack()?,
+    })
+}
+
+/// Creates a `ProcessUpdateGrace` instruction.
+pub fn update_grace(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    first_creator: &Pubkey,
+    grace_period: u64,
+    late_fee_bps: u64,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new_readonly(*first_creator, false),
+        ],
+        data: TokenInstruction::ProcessUpdateGrace(ProcessUpdateGrace {
+            grace_period,
+            late_fee_bps,
         })
+        .pack()?,
+    })
+}
+
+/// Creates a `ProcessUpdateAuction` instruction.
+pub fn update_auction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    first_creator: &Pubkey,
+    auction: Option<AuctionConfig>,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new_readonly(*first_creator, false),
+        ],
+        data: TokenInstruction::ProcessUpdateAuction(ProcessUpdateAuction { auction }).pack()?,
+    })
+}
+
+/// Creates a `ProcessStartAuction` instruction.
+pub fn start_auction(program_id: &Pubkey, lender: &Pubkey, data_account: &Pubkey, whitelist: &Pubkey) -> Result<Instruction, ProgramError> {
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*lender, true),
+            AccountMeta::new(*data_account, false),
+            AccountMeta::new_readonly(*whitelist, false),
+        ],
+        data: TokenInstruction::ProcessStartAuction.pack()?,
+    })
+}
+
+/// Creates a `ProcessBid` instruction. `whitelist_key` is the collection mint
+/// or first creator keying the whitelist, `creators` are the verified metadata
+/// creators in order.
+pub fn bid(
+    program_id: &Pubkey,
+    bidder: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    whitelist_key: &Pubkey,
+    creators: &[Pubkey],
+    lender: &Pubkey,
+    max_price: u64,
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, data_account, program_id);
+    let (whitelist, _) = derive_whitelist_address(whitelist_key, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*bidder, true),
+        AccountMeta::new(*nft_owner, false),
+        AccountMeta::new_readonly(*nft_mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*data_account, false),
+        AccountMeta::new_readonly(nft_vault, false),
+        AccountMeta::new(get_associated_token_address(&nft_vault, nft_mint), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(*lender, false),
+        AccountMeta::new(get_associated_token_address(bidder, nft_mint), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(whitelist, false),
+    ];
+    accounts.extend(interest_accounts(program_id, bidder, lender, nft_mint, creators, None));
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessBid(ProcessBid { max_price }).pack()?,
+    })
+}
+
+/// Creates a `ProcessUpdateMerkleRoot` instruction.
+pub fn update_merkle_root(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    first_creator: &Pubkey,
+    merkle_root: Option<[u8; 32]>,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new_readonly(*first_creator, false),
+        ],
+        data: TokenInstruction::ProcessUpdateMerkleRoot(ProcessUpdateMerkleRoot { merkle_root }).pack()?,
+    })
+}
+
+/// Creates a `ProcessUpdateRoyalty` instruction.
+pub fn update_royalty(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    first_creator: &Pubkey,
+    royalty_bps: u64,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new(whitelist, false),
+            AccountMeta::new_readonly(*first_creator, false),
+        ],
+        data: TokenInstruction::ProcessUpdateRoyalty(ProcessUpdateRoyalty { royalty_bps }).pack()?,
+    })
+}
+
+/// Creates a `ProcessWithdrawFees` instruction paying treasury fees to `destination`,
+/// the owner of the receiving token account for SPL fees.
+pub fn withdraw_fees(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+    currency_mint: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let (treasury, _) = derive_treasury_address(program_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+        AccountMeta::new(treasury, false),
+        AccountMeta::new(*destination, false),
+    ];
+    if let Some(mint) = currency_mint {
+        accounts.extend(vec![
+            AccountMeta::new(get_associated_token_address(&treasury, mint), false),
+            AccountMeta::new(get_associated_token_address(destination, mint), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ]);
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessWithdrawFees(ProcessWithdrawFees {
+            amount,
+            currency_mint: currency_mint.copied(),
+        })
+        .pack()?,
+    })
+}
+
+/// Creates a `ProcessExtend` instruction signed by both the borrower and the lender.
+pub fn extend(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    lender: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    whitelist: &Pubkey,
+    creators: &[Pubkey],
+    duration: u64,
+    interest_rate_bps: u64,
+    currency_mint: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*nft_owner, true),
+        AccountMeta::new(*lender, true),
+        AccountMeta::new(*data_account, false),
+        AccountMeta::new_readonly(*whitelist, false),
+        AccountMeta::new_readonly(*nft_mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(interest_accounts(program_id, nft_owner, lender, nft_mint, creators, currency_mint));
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessExtend(ProcessExtend {
+            duration,
+            interest_rate_bps,
+        })
+        .pack()?,
+    })
+}
+
+/// Accounts which follow the lender when interest is paid: protocol config,
+/// treasury, metadata, SPL accounts of the currency and royalty creators.
+fn interest_accounts(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    lender: &Pubkey,
+    nft_mint: &Pubkey,
+    creators: &[Pubkey],
+    currency_mint: Option<&Pubkey>,
+) -> Vec<AccountMeta> {
+    let (treasury, _) = derive_treasury_address(program_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+        AccountMeta::new(treasury, false),
+        AccountMeta::new_readonly(derive_metadata_address(nft_mint).0, false),
+    ];
+    if let Some(mint) = currency_mint {
+        accounts.extend(vec![
+            AccountMeta::new(get_associated_token_address(payer, mint), false),
+            AccountMeta::new(get_associated_token_address(lender, mint), false),
+            AccountMeta::new(get_associated_token_address(&treasury, mint), false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ]);
+    }
+    for creator in creators {
+        accounts.push(AccountMeta::new(*creator, false));
+        if let Some(mint) = currency_mint {
+            accounts.push(AccountMeta::new(get_associated_token_address(creator, mint), false));
+        }
     }
-}
\ No newline at end of file
+    accounts
+}
+
+/// Creates a `ProcessRefinance` instruction moving the loan to the offer of `new_lender`.
+pub fn refinance(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    new_lender: &Pubkey,
+    lender: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    whitelist: &Pubkey,
+    creators: &[Pubkey],
+    currency_mint: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let (offer, _) = generate_pda_and_bump_seed(OFFERPREFIX, new_lender, data_account, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*nft_owner, true),
+        AccountMeta::new_readonly(*new_lender, false),
+        AccountMeta::new(*lender, false),
+        AccountMeta::new(*data_account, false),
+        AccountMeta::new(offer, false),
+        AccountMeta::new_readonly(*whitelist, false),
+        AccountMeta::new_readonly(*nft_mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(interest_accounts(program_id, &offer, lender, nft_mint, creators, currency_mint));
+    if let Some(mint) = currency_mint {
+        accounts.extend(vec![
+            AccountMeta::new(get_associated_token_address(&offer, mint), false),
+            AccountMeta::new(get_associated_token_address(nft_owner, mint), false),
+        ]);
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessRefinance.pack()?,
+    })
+}
+
+/// Creates a `ProcessPartialRepay` instruction, taking the accounts of `repay`.
+pub fn partial_repay(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    whitelist_key: &Pubkey,
+    creators: &[Pubkey],
+    lender: &Pubkey,
+    amount: u64,
+    currency_mint: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let mut ix = repay(
+        program_id,
+        nft_owner,
+        nft_mint,
+        data_account,
+        whitelist_key,
+        creators,
+        lender,
+        amount,
+        currency_mint,
+    )?;
+    ix.data = TokenInstruction::ProcessPartialRepay(ProcessPartialRepay { amount }).pack()?;
+    Ok(ix)
+}
+
+/// Creates a `ProcessPoolDeposit` instruction.
+pub fn pool_deposit(
+    program_id: &Pubkey,
+    lender: &Pubkey,
+    whitelist: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let (pool, _) = derive_pool_address(whitelist, program_id);
+    let (share, _) = generate_pda_and_bump_seed(POOL, &pool, lender, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*lender, true),
+            AccountMeta::new(pool, false),
+            AccountMeta::new(share, false),
+            AccountMeta::new_readonly(*whitelist, false),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: TokenInstruction::ProcessPoolDeposit(ProcessPoolDeposit { amount }).pack()?,
+    })
+}
+
+/// Creates a `ProcessPoolWithdraw` instruction.
+pub fn pool_withdraw(
+    program_id: &Pubkey,
+    lender: &Pubkey,
+    whitelist: &Pubkey,
+    shares: u64,
+) -> Result<Instruction, ProgramError> {
+    let (pool, _) = derive_pool_address(whitelist, program_id);
+    let (share, _) = generate_pda_and_bump_seed(POOL, &pool, lender, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*lender, true),
+            AccountMeta::new(pool, false),
+            AccountMeta::new(share, false),
+        ],
+        data: TokenInstruction::ProcessPoolWithdraw(ProcessPoolWithdraw { shares }).pack()?,
+    })
+}
+
+/// Creates a `ProcessPoolBorrow` instruction.
+pub fn pool_borrow(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    whitelist: &Pubkey,
+    price_account: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, data_account, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*nft_owner, true),
+            AccountMeta::new(derive_pool_address(whitelist, program_id).0, false),
+            AccountMeta::new(*data_account, false),
+            AccountMeta::new_readonly(nft_vault, false),
+            AccountMeta::new_readonly(*nft_mint, false),
+            AccountMeta::new_readonly(get_associated_token_address(&nft_vault, nft_mint), false),
+            AccountMeta::new_readonly(*whitelist, false),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new_readonly(*price_account, false),
+        ],
+        data: TokenInstruction::ProcessPoolBorrow(ProcessPoolBorrow { amount }).pack()?,
+    })
+}
+
+/// Creates a `ProcessCollectionOffer` instruction.
+pub fn collection_offer(
+    program_id: &Pubkey,
+    lender: &Pubkey,
+    first_creator: &Pubkey,
+    price_account: &Pubkey,
+    amount: u64,
+    duration: u64,
+    fills: u64,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    let (offer, _) = generate_pda_and_bump_seed(COLLECTION_OFFER, &whitelist, lender, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*lender, true),
+            AccountMeta::new(offer, false),
+            AccountMeta::new_readonly(whitelist, false),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+            AccountMeta::new_readonly(*price_account, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: TokenInstruction::ProcessCollectionOffer(ProcessCollectionOffer {
+            amount,
+            duration,
+            fills,
+        })
+        .pack()?,
+    })
+}
+
+/// Creates a `ProcessAcceptCollectionOffer` instruction, taking the accounts of
+/// `deposit_with_proof` and the offer of `lender`.
+pub fn accept_collection_offer(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    first_creator: &Pubkey,
+    lender: &Pubkey,
+    proof: Vec<[u8; 32]>,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    let (offer, _) = generate_pda_and_bump_seed(COLLECTION_OFFER, &whitelist, lender, program_id);
+    let mut ix = deposit_with_proof(program_id, nft_owner, nft_mint, data_account, first_creator, 0, None, vec![])?;
+    ix.accounts.push(AccountMeta::new(offer, false));
+    ix.data = TokenInstruction::ProcessAcceptCollectionOffer(ProcessAcceptCollectionOffer { proof }).pack()?;
+    Ok(ix)
+}
+
+/// Creates a `ProcessWithdrawCollectionOffer` instruction.
+pub fn withdraw_collection_offer(program_id: &Pubkey, lender: &Pubkey, first_creator: &Pubkey) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    let (offer, _) = generate_pda_and_bump_seed(COLLECTION_OFFER, &whitelist, lender, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*lender, true),
+            AccountMeta::new(offer, false),
+        ],
+        data: TokenInstruction::ProcessWithdrawCollectionOffer.pack()?,
+    })
+}
+
+/// Creates a `ProcessDepositAndBorrow` instruction, taking the accounts of
+/// `deposit_with_proof` and the pool of the collection.
+pub fn deposit_and_borrow(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    first_creator: &Pubkey,
+    price_account: &Pubkey,
+    duration: u64,
+    amount: u64,
+    proof: Vec<[u8; 32]>,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    let mut ix = deposit_with_proof(program_id, nft_owner, nft_mint, data_account, first_creator, duration, None, vec![])?;
+    ix.accounts.push(AccountMeta::new(derive_pool_address(&whitelist, program_id).0, false));
+    ix.accounts.push(AccountMeta::new_readonly(*price_account, false));
+    ix.data = TokenInstruction::ProcessDepositAndBorrow(ProcessDepositAndBorrow {
+        duration,
+        amount,
+        proof,
+    })
+    .pack()?;
+    Ok(ix)
+}
+
+/// Creates a `ProcessDepositAndBorrow` instruction borrowing from the
+/// collection offer of `lender`.
+pub fn deposit_and_borrow_from_offer(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    first_creator: &Pubkey,
+    lender: &Pubkey,
+    duration: u64,
+    amount: u64,
+    proof: Vec<[u8; 32]>,
+) -> Result<Instruction, ProgramError> {
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    let (offer, _) = generate_pda_and_bump_seed(COLLECTION_OFFER, &whitelist, lender, program_id);
+    let mut ix = deposit_with_proof(program_id, nft_owner, nft_mint, data_account, first_creator, duration, None, vec![])?;
+    ix.accounts.push(AccountMeta::new(offer, false));
+    ix.data = TokenInstruction::ProcessDepositAndBorrow(ProcessDepositAndBorrow {
+        duration,
+        amount,
+        proof,
+    })
+    .pack()?;
+    Ok(ix)
+}
+
+/// Accounts moving the NFTs of a bundle out of its vault to `recipient`.
+fn bundle_release_accounts(program_id: &Pubkey, nft_owner: &Pubkey, bundle: &Pubkey, recipient: &Pubkey, nft_mints: &[Pubkey]) -> Vec<AccountMeta> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, bundle, program_id);
+    let mut accounts = Vec::with_capacity(3 * nft_mints.len());
+    for nft_mint in nft_mints {
+        accounts.push(AccountMeta::new_readonly(*nft_mint, false));
+        accounts.push(AccountMeta::new(get_associated_token_address(&nft_vault, nft_mint), false));
+        accounts.push(AccountMeta::new(get_associated_token_address(recipient, nft_mint), false));
+    }
+    accounts
+}
+
+/// Creates a `ProcessBundleDeposit` instruction. `nfts` pairs each NFT mint
+/// with the first creator keying its whitelist.
+pub fn bundle_deposit(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    bundle: &Pubkey,
+    nfts: &[(Pubkey, Pubkey)],
+    amount: u64,
+    duration: u64,
+    proofs: Vec<Vec<[u8; 32]>>,
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, bundle, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*nft_owner, true),
+        AccountMeta::new(*bundle, true),
+        AccountMeta::new_readonly(nft_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+    ];
+    for (nft_mint, first_creator) in nfts {
+        accounts.push(AccountMeta::new_readonly(*nft_mint, false));
+        accounts.push(AccountMeta::new(get_associated_token_address(nft_owner, nft_mint), false));
+        accounts.push(AccountMeta::new(get_associated_token_address(&nft_vault, nft_mint), false));
+        accounts.push(AccountMeta::new_readonly(derive_metadata_address(nft_mint).0, false));
+        accounts.push(AccountMeta::new_readonly(derive_whitelist_address(first_creator, program_id).0, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessBundleDeposit(ProcessBundleDeposit {
+            amount,
+            duration,
+            proofs,
+        })
+        .pack()?,
+    })
+}
+
+/// Creates a `ProcessBundleLend` instruction. `collections` pairs the first
+/// creator keying each NFT's whitelist with its price account, in bundle order.
+pub fn bundle_lend(
+    program_id: &Pubkey,
+    lender: &Pubkey,
+    nft_owner: &Pubkey,
+    bundle: &Pubkey,
+    collections: &[(Pubkey, Pubkey)],
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*lender, true),
+        AccountMeta::new(*nft_owner, false),
+        AccountMeta::new(*bundle, false),
+        AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    for (first_creator, price_account) in collections {
+        accounts.push(AccountMeta::new_readonly(derive_whitelist_address(first_creator, program_id).0, false));
+        accounts.push(AccountMeta::new_readonly(*price_account, false));
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessBundleLend.pack()?,
+    })
+}
+
+/// Creates a `ProcessBundleCancel` instruction.
+pub fn bundle_cancel(program_id: &Pubkey, nft_owner: &Pubkey, bundle: &Pubkey, nft_mints: &[Pubkey]) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, bundle, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*nft_owner, true),
+        AccountMeta::new(*bundle, false),
+        AccountMeta::new_readonly(nft_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(bundle_release_accounts(program_id, nft_owner, bundle, nft_owner, nft_mints));
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessBundleCancel.pack()?,
+    })
+}
+
+/// Creates a `ProcessBundleRepay` instruction.
+pub fn bundle_repay(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    lender: &Pubkey,
+    bundle: &Pubkey,
+    nft_mints: &[Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, bundle, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*nft_owner, true),
+        AccountMeta::new(*lender, false),
+        AccountMeta::new(*bundle, false),
+        AccountMeta::new_readonly(nft_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+        AccountMeta::new(derive_treasury_address(program_id).0, false),
+        AccountMeta::new_readonly(derive_metadata_address(&nft_mints[0]).0, false),
+    ];
+    accounts.extend(bundle_release_accounts(program_id, nft_owner, bundle, nft_owner, nft_mints));
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessBundleRepay(ProcessBundleRepay { amount }).pack()?,
+    })
+}
+
+/// Creates a `ProcessBundleForeclose` instruction.
+pub fn bundle_foreclose(
+    program_id: &Pubkey,
+    lender: &Pubkey,
+    nft_owner: &Pubkey,
+    bundle: &Pubkey,
+    nft_mints: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, bundle, program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*lender, true),
+        AccountMeta::new_readonly(*nft_owner, false),
+        AccountMeta::new(*bundle, false),
+        AccountMeta::new_readonly(nft_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(bundle_release_accounts(program_id, nft_owner, bundle, lender, nft_mints));
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: TokenInstruction::ProcessBundleForeclose.pack()?,
+    })
+}
+
+/// Creates a `ProcessDepositEscrowless` instruction.
+pub fn deposit_escrowless(
+    program_id: &Pubkey,
+    nft_owner: &Pubkey,
+    nft_mint: &Pubkey,
+    data_account: &Pubkey,
+    first_creator: &Pubkey,
+    duration: u64,
+    currency_mint: Option<&Pubkey>,
+    proof: Vec<[u8; 32]>,
+) -> Result<Instruction, ProgramError> {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, data_account, program_id);
+    let (whitelist, _) = derive_whitelist_address(first_creator, program_id);
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*nft_owner, true),
+            AccountMeta::new_readonly(*nft_mint, false),
+            AccountMeta::new(get_associated_token_address(nft_owner, nft_mint), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(*data_account, true),
+            AccountMeta::new_readonly(nft_vault, false),
+            AccountMeta::new_readonly(derive_edition_address(nft_mint).0, false),
+            AccountMeta::new_readonly(spl_token_metadata::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(derive_metadata_address(nft_mint).0, false),
+            AccountMeta::new_readonly(whitelist, false),
+            AccountMeta::new_readonly(derive_config_address(program_id).0, false),
+        ],
+        data: TokenInstruction::ProcessDepositEscrowless(ProcessDeposit {
+            duration,
+            currency_mint: currency_mint.copied(),
+            proof,
+        })
+        .pack()?,
+    })
+}
+
+/// Adapts an `offer`, `select`, `cancel`, `repay`, `partial_repay` or
+/// `foreclose` instruction to an escrowless loan. The owner's token account
+/// takes the place of the vault's, and the master edition and the token
+/// metadata program the thaw needs are appended.
+pub fn escrowless(mut ix: Instruction, nft_owner: &Pubkey, nft_mint: &Pubkey, data_account: &Pubkey) -> Instruction {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, nft_owner, data_account, &ix.program_id);
+    let vault_token_account = get_associated_token_address(&nft_vault, nft_mint);
+    for account in ix.accounts.iter_mut().filter(|a| a.pubkey == vault_token_account) {
+        account.pubkey = get_associated_token_address(nft_owner, nft_mint);
+    }
+    ix.accounts.push(AccountMeta::new_readonly(derive_edition_address(nft_mint).0, false));
+    ix.accounts.push(AccountMeta::new_readonly(spl_token_metadata::id(), false));
+    ix
+}
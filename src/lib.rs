@@ -3,6 +3,7 @@ pub mod error;
 pub mod instruction;
 pub mod utils;
 pub mod state;
+pub mod oracle;
 use crate::{
     processor::Processor,
     error::TokenError
@@ -13,7 +14,36 @@ use solana_program::{
     program_error::PrintProgramError,
 };
 pub const NFTPREFIX: &str = "nft";
+pub const OFFERPREFIX: &str = "offer";
 pub const WHITELIST: &str = "whitelist";
+pub const CONFIG: &str = "config";
+pub const PRICE: &str = "price";
+pub const TREASURY: &str = "treasury";
+pub const POOL: &str = "pool";
+pub const COLLECTION_OFFER: &str = "collection_offer";
+pub const SECONDS_PER_DAY: u64 = 86400;
+pub const SECONDS_PER_YEAR: u64 = 365 * SECONDS_PER_DAY;
+pub const BASIS_POINTS: u64 = 10_000;
+pub const NATIVE_DECIMALS: u8 = 9;
+/// Room reserved in a whitelist account for SPL loan currencies
+pub const MAX_LOAN_MINTS: usize = 4;
+/// Deepest whitelist merkle tree a deposit can prove a mint against
+pub const MAX_MERKLE_PROOF_LEN: usize = 24;
+/// Most NFTs a bundle loan can hold, bounded by the accounts of a transaction
+pub const MAX_BUNDLE_NFTS: usize = 4;
+/// Longest loan term a borrower or lender can agree on
+pub const MAX_LOAN_DURATION: u64 = 365 * SECONDS_PER_DAY;
+/// Highest annual interest rate of a collection or a loan extension, 1000%
+pub const MAX_INTEREST_RATE_BPS: u64 = 10 * BASIS_POINTS;
+/// Longest term a lending pool lends for, so its defaults can be auctioned
+pub const MAX_POOL_LOAN_DURATION: u64 = 30 * SECONDS_PER_DAY;
+/// Rounds a defaulted pool loan is auctioned for, the last one descending to
+/// a price of zero
+pub const MAX_POOL_AUCTION_ROUNDS: u8 = 3;
+/// Shares per lamport minted by an empty pool. The pool is priced with this
+/// many virtual shares backed by one virtual lamport, so lamports sent to the
+/// pool outside a deposit can't inflate the share price against depositors.
+pub const POOL_VIRTUAL_SHARES: u64 = 1_000;
 
 entrypoint!(process_instruction);
 fn process_instruction(
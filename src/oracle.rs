@@ -0,0 +1,110 @@
+//! Collection price feeds used to value an NFT when a lender makes an offer
+use solana_program::{
+    pubkey::Pubkey,
+    account_info::AccountInfo,
+    program_error::ProgramError,
+    msg,
+};
+use mokshyafeed::Price;
+use std::convert::TryFrom;
+use crate::{
+    error::TokenError,
+    state::{PriceConfig,PriceSource,FloorPrice,ManualPrice},
+    utils::derive_manual_price_address,
+    BASIS_POINTS,
+};
+
+/// Returns the collection price in the smallest unit of the loan currency
+/// from the source configured on the whitelist, failing if it is stale or
+/// not precise enough. `decimals` are the decimals of the loan currency.
+pub fn collection_price(
+    program_id: &Pubkey,
+    whitelist: &Pubkey,
+    currency_mint: Option<&Pubkey>,
+    decimals: u8,
+    config: &PriceConfig,
+    price_info: &AccountInfo,
+    now: u64,
+) -> Result<u64, ProgramError> {
+    let (price, update_time) = match &config.source
+    {
+        PriceSource::Mokshya { program, account } =>
+        {
+            check_price_account(price_info, account, program)?;
+            let feed = Price::from_account(price_info)?;
+            (feed.price, feed.update_time)
+        }
+        PriceSource::Floor { program, account } =>
+        {
+            check_price_account(price_info, account, program)?;
+            floor_price(&FloorPrice::from_account(price_info)?, config.max_confidence_bps, decimals)?
+        }
+        PriceSource::Manual =>
+        {
+            let (price_address, _) = derive_manual_price_address(whitelist, currency_mint, program_id);
+            check_price_account(price_info, &price_address, program_id)?;
+            let manual = ManualPrice::from_account(price_info)?;
+            (manual.price, manual.update_time)
+        }
+    };
+    let age = now.saturating_sub(update_time);
+    if age > config.max_age
+    {
+        msg!("The price was updated {} seconds ago",age);
+        return Err(TokenError::StalePrice.into());
+    }
+    if price==0
+    {
+        msg!("The price feed has no price");
+        return Err(TokenError::InvalidPrice.into());
+    }
+    Ok(price)
+}
+
+fn check_price_account(price_info: &AccountInfo, address: &Pubkey, owner: &Pubkey) -> Result<(), ProgramError> {
+    if price_info.key!=address
+    {
+        msg!("The price account doesn't match the whitelist");
+        return Err(TokenError::InvalidPriceAccount.into());
+    }
+    if price_info.owner!=owner
+    {
+        msg!("The price account isn't owned by the price program");
+        return Err(TokenError::InvalidPriceAccount.into());
+    }
+    Ok(())
+}
+
+/// Checks the confidence of a floor price and converts it to the smallest
+/// unit of the loan currency, returns the price and its publish time.
+fn floor_price(feed: &FloorPrice, max_confidence_bps: u64, decimals: u8) -> Result<(u64, u64), ProgramError> {
+    if feed.price<=0 || feed.publish_time<0
+    {
+        msg!("The floor price is negative");
+        return Err(TokenError::InvalidPrice.into());
+    }
+    let price = feed.price as u128;
+    let confidence = (feed.conf as u128)
+        .checked_mul(BASIS_POINTS as u128)
+        .ok_or(TokenError::Overflow)?;
+    if confidence > price * max_confidence_bps as u128
+    {
+        msg!("The confidence interval {} is too wide for the price {}",feed.conf,feed.price);
+        return Err(TokenError::PriceConfidenceTooWide.into());
+    }
+    //the feed quotes whole units, scale it to the smallest unit
+    let exponent = feed.expo.checked_add(decimals as i32).ok_or(TokenError::Overflow)?;
+    let scale = 10u128
+        .checked_pow(exponent.unsigned_abs())
+        .ok_or(TokenError::Overflow)?;
+    let lamports = if exponent>=0
+    {
+        price.checked_mul(scale).ok_or(TokenError::Overflow)?
+    }
+    else
+    {
+        price / scale
+    };
+    let lamports = u64::try_from(lamports).map_err(|_| TokenError::Overflow)?;
+    Ok((lamports, feed.publish_time as u64))
+}
@@ -2,110 +2,132 @@ use crate::{
     error::TokenError,
     instruction::{
         TokenInstruction,
+        ProcessDeposit,
         ProcessOffer,
         ProcessInterest,
         ProcessWhitelist,
         ProcessUpdate,
+        ProcessInitConfig,
+        ProcessUpdateConfig,
+        ProcessUpdatePrice,
+        ProcessPostPrice,
+        ProcessUpdateLtv,
+        ProcessUpdateLoanMints,
+        ProcessWithdrawFees,
+        ProcessUpdateRoyalty,
+        ProcessUpdateMerkleRoot,
+        ProcessUpdateAuction,
+        ProcessBid,
+        ProcessUpdateGrace,
+        ProcessExtend,
+        ProcessPartialRepay,
+        ProcessPoolDeposit,
+        ProcessPoolWithdraw,
+        ProcessPoolBorrow,
+        ProcessCollectionOffer,
+        ProcessAcceptCollectionOffer,
+        ProcessDepositAndBorrow,
+        ProcessBundleDeposit,
+        ProcessBundleRepay,
     },
-    utils::{generate_pda_and_bump_seed,derive_whitelist_address,get_token_balance},
-    NFTPREFIX,WHITELIST,
-    state::{NftDetails,Whitelist}
+    utils::{generate_pda_and_bump_seed,derive_whitelist_address,derive_metadata_address,derive_config_address,derive_manual_price_address,get_token_balance,load_config,check_admin,upgrade_authority,transfer_lamports,close_account,max_loan_amount,check_associated_token_account,transfer_tokens,close_token_account,calculate_interest_share,calculate_creator_share,derive_treasury_address,verified_collection,merkle_leaf,verify_merkle_proof,auction_price,scale_bps,accrued_interest,repayment_quote,calculate_repayment,derive_pool_address,freeze_delegated_account,pool_shares,pool_amount,check_loan_duration,check_interest_rate},
+    oracle::collection_price,
+    NFTPREFIX,OFFERPREFIX,WHITELIST,CONFIG,PRICE,TREASURY,POOL,COLLECTION_OFFER,BASIS_POINTS,NATIVE_DECIMALS,MAX_LOAN_MINTS,MAX_MERKLE_PROOF_LEN,MAX_POOL_LOAN_DURATION,MAX_POOL_AUCTION_ROUNDS,MAX_BUNDLE_NFTS,
+    state::{NftDetails,Whitelist,ProtocolConfig,LoanOffer,PriceConfig,ManualPrice,LoanCurrency,Auction,AuctionConfig,LendingPool,PoolShare,CollectionOffer,BundleLoan,BundleNft}
 };
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::BorshSerialize;
 
 use solana_program::{
-    account_info::{AccountInfo,next_account_info},
+    account_info::{AccountInfo,next_account_info,next_account_infos},
     program_error::{PrintProgramError,ProgramError},
     decode_error::DecodeError,
     entrypoint::ProgramResult,
     program::{invoke,invoke_signed},
+    program_pack::Pack,
     system_instruction,
     pubkey::Pubkey,
     sysvar::{rent::Rent,Sysvar,clock::Clock},
     msg,
 };
+use solana_program::program_option::COption;
 use spl_associated_token_account::get_associated_token_address;
 use spl_token_metadata::state::{Metadata,Creator};
 use num_traits::FromPrimitive;
-use mokshyafeed::collection_price;
 
+/// Accounts of an NFT deposit, as listed for `ProcessDeposit`.
+struct DepositAccounts<'a, 'b> {
+    nft_owner: &'a AccountInfo<'b>, // sender or signer
+    nft_mint: &'a AccountInfo<'b>, // mint address of nft
+    nft_owner_nft_associated: &'a AccountInfo<'b>, // nft owner nft id token account address
+    token_program_id: &'a AccountInfo<'b>, // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+    pda: &'a AccountInfo<'b>, // pda data
+    nft_vault: &'a AccountInfo<'b>, // nft vault address from NFTPREFIX, nft_owner, pda and program id
+    nft_associated_address: &'a AccountInfo<'b>, // address generated from nft_vault_address and nft mint address token account address
+    associated_token_info: &'a AccountInfo<'b>, // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+    rent_info: &'a AccountInfo<'b>, // rent
+    system_program: &'a AccountInfo<'b>, //system program
+    meta_data_account: &'a AccountInfo<'b>,
+    whitelist_info: &'a AccountInfo<'b>,
+    config_info: &'a AccountInfo<'b>, // protocol config
+}
 
-const METAPLEX_PROGRAM_ID: &'static str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
 /// Program state handler.
 pub struct Processor {}
 impl Processor {
-    pub fn process_deposit_nft(program_id: &Pubkey,accounts: &[AccountInfo],)-> ProgramResult {
-        //depositing the NFT
+    pub fn process_deposit_nft(program_id: &Pubkey,accounts: &[AccountInfo],duration:u64,currency_mint:Option<Pubkey>,proof:Vec<[u8; 32]>)-> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let nft_owner =  next_account_info(account_info_iter)?; // sender or signer
-        let nft_mint = next_account_info(account_info_iter)?;  // mint address of nft
-        let nft_owner_nft_associated = next_account_info(account_info_iter)?;  // nft owner nft id token account address
-        let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
-        let pda = next_account_info(account_info_iter)?; // pda data 
-        let nft_vault = next_account_info(account_info_iter)?;  // nft vault address from NFTPREFIX, nft_owner, pda and program id
-        let nft_associated_address = next_account_info(account_info_iter)?; // address generated from nft_vault_address and nft mint address token account address
-        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
-        let rent_info  = next_account_info(account_info_iter)?; // rent 
-        let system_program = next_account_info(account_info_iter)?; //system program
-        let meta_data_account=next_account_info(account_info_iter)?;
-        let whitelist_info =next_account_info(account_info_iter)?; //
-
-        //metaplex program id
-        let metaplex_pid=METAPLEX_PROGRAM_ID
-        .parse::<Pubkey>()
-        .expect("Failed to parse Metaplex Program Id");
-
-        let seeds = &[
-        "metadata".as_bytes(),
-        metaplex_pid.as_ref(),
-        nft_mint.key.as_ref(),];
-
-        let (metadata_address, _) = Pubkey::find_program_address(seeds, &metaplex_pid);
-
-        //verifying the collection
-        if *meta_data_account.key!=metadata_address
-        {
-            msg!("The metadata account doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-        let metadata=Metadata::from_account_info(meta_data_account)?;
-        let mut creators: Vec<Creator> = Vec::new();
-        if let Some(c) = metadata.data.creators 
-        {
-            creators = c
-                .iter()
-                .map(|c| Creator {
-                                address: c.address,
-                                verified: c.verified,
-                                share: c.share,
-                                }).collect::<Vec<Creator>>();
-            }
-        if whitelist_info.owner!=program_id
-        {
-            msg!("Whitelist Info is not owned by the program");
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-        let first_creator=creators[0].address;
-        let (whitelist_address, _)=derive_whitelist_address(&first_creator, program_id);
+        let deposit = Self::next_deposit_accounts(account_info_iter)?;
+        Self::deposit_nft(program_id, &deposit, duration, currency_mint, &proof)
+    }
+    /// Reads the accounts of an NFT deposit off the instruction's accounts.
+    fn next_deposit_accounts<'a, 'b>(account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>) -> Result<DepositAccounts<'a, 'b>, ProgramError> {
+        Ok(DepositAccounts {
+            nft_owner: next_account_info(account_info_iter)?,
+            nft_mint: next_account_info(account_info_iter)?,
+            nft_owner_nft_associated: next_account_info(account_info_iter)?,
+            token_program_id: next_account_info(account_info_iter)?,
+            pda: next_account_info(account_info_iter)?,
+            nft_vault: next_account_info(account_info_iter)?,
+            nft_associated_address: next_account_info(account_info_iter)?,
+            associated_token_info: next_account_info(account_info_iter)?,
+            rent_info: next_account_info(account_info_iter)?,
+            system_program: next_account_info(account_info_iter)?,
+            meta_data_account: next_account_info(account_info_iter)?,
+            whitelist_info: next_account_info(account_info_iter)?,
+            config_info: next_account_info(account_info_iter)?,
+        })
+    }
+    /// Verifies the NFT against the whitelist and moves it into its vault,
+    /// recording the deposit in the data account.
+    fn deposit_nft(program_id: &Pubkey,deposit: &DepositAccounts,duration:u64,currency_mint:Option<Pubkey>,proof:&[[u8; 32]])-> ProgramResult {
+        //depositing the NFT
+        let DepositAccounts {
+            nft_owner,
+            nft_mint,
+            nft_owner_nft_associated,
+            token_program_id,
+            pda,
+            nft_vault,
+            nft_associated_address,
+            associated_token_info,
+            rent_info,
+            system_program,
+            meta_data_account,
+            whitelist_info,
+            config_info,
+        } = *deposit;
 
-        if whitelist_address!=*whitelist_info.key
-        {
-            msg!("Whitelist Info key doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-        let wlist = Whitelist::from_account(whitelist_info)?;
-        if wlist.state
+        if load_config(program_id, config_info)?.deposits_paused
         {
-            msg!("The collection isn't initialized at all");
-            return Err(ProgramError::MissingRequiredSignature);
+            msg!("Deposits are paused");
+            return Err(TokenError::ProtocolPaused.into());
         }
-        for i in 0..creators.len()
+        check_loan_duration(duration)?;
+        let wlist = Self::check_whitelisted(program_id, nft_mint, meta_data_account, whitelist_info, proof)?;
+        if wlist.price_config(currency_mint.as_ref()).is_none()
         {
-            if wlist.producer[i]!=creators[i].address
-            {
-                msg!("The creators doesn't match");
-                return Err(ProgramError::MissingRequiredSignature);
-            }
+            msg!("The collection can't be borrowed against in this currency");
+            return Err(TokenError::CurrencyNotAllowed.into());
         }
        //checking if the owner is the signer or not
         if !nft_owner.is_signer
@@ -118,7 +140,7 @@ impl Processor {
         //verifying nft token address
         if nft_token_address!=*nft_owner_nft_associated.key
         {
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::InvalidTokenAccount.into());
         }
         
         //nft_vault where nft is stored
@@ -131,7 +153,7 @@ impl Processor {
          //nft_vault where nft is stored
         if nft_vault_address!=*nft_vault.key
         {
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::InvalidVaultAddress.into());
         }
         //signer seeds for nft_vault
         let _nft_vault_signer_seeds: &[&[_]] = &[
@@ -146,18 +168,18 @@ impl Processor {
          //verifying nft token address
          if vault_nft_token_address!=*nft_associated_address.key
          {
-             return Err(ProgramError::MissingRequiredSignature);
+             return Err(TokenError::InvalidTokenAccount.into());
          }
          
          //rent account
         let rent = Rent::get()?;
-        let transfer_amount =  rent.minimum_balance(std::mem::size_of::<NftDetails>());
+        let transfer_amount =  rent.minimum_balance(NftDetails::LEN);
         invoke(
             &system_instruction::create_account(
                 nft_owner.key,
                 pda.key,
                 transfer_amount,
-                std::mem::size_of::<NftDetails>() as u64,
+                NftDetails::LEN as u64,
                 program_id,
             ),
             &[
@@ -206,15 +228,250 @@ impl Processor {
             ],
         )?;
         //let now = Clock::get()?.unix_timestamp as u64; 
-        let mut escrow = NftDetails::try_from_slice(&pda.data.borrow())?;
+        let mut escrow = NftDetails::from_account(pda)?;
         escrow.nft_mint=*nft_mint.key;
         escrow.nft_owner=*nft_owner.key;
+        escrow.loan_duration=duration;
+        escrow.interest_rate_bps=wlist.interest_rate_bps;
+        escrow.whitelist=*whitelist_info.key;
+        escrow.currency_mint=currency_mint;
         escrow.canceled=false;
         escrow.serialize(&mut &mut pda.data.borrow_mut()[..])?;
         
         Ok(())
     }
-    pub fn process_offer(program_id: &Pubkey,accounts: &[AccountInfo], amount:u64)->ProgramResult{   
+    pub fn process_deposit_escrowless(program_id: &Pubkey,accounts: &[AccountInfo],duration:u64,currency_mint:Option<Pubkey>,proof:Vec<[u8; 32]>)-> ProgramResult {
+        //locking the NFT in the owner's wallet
+        let account_info_iter = &mut accounts.iter();
+        let nft_owner =  next_account_info(account_info_iter)?; // sender or signer
+        let nft_mint = next_account_info(account_info_iter)?;  // mint address of nft
+        let nft_owner_nft_associated = next_account_info(account_info_iter)?;  // nft owner nft id token account address
+        let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let pda = next_account_info(account_info_iter)?; // pda data 
+        let nft_vault = next_account_info(account_info_iter)?;  // nft vault address from NFTPREFIX, nft_owner, pda and program id, approved as delegate
+        let edition = next_account_info(account_info_iter)?; // master edition of the nft, its freeze authority
+        let metadata_program = next_account_info(account_info_iter)?; // token metadata program
+        let system_program = next_account_info(account_info_iter)?; //system program
+        let meta_data_account=next_account_info(account_info_iter)?;
+        let whitelist_info =next_account_info(account_info_iter)?; //
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+
+        if load_config(program_id, config_info)?.deposits_paused
+        {
+            msg!("Deposits are paused");
+            return Err(TokenError::ProtocolPaused.into());
+        }
+        check_loan_duration(duration)?;
+        let wlist = Self::check_whitelisted(program_id, nft_mint, meta_data_account, whitelist_info, &proof)?;
+        if wlist.price_config(currency_mint.as_ref()).is_none()
+        {
+            msg!("The collection can't be borrowed against in this currency");
+            return Err(TokenError::CurrencyNotAllowed.into());
+        }
+        if !nft_owner.is_signer
+        {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if get_associated_token_address(nft_owner.key,nft_mint.key)!=*nft_owner_nft_associated.key
+        {
+            return Err(TokenError::InvalidTokenAccount.into());
+        }
+        let (nft_vault_address, bump_seed) = generate_pda_and_bump_seed(
+            NFTPREFIX,
+            nft_owner.key,
+            pda.key,
+            program_id
+        );
+        if nft_vault_address!=*nft_vault.key
+        {
+            return Err(TokenError::InvalidVaultAddress.into());
+        }
+        let nft_vault_signer_seeds: &[&[_]] = &[
+            NFTPREFIX.as_bytes(),
+            &nft_owner.key.to_bytes(),
+            &pda.key.to_bytes(),
+            &[bump_seed],
+        ];
+        if get_token_balance(nft_owner_nft_associated)?!=1
+        {
+            msg!("The owner doesn't hold the NFT");
+            return Err(TokenError::InvalidTokenAccount.into());
+        }
+        let rent = Rent::get()?;
+        invoke(
+            &system_instruction::create_account(
+                nft_owner.key,
+                pda.key,
+                rent.minimum_balance(NftDetails::LEN),
+                NftDetails::LEN as u64,
+                program_id,
+            ),
+            &[
+                nft_owner.clone(),
+                pda.clone(),
+                system_program.clone(),
+            ],
+        )?;
+        //the vault becomes the delegate and freezes the NFT in place with the master edition's authority
+        invoke(
+            &spl_token::instruction::approve(
+                token_program_id.key,
+                nft_owner_nft_associated.key,
+                nft_vault.key,
+                nft_owner.key,
+                &[],
+                1
+            )?,
+            &[
+                token_program_id.clone(),
+                nft_owner_nft_associated.clone(),
+                nft_vault.clone(),
+                nft_owner.clone(),
+            ],
+        )?;
+        freeze_delegated_account(metadata_program, nft_vault, nft_owner_nft_associated, edition, nft_mint, token_program_id, true, &[nft_vault_signer_seeds])?;
+
+        let mut escrow = NftDetails::from_account(pda)?;
+        escrow.nft_mint=*nft_mint.key;
+        escrow.nft_owner=*nft_owner.key;
+        escrow.loan_duration=duration;
+        escrow.interest_rate_bps=wlist.interest_rate_bps;
+        escrow.whitelist=*whitelist_info.key;
+        escrow.escrowless=true;
+        escrow.currency_mint=currency_mint;
+        escrow.canceled=false;
+        escrow.serialize(&mut &mut pda.data.borrow_mut()[..])?;
+        Ok(())
+    }
+    /// Checks `collateral` holds the NFT of `data`: the vault's associated token
+    /// account, or for escrowless loans the owner's token account frozen with
+    /// the vault as delegate.
+    fn check_collateral(data: &NftDetails, nft_vault: &AccountInfo, collateral: &AccountInfo) -> ProgramResult {
+        if !data.escrowless
+        {
+            check_associated_token_account(collateral, nft_vault.key, &data.nft_mint)?;
+            if get_token_balance(collateral)?!=1
+            {
+                msg!("The vault doesn't contain the specified NFT");
+                return Err(TokenError::NftNotInVault.into());
+            }
+            return Ok(());
+        }
+        check_associated_token_account(collateral, &data.nft_owner, &data.nft_mint)?;
+        let account = spl_token::state::Account::unpack(&collateral.data.borrow())?;
+        if account.amount!=1 || account.delegate!=COption::Some(*nft_vault.key) || !account.is_frozen()
+        {
+            msg!("The NFT isn't frozen in the owner's wallet");
+            return Err(TokenError::NftNotInVault.into());
+        }
+        Ok(())
+    }
+    /// Thaws the owner's token account of an escrowless loan. The master edition
+    /// and the token metadata program follow the instruction's other accounts.
+    fn thaw_collateral<'a, 'b>(
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        nft_vault: &AccountInfo<'b>,
+        collateral: &AccountInfo<'b>,
+        nft_mint: &AccountInfo<'b>,
+        token_program_id: &AccountInfo<'b>,
+        nft_vault_signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let edition = next_account_info(account_info_iter)?; // master edition of the nft
+        let metadata_program = next_account_info(account_info_iter)?; // token metadata program
+        freeze_delegated_account(metadata_program, nft_vault, collateral, edition, nft_mint, token_program_id, false, &[nft_vault_signer_seeds])
+    }
+    /// Checks the metadata of `nft_mint` against the whitelist it claims,
+    /// by verified collection or creators and the merkle root if any.
+    fn check_whitelisted(program_id: &Pubkey, nft_mint: &AccountInfo, meta_data_account: &AccountInfo, whitelist_info: &AccountInfo, proof: &[[u8; 32]]) -> Result<Whitelist, ProgramError> {
+        let (metadata_address, _) = derive_metadata_address(nft_mint.key);
+
+        //verifying the collection
+        if *meta_data_account.key!=metadata_address
+        {
+            msg!("The metadata account doesn't match");
+            return Err(TokenError::InvalidMetadataAccount.into());
+        }
+        let metadata=Metadata::from_account_info(meta_data_account)?;
+        if whitelist_info.owner!=program_id
+        {
+            msg!("Whitelist Info is not owned by the program");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        //a verified collection keys the whitelist, otherwise the first creator does
+        let collection = verified_collection(meta_data_account)?
+            .filter(|c| derive_whitelist_address(c, program_id).0==*whitelist_info.key);
+        let creators: Vec<Creator> = metadata.data.creators.unwrap_or_default();
+        let whitelist_key = match collection
+        {
+            Some(c) => c,
+            None =>
+            {
+                match creators.first()
+                {
+                    Some(creator) => creator.address,
+                    None =>
+                    {
+                        msg!("The metadata has neither a verified collection nor creators");
+                        return Err(TokenError::MissingCreators.into());
+                    }
+                }
+            }
+        };
+        let (whitelist_address, _)=derive_whitelist_address(&whitelist_key, program_id);
+
+        if whitelist_address!=*whitelist_info.key
+        {
+            msg!("Whitelist Info key doesn't match");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        let wlist = Whitelist::from_account(whitelist_info)?;
+        if !wlist.state
+        {
+            msg!("The collection isn't whitelisted");
+            return Err(TokenError::CollectionNotWhitelisted.into());
+        }
+        if wlist.collection!=collection
+        {
+            msg!("The collection doesn't match");
+            return Err(TokenError::CollectionNotWhitelisted.into());
+        }
+        if collection.is_none()
+        {
+            if wlist.producer.len()!=creators.len()
+            {
+                msg!("The number of creators doesn't match");
+                return Err(TokenError::CollectionNotWhitelisted.into());
+            }
+            for (producer, creator) in wlist.producer.iter().zip(creators.iter())
+            {
+                if *producer!=creator.address
+                {
+                    msg!("The creators doesn't match");
+                    return Err(TokenError::CollectionNotWhitelisted.into());
+                }
+                if !creator.verified
+                {
+                    msg!("The creator {} isn't verified",creator.address);
+                    return Err(TokenError::UnverifiedCreator.into());
+                }
+            }
+        }
+        if let Some(root) = wlist.merkle_root
+        {
+            if proof.len()>MAX_MERKLE_PROOF_LEN
+            {
+                msg!("The merkle proof has {} nodes",proof.len());
+                return Err(TokenError::MerkleProofTooLong.into());
+            }
+            if !verify_merkle_proof(merkle_leaf(nft_mint.key), proof, &root)
+            {
+                msg!("The NFT mint isn't allowed by the whitelist");
+                return Err(TokenError::InvalidMerkleProof.into());
+            }
+        }
+        Ok(wlist)
+    }
+    pub fn process_offer(program_id: &Pubkey,accounts: &[AccountInfo], amount:u64, duration:u64)->ProgramResult{   
         let account_info_iter = &mut accounts.iter();
         let lender =  next_account_info(account_info_iter)?; // sender or signer
         let nft_owner = next_account_info(account_info_iter)?; // auction creator
@@ -222,8 +479,19 @@ impl Processor {
         let nft_mint =next_account_info(account_info_iter)?; // 
         let nft_associated_address = next_account_info(account_info_iter)?; // address generated from nft_vault_address and nft mint address token account address
         let system_program = next_account_info(account_info_iter)?;//system_program
-        let mokshya_prm=next_account_info(account_info_iter)?; 
+        let whitelist_info = next_account_info(account_info_iter)?; // whitelist of the collection
         let data_account = next_account_info(account_info_iter)?; 
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let offer_info = next_account_info(account_info_iter)?; // offer address from OFFERPREFIX, lender, data_account and program id
+        let price_info = next_account_info(account_info_iter)?; // price account of the whitelist's price source
+        // followed for SPL loans by the currency mint, the lender's token account, the offer's
+        // associated token account, the token program, the associated token program and rent
+
+        if load_config(program_id, config_info)?.loans_paused
+        {
+            msg!("Lending is paused");
+            return Err(TokenError::ProtocolPaused.into());
+        }
 
         if !lender.is_signer
         {
@@ -233,36 +501,27 @@ impl Processor {
         if data_account.owner!=program_id
         {
             msg!("The data_account isn't owned by the program");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::InvalidDataAccount.into());
         }
-        let mut data = NftDetails::try_from_slice(&data_account.data.borrow())?;
+        let mut data = NftDetails::from_account(data_account)?;
         if data.nft_owner!=*nft_owner.key
         {
             msg!("The NFT owner doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::OwnerMismatch.into());
         }
         if data.nft_mint!=*nft_mint.key
         {
-            msg!("The NFT owner doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-        if data.loan_offered 
-        {
-            msg!("The Loan is already offered");
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-        if data.loan_taken
-        {
-            msg!("The Loan is already taken");
-            return Err(ProgramError::MissingRequiredSignature);
+            msg!("The NFT mint doesn't match");
+            return Err(TokenError::MintMismatch.into());
         }
-        if data.canceled
+        //offers on a running loan can refinance it
+        if data.canceled || data.auction.is_some()
         {
             msg!("The process is canceled");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::LoanCanceled.into());
         }
          //nft_vault where nft is stored
-         let (nft_vault_address, bump_seed) = generate_pda_and_bump_seed(
+         let (nft_vault_address, _) = generate_pda_and_bump_seed(
             NFTPREFIX,
             nft_owner.key,
             data_account.key,
@@ -271,58 +530,151 @@ impl Processor {
          //nft_vault where nft is stored
         if nft_vault_address!=*nft_vault.key
         {
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::InvalidVaultAddress.into());
         }
-        //signer seeds for nft_vault
-        let _nft_vault_signer_seeds: &[&[_]] = &[
-            NFTPREFIX.as_bytes(),
-            &nft_owner.key.to_bytes(),
+        //offer account which escrows the lender's lamports
+        let (offer_address, offer_bump) = generate_pda_and_bump_seed(
+            OFFERPREFIX,
+            lender.key,
+            data_account.key,
+            program_id
+        );
+        if offer_address!=*offer_info.key
+        {
+            msg!("The offer account doesn't match");
+            return Err(TokenError::InvalidOfferAccount.into());
+        }
+        if !offer_info.data_is_empty()
+        {
+            msg!("The lender already has an offer on this NFT");
+            return Err(TokenError::OfferAlreadyExists.into());
+        }
+        //signer seeds for the offer account
+        let offer_signer_seeds: &[&[_]] = &[
+            OFFERPREFIX.as_bytes(),
+            &lender.key.to_bytes(),
             &data_account.key.to_bytes(),
-            &[bump_seed],
+            &[offer_bump],
         ];
-          //finding nft token account
-          let vault_nft_token_address=get_associated_token_address(nft_vault.key,nft_mint.key);
+        Self::check_collateral(&data, nft_vault, nft_associated_address)?;
 
-        //verifying nft token address
-        if vault_nft_token_address!=*nft_associated_address.key
+        if *whitelist_info.key!=data.whitelist || whitelist_info.owner!=program_id
         {
-            return Err(ProgramError::MissingRequiredSignature);
-        }         
-    
-        let token_balance = get_token_balance(nft_associated_address)?;
-        if token_balance!=1
+            msg!("Whitelist Info doesn't match the deposit");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        let wlist = Whitelist::from_account(whitelist_info)?;
+        if !wlist.state
         {
-            msg!("The vault doesn't contain the specified NFT");
-            return Err(ProgramError::MissingRequiredSignature);
+            msg!("The collection isn't whitelisted");
+            return Err(TokenError::CollectionNotWhitelisted.into());
         }
-
+        let price_config = wlist.price_config(data.currency_mint.as_ref()).ok_or(TokenError::CurrencyNotAllowed)?;
+        let currency_accounts = match data.currency_mint
+        {
+            Some(_) => Some(next_account_infos(account_info_iter, 6)?),
+            None => None,
+        };
+        let decimals = match (data.currency_mint, currency_accounts)
+        {
+            (Some(mint), Some(currency_accounts)) =>
+            {
+                if *currency_accounts[0].key!=mint
+                {
+                    msg!("The currency mint doesn't match");
+                    return Err(TokenError::CurrencyMismatch.into());
+                }
+                spl_token::state::Mint::unpack(&currency_accounts[0].data.borrow())?.decimals
+            }
+            _ => NATIVE_DECIMALS,
+        };
+        let now = Clock::get()?.unix_timestamp as u64;
         msg!("The price of the nft collection is ");
-        let price=collection_price(&mokshya_prm.key, data_account)?;
+        let price=collection_price(program_id, whitelist_info.key, data.currency_mint.as_ref(), decimals, price_config, price_info, now)?;
         msg!("{}",price);
-        if amount < price/2
+        let max_loan = max_loan_amount(price, wlist.max_ltv_bps)?;
+        if amount==0
         {
-            msg!("The amount offered is lower ");
-            return Err(ProgramError::MissingRequiredSignature);
+            msg!("The amount offered is zero");
+            return Err(TokenError::OfferTooLow.into());
         }
-        if amount > price/2
+        if amount > max_loan
         {
-            msg!("The amount offered is higher only {} will be deducted ",price/2);   
+            msg!("The amount offered is higher than the limit of {}",max_loan);
+            return Err(TokenError::OfferTooHigh.into());
         }
-        invoke(
-            &system_instruction::transfer(
+        let loan_amt = amount;
+        let loan_duration = if duration!=0
+        {
+            msg!("The lender proposes a loan duration of {} seconds",duration);
+            check_loan_duration(duration)?;
+            duration
+        }
+        else
+        {
+            data.loan_duration
+        };
+        //the offer account holds its rent and the escrowed lamports of a native loan
+        let rent = Rent::get()?;
+        let mut offer_lamports = rent.minimum_balance(LoanOffer::LEN);
+        if currency_accounts.is_none()
+        {
+            offer_lamports=offer_lamports.checked_add(loan_amt).ok_or(TokenError::Overflow)?;
+        }
+        invoke_signed(
+            &system_instruction::create_account(
                 lender.key,
-                nft_vault.key,
-                price/2,
+                offer_info.key,
+                offer_lamports,
+                LoanOffer::LEN as u64,
+                program_id,
             ),
             &[
                 lender.clone(),
-                nft_vault.clone(),
+                offer_info.clone(),
                 system_program.clone()
             ],
+            &[&offer_signer_seeds],
         )?;
-        data.loan_amt=price/2;
+        //SPL loans are escrowed in the offer's associated token account
+        if let Some(currency_accounts) = currency_accounts
+        {
+            let currency_mint = &currency_accounts[0];
+            let lender_token_account = &currency_accounts[1];
+            let offer_token_account = &currency_accounts[2];
+            let token_program_id = &currency_accounts[3];
+            let associated_token_info = &currency_accounts[4];
+            let rent_info = &currency_accounts[5];
+            check_associated_token_account(offer_token_account, offer_info.key, currency_mint.key)?;
+            invoke(
+                &spl_associated_token_account::instruction::create_associated_token_account(
+                    lender.key,
+                    offer_info.key,
+                    currency_mint.key,
+                ),&[
+                    lender.clone(),
+                    offer_token_account.clone(),
+                    offer_info.clone(),
+                    currency_mint.clone(),
+                    token_program_id.clone(),
+                    rent_info.clone(),
+                    associated_token_info.clone(),
+                    system_program.clone()
+                ]
+            )?;
+            transfer_tokens(token_program_id, lender_token_account, offer_token_account, lender, loan_amt, &[])?;
+        }
+        let offer = LoanOffer{
+            nft_details: *data_account.key,
+            lender: *lender.key,
+            loan_amt,
+            loan_duration,
+            interest_rate_bps: data.interest_rate_bps,
+            accepted: false,
+        };
+        offer.serialize(&mut *offer_info.data.borrow_mut())?;
+        data.offer_count=data.offer_count.checked_add(1).ok_or(TokenError::Overflow)?;
         data.loan_offered=true;
-        data.lender=*lender.key;
         data.serialize(&mut &mut data_account.data.borrow_mut()[..])?;
        
        Ok(())
@@ -335,52 +687,56 @@ impl Processor {
         let nft_vault = next_account_info(account_info_iter)?; // nft vault which saves the amount 
         let nft_mint =next_account_info(account_info_iter)?; // 
         let nft_associated_address = next_account_info(account_info_iter)?; // address generated from nft_vault_address and nft mint address token account address
-        let system_program = next_account_info(account_info_iter)?;//system_program
+        let _system_program = next_account_info(account_info_iter)?;//system_program
         let data_account = next_account_info(account_info_iter)?; 
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let offer_info = next_account_info(account_info_iter)?; // offer of the selected lender
+        // followed for SPL loans by the offer's associated token account, the owner's token account
+        // and the token program
 
+        if load_config(program_id, config_info)?.loans_paused
+        {
+            msg!("Lending is paused");
+            return Err(TokenError::ProtocolPaused.into());
+        }
         if !nft_owner.is_signer
         {
-            msg!("The lender isn't signer");
+            msg!("The NFT owner isn't signer");
             return Err(ProgramError::MissingRequiredSignature);   
         }
         if data_account.owner!=program_id
         {
             msg!("The data_account isn't owned by the program");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::InvalidDataAccount.into());
         }
-        let mut data = NftDetails::try_from_slice(&data_account.data.borrow())?;
+        let mut data = NftDetails::from_account(data_account)?;
         if data.nft_owner!=*nft_owner.key
         {
             msg!("The NFT owner doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::OwnerMismatch.into());
         }
         if data.nft_mint!=*nft_mint.key
         {
-            msg!("The NFT owner doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-        if data.lender!=*lender.key
-        {
-            msg!("The lender doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
+            msg!("The NFT mint doesn't match");
+            return Err(TokenError::MintMismatch.into());
         }
         if !data.loan_offered 
         {
             msg!("The Loan isn't offered at all");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::LoanNotOffered.into());
         }
         if data.loan_taken
         {
             msg!("The Loan is already taken");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::LoanAlreadyTaken.into());
         }
         if data.canceled
         {
             msg!("The process is canceled");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::LoanCanceled.into());
         }
          //nft_vault where nft is stored
-         let (nft_vault_address, bump_seed) = generate_pda_and_bump_seed(
+         let (nft_vault_address, _) = generate_pda_and_bump_seed(
             NFTPREFIX,
             nft_owner.key,
             data_account.key,
@@ -389,49 +745,155 @@ impl Processor {
          //nft_vault where nft is stored
         if nft_vault_address!=*nft_vault.key
         {
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::InvalidVaultAddress.into());
         }
-        //signer seeds for nft_vault
-        let nft_vault_signer_seeds: &[&[_]] = &[
-            NFTPREFIX.as_bytes(),
-            &nft_owner.key.to_bytes(),
-            &data_account.key.to_bytes(),
-            &[bump_seed],
-        ];
-          //finding nft token account
-          let vault_nft_token_address=get_associated_token_address(nft_vault.key,nft_mint.key);
-
-        //verifying nft token address
-        if vault_nft_token_address!=*nft_associated_address.key
+        let (offer_address, offer_bump) = generate_pda_and_bump_seed(
+            OFFERPREFIX,
+            lender.key,
+            data_account.key,
+            program_id
+        );
+        if offer_address!=*offer_info.key
         {
-            return Err(ProgramError::MissingRequiredSignature);
-        }         
-    
-        let token_balance = get_token_balance(nft_associated_address)?;
-        if token_balance!=1
+            msg!("The offer account doesn't match");
+            return Err(TokenError::InvalidOfferAccount.into());
+        }
+        if offer_info.owner!=program_id
         {
-            msg!("The vault doesn't contain the specified NFT");
-            return Err(ProgramError::MissingRequiredSignature);
+            msg!("The offer account isn't owned by the program");
+            return Err(TokenError::InvalidOfferAccount.into());
         }
-        invoke_signed(  
-            &system_instruction::transfer(
-            nft_vault.key,
-            nft_owner.key,
-            data.loan_amt,    
-        ),
-        &[
-            nft_vault.clone(),
-            nft_owner.clone(),
-            system_program.clone(),
-        ],
-        &[&nft_vault_signer_seeds],
-        )?;
+        let mut offer = LoanOffer::from_account(offer_info)?;
+        if offer.lender!=*lender.key || offer.nft_details!=*data_account.key
+        {
+            msg!("The lender doesn't match");
+            return Err(TokenError::LenderMismatch.into());
+        }
+        if offer.accepted
+        {
+            msg!("The offer is already accepted");
+            return Err(TokenError::OfferAlreadyAccepted.into());
+        }
+        Self::check_collateral(&data, nft_vault, nft_associated_address)?;
+        //paying out the escrowed loan from the offer account
+        match data.currency_mint
+        {
+            Some(mint) =>
+            {
+                let offer_token_account = next_account_info(account_info_iter)?;
+                let owner_token_account = next_account_info(account_info_iter)?;
+                let token_program_id = next_account_info(account_info_iter)?;
+                check_associated_token_account(offer_token_account, offer_info.key, &mint)?;
+                let offer_signer_seeds: &[&[_]] = &[
+                    OFFERPREFIX.as_bytes(),
+                    &lender.key.to_bytes(),
+                    &data_account.key.to_bytes(),
+                    &[offer_bump],
+                ];
+                transfer_tokens(token_program_id, offer_token_account, owner_token_account, offer_info, offer.loan_amt, &[offer_signer_seeds])?;
+            }
+            None => transfer_lamports(offer_info, nft_owner, offer.loan_amt)?,
+        }
+        offer.accepted=true;
+        offer.serialize(&mut *offer_info.data.borrow_mut())?;
+
         let now = Clock::get()?.unix_timestamp as u64; 
         data.loan_start = now;
+        data.interest_start = now;
+        data.loan_amt=offer.loan_amt;
+        data.outstanding_principal=offer.loan_amt;
+        data.loan_duration=offer.loan_duration;
+        data.interest_rate_bps=offer.interest_rate_bps;
+        data.lender=*lender.key;
+        data.offer_count=data.offer_count.checked_sub(1).ok_or(TokenError::Overflow)?;
         data.loan_taken=true;
         data.serialize(&mut &mut data_account.data.borrow_mut()[..])?;
         Ok(())
     }
+    pub fn process_withdraw_offer(program_id: &Pubkey,accounts: &[AccountInfo],)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lender =  next_account_info(account_info_iter)?; // sender or signer
+        let data_account = next_account_info(account_info_iter)?; // nft details the offer was made on
+        let offer_info = next_account_info(account_info_iter)?; // offer address from OFFERPREFIX, lender, data_account and program id
+        // followed for SPL loans by the offer's associated token account, the lender's token account
+        // and the token program
+
+        if !lender.is_signer
+        {
+            msg!("The lender isn't signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if data_account.owner!=program_id
+        {
+            msg!("The data_account isn't owned by the program");
+            return Err(TokenError::InvalidDataAccount.into());
+        }
+        if offer_info.owner!=program_id
+        {
+            msg!("The offer account isn't owned by the program");
+            return Err(TokenError::InvalidOfferAccount.into());
+        }
+        let (offer_address, offer_bump) = generate_pda_and_bump_seed(
+            OFFERPREFIX,
+            lender.key,
+            data_account.key,
+            program_id
+        );
+        if offer_address!=*offer_info.key
+        {
+            msg!("The offer account doesn't match");
+            return Err(TokenError::InvalidOfferAccount.into());
+        }
+        let offer = LoanOffer::from_account(offer_info)?;
+        if offer.lender!=*lender.key || offer.nft_details!=*data_account.key
+        {
+            msg!("The lender doesn't match");
+            return Err(TokenError::LenderMismatch.into());
+        }
+        let mut data = NftDetails::from_account(data_account)?;
+        if offer.accepted
+        {
+            //the loan of an accepted offer has to be settled or refinanced before the rent is returned
+            if !data.canceled && data.lender==offer.lender
+            {
+                msg!("The loan of this offer is still running");
+                return Err(TokenError::LoanStillActive.into());
+            }
+        }
+        else
+        {
+            //an open offer can be withdrawn at any time before the borrower selects it
+            data.offer_count=data.offer_count.checked_sub(1).ok_or(TokenError::Overflow)?;
+            if !data.loan_taken
+            {
+                data.loan_offered=data.offer_count>0;
+                data.lender=Pubkey::default();
+            }
+            data.serialize(&mut &mut data_account.data.borrow_mut()[..])?;
+        }
+        msg!("Refunding the offer to the lender");
+        if let Some(mint) = data.currency_mint
+        {
+            let offer_token_account = next_account_info(account_info_iter)?;
+            let lender_token_account = next_account_info(account_info_iter)?;
+            let token_program_id = next_account_info(account_info_iter)?;
+            check_associated_token_account(offer_token_account, offer_info.key, &mint)?;
+            let offer_signer_seeds: &[&[_]] = &[
+                OFFERPREFIX.as_bytes(),
+                &lender.key.to_bytes(),
+                &data_account.key.to_bytes(),
+                &[offer_bump],
+            ];
+            let escrowed = get_token_balance(offer_token_account)?;
+            if escrowed>0
+            {
+                transfer_tokens(token_program_id, offer_token_account, lender_token_account, offer_info, escrowed, &[offer_signer_seeds])?;
+            }
+            close_token_account(token_program_id, offer_token_account, lender, offer_info, &[offer_signer_seeds])?;
+        }
+        close_account(offer_info, lender)?;
+        Ok(())
+    }
     pub fn process_cancel(program_id: &Pubkey,accounts: &[AccountInfo],)-> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let nft_owner =  next_account_info(account_info_iter)?; // sender or signer
@@ -454,7 +916,7 @@ impl Processor {
          //verifying nft token address
          if nft_token_address!=*nft_owner_nft_associated.key
          {
-             return Err(ProgramError::MissingRequiredSignature);
+             return Err(TokenError::InvalidTokenAccount.into());
          }
          
          //nft_vault where nft is stored
@@ -467,7 +929,7 @@ impl Processor {
           //nft_vault where nft is stored
          if nft_vault_address!=*nft_vault.key
          {
-             return Err(ProgramError::MissingRequiredSignature);
+             return Err(TokenError::InvalidVaultAddress.into());
          }
          //signer seeds for nft_vault
          let nft_vault_signer_seeds: &[&[_]] = &[
@@ -476,85 +938,74 @@ impl Processor {
              &pda.key.to_bytes(),
              &[bump_seed],
          ];
-          //finding nft token account
-          let vault_nft_token_address=get_associated_token_address(nft_vault.key,nft_mint.key);
- 
-          //verifying nft token address
-          if vault_nft_token_address!=*nft_associated_address.key
-          {
-              return Err(ProgramError::MissingRequiredSignature);
-          }
           if pda.owner!=program_id
           {
               msg!("The data_account isn't owned by the program");
-              return Err(ProgramError::MissingRequiredSignature);
+              return Err(TokenError::InvalidDataAccount.into());
           }
-          let mut data = NftDetails::try_from_slice(&pda.data.borrow())?;
+          let mut data = NftDetails::from_account(pda)?;
 
           if data.nft_owner!=*nft_owner.key
           {
               msg!("The NFT owner doesn't match");
-              return Err(ProgramError::MissingRequiredSignature);
+              return Err(TokenError::OwnerMismatch.into());
           }
           if data.nft_mint!=*nft_mint.key
           {
-              msg!("The NFT owner doesn't match");
-              return Err(ProgramError::MissingRequiredSignature);
+              msg!("The NFT mint doesn't match");
+              return Err(TokenError::MintMismatch.into());
           }
           if data.loan_taken
            {
               msg!("The Loan is already taken, you need to pay interest to release the fund");
-              return Err(ProgramError::MissingRequiredSignature);
+              return Err(TokenError::LoanAlreadyTaken.into());
            }
-           let token_balance = get_token_balance(nft_associated_address)?;
-           if token_balance!=1
-           {
-               msg!("The vault doesn't contain the specified NFT");
-               return Err(ProgramError::MissingRequiredSignature);
-            }
-            //checking if someone has already offerred the loan or not
+           Self::check_collateral(&data, nft_vault, nft_associated_address)?;
+            //open offers stay in their own accounts, the lenders withdraw them after the cancel
             if data.loan_offered
             {
-                let lender = next_account_info(account_info_iter)?; //lender account
-                if *lender.key!=data.lender
-                {
-                    msg!("The lender key doesn't match");
-                    return Err(ProgramError::MissingRequiredSignature);
-                }
-                msg!("Releasing the fund of the lender");
-                invoke_signed(  
-                    &system_instruction::transfer(
-                    nft_vault.key,
-                    nft_owner.key,
-                    data.loan_amt,    
-                ),
-                &[
-                    nft_vault.clone(),
-                    nft_owner.clone(),
-                    system_program.clone(),
-                ],
-                &[&nft_vault_signer_seeds],
+                msg!("{} open offers become refundable",data.offer_count);
+            }
+            if data.escrowless
+            {
+                //the NFT never left the owner's wallet, thawing it and revoking the vault's delegation releases it
+                Self::thaw_collateral(account_info_iter, nft_vault, nft_associated_address, nft_mint, token_program_id, nft_vault_signer_seeds)?;
+                invoke(
+                    &spl_token::instruction::revoke(
+                        token_program_id.key,
+                        nft_associated_address.key,
+                        nft_owner.key,
+                        &[]
+                    )?,
+                    &[
+                        token_program_id.clone(),
+                        nft_associated_address.clone(),
+                        nft_owner.clone(),
+                    ],
+                )?;
+            }
+            else
+            {
+                //All conditions satisfied release the NFT
+                invoke_signed(
+                    &spl_token::instruction::transfer(
+                        token_program_id.key,
+                        nft_associated_address.key,
+                        nft_owner_nft_associated.key,
+                        nft_vault.key,
+                        &[nft_vault.key],
+                        1
+                    )?,
+                    &[
+                        token_program_id.clone(),
+                        nft_owner_nft_associated.clone(),
+                        nft_associated_address.clone(),
+                        nft_vault.clone(),
+                        system_program.clone()
+                    ],
+                    &[&nft_vault_signer_seeds],
                 )?;
             }
-            //All conditions satisfied release the NFT
-            invoke_signed(
-                &spl_token::instruction::transfer(
-                    token_program_id.key,
-                    nft_associated_address.key,
-                    nft_owner_nft_associated.key,
-                    nft_vault.key,
-                    &[nft_vault.key],
-                    1
-                )?,
-                &[
-                    token_program_id.clone(),
-                    nft_owner_nft_associated.clone(),
-                    nft_associated_address.clone(),
-                    nft_owner.clone(),
-                    system_program.clone()
-                ],
-                &[&nft_vault_signer_seeds],
-            )?;          
   
             data.canceled=true;
             data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
@@ -570,8 +1021,7 @@ impl Processor {
         let nft_vault = next_account_info(account_info_iter)?;  // nft vault address from NFTPREFIX, nft_owner, pda and program id
         let nft_associated_address = next_account_info(account_info_iter)?; // address generated from nft_vault_address and nft mint address token account address
         let system_program = next_account_info(account_info_iter)?; //system program
-        let whitelist_info =next_account_info(account_info_iter)?; //
-        let creator1=next_account_info(account_info_iter)?; //
+        let whitelist_info =next_account_info(account_info_iter)?; // whitelist of the collection
          //checking if the owner is the signer or not
          if !nft_owner.is_signer
          {
@@ -583,7 +1033,7 @@ impl Processor {
          //verifying nft token address
          if nft_token_address!=*nft_owner_nft_associated.key
          {
-             return Err(ProgramError::MissingRequiredSignature);
+             return Err(TokenError::InvalidTokenAccount.into());
          }
          
          //nft_vault where nft is stored
@@ -596,7 +1046,7 @@ impl Processor {
           //nft_vault where nft is stored
          if nft_vault_address!=*nft_vault.key
          {
-             return Err(ProgramError::MissingRequiredSignature);
+             return Err(TokenError::InvalidVaultAddress.into());
          }
          //signer seeds for nft_vault
          let nft_vault_signer_seeds: &[&[_]] = &[
@@ -605,69 +1055,48 @@ impl Processor {
              &pda.key.to_bytes(),
              &[bump_seed],
          ];
-          //finding nft token account
-          let vault_nft_token_address=get_associated_token_address(nft_vault.key,nft_mint.key);
- 
-          //verifying nft token address
-          if vault_nft_token_address!=*nft_associated_address.key
-          {
-              return Err(ProgramError::MissingRequiredSignature);
-          }
           if pda.owner!=program_id
           {
               msg!("The data_account isn't owned by the program");
-              return Err(ProgramError::MissingRequiredSignature);
+              return Err(TokenError::InvalidDataAccount.into());
           }
-          let mut data = NftDetails::try_from_slice(&pda.data.borrow())?;
+          let mut data = NftDetails::from_account(pda)?;
 
           if data.nft_owner!=*nft_owner.key
           {
               msg!("The NFT owner doesn't match");
-              return Err(ProgramError::MissingRequiredSignature);
+              return Err(TokenError::OwnerMismatch.into());
           }
           if data.nft_mint!=*nft_mint.key
           {
-              msg!("The NFT owner doesn't match");
-              return Err(ProgramError::MissingRequiredSignature);
+              msg!("The NFT mint doesn't match");
+              return Err(TokenError::MintMismatch.into());
           }
           if !data.loan_taken
            {
               msg!("The Loan isnot taken");
-              return Err(ProgramError::MissingRequiredSignature);
+              return Err(TokenError::LoanNotTaken.into());
            }
-           let token_balance = get_token_balance(nft_associated_address)?;
-           if token_balance!=1
-            {
-               msg!("The vault doesn't contain the specified NFT");
-               return Err(ProgramError::MissingRequiredSignature);
-            }
+           Self::check_collateral(&data, nft_vault, nft_associated_address)?;
             let now = Clock::get()?.unix_timestamp as u64; 
-            let days:u64 =(now-data.loan_start)/86400;
-            if days >14
-            {
-                msg!("Your collateral NFT is taken by Platform You aren't allowed to withdraw");
-                return Err(ProgramError::MissingRequiredSignature);
-            }
-            let first_creator=creator1.key;
-            let (whitelist_address, _)=derive_whitelist_address(first_creator, program_id);
-
-            if whitelist_address!=*whitelist_info.key
+            let elapsed = now.checked_sub(data.loan_start).ok_or(TokenError::Overflow)?;
+            if *whitelist_info.key!=data.whitelist || whitelist_info.owner!=program_id
             {
                 msg!("Whitelist Info key doesn't match");
-                return Err(ProgramError::MissingRequiredSignature);
-            }   
-
-            if whitelist_info.owner!=program_id
-            {
-                msg!("Whitelist Info not owned by the program");
-                return Err(ProgramError::MissingRequiredSignature);
+                return Err(TokenError::InvalidWhitelistAccount.into());
             }
             let wlist = Whitelist::from_account(whitelist_info)?;  
-            let total_payment=data.loan_amt*(1+wlist.daily_interest_rate*days);
+            if elapsed > data.loan_duration.checked_add(wlist.grace_period).ok_or(TokenError::Overflow)?
+            {
+                msg!("Your collateral NFT is taken by Platform You aren't allowed to withdraw");
+                return Err(TokenError::LoanExpired.into());
+            }
+            let interest = accrued_interest(&data, now, wlist.late_fee_bps)?;
+            let total_payment = repayment_quote(&data, now, wlist.late_fee_bps)?;
             if amount<total_payment
             {
                 msg!("You should pay {} this much amount to clear the debt",total_payment);
-                return Err(ProgramError::MissingRequiredSignature);
+                return Err(TokenError::InsufficientRepayment.into());
             }            
             //checking if someone has already offerred the loan or not
 
@@ -675,41 +1104,69 @@ impl Processor {
             if *lender.key!=data.lender
             {
                 msg!("The lender key doesn't match");
-                return Err(ProgramError::MissingRequiredSignature);
+                return Err(TokenError::LenderMismatch.into());
             }
-            msg!("Releasing the fund of the lender");
-            invoke(  
-                &system_instruction::transfer(
-                nft_owner.key,
-                lender.key,
-                total_payment,    
-            ),
-            &[
-                nft_owner.clone(),
-                lender.clone(),
-                system_program.clone(),
-            ],
+            let config_info = next_account_info(account_info_iter)?; // protocol config
+            let config = load_config(program_id, config_info)?;
+            Self::pay_lender(
+                program_id,
+                account_info_iter,
+                &config,
+                nft_owner,
+                &[],
+                nft_owner,
+                lender,
+                nft_mint.key,
+                token_program_id,
+                system_program,
+                data.currency_mint,
+                wlist.royalty_bps,
+                data.outstanding_principal,
+                interest,
             )?;
+            Self::settle_pool(program_id, lender, data.outstanding_principal)?;
+            data.outstanding_principal=0;
 
-            //All conditions satisfied release the NFT
-            invoke_signed(
-                &spl_token::instruction::transfer(
-                    token_program_id.key,
-                    nft_associated_address.key,
-                    nft_owner_nft_associated.key,
-                    nft_vault.key,
-                    &[nft_vault.key],
-                    1
-                )?,
-                &[
-                    token_program_id.clone(),
-                    nft_owner_nft_associated.clone(),
-                    nft_associated_address.clone(),
-                    nft_owner.clone(),
-                    system_program.clone()
-                ],
-                &[&nft_vault_signer_seeds],
-            )?;          
+            if data.escrowless
+            {
+                //the NFT never left the owner's wallet, thawing it and revoking the vault's delegation releases it
+                Self::thaw_collateral(account_info_iter, nft_vault, nft_associated_address, nft_mint, token_program_id, nft_vault_signer_seeds)?;
+                invoke(
+                    &spl_token::instruction::revoke(
+                        token_program_id.key,
+                        nft_associated_address.key,
+                        nft_owner.key,
+                        &[]
+                    )?,
+                    &[
+                        token_program_id.clone(),
+                        nft_associated_address.clone(),
+                        nft_owner.clone(),
+                    ],
+                )?;
+            }
+            else
+            {
+                //All conditions satisfied release the NFT
+                invoke_signed(
+                    &spl_token::instruction::transfer(
+                        token_program_id.key,
+                        nft_associated_address.key,
+                        nft_owner_nft_associated.key,
+                        nft_vault.key,
+                        &[nft_vault.key],
+                        1
+                    )?,
+                    &[
+                        token_program_id.clone(),
+                        nft_owner_nft_associated.clone(),
+                        nft_associated_address.clone(),
+                        nft_vault.clone(),
+                        system_program.clone()
+                    ],
+                    &[&nft_vault_signer_seeds],
+                )?;
+            }
   
             data.canceled=true;
             data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
@@ -717,170 +1174,1923 @@ impl Processor {
 
          Ok(())
     }
-    pub fn process_lending(program_id: &Pubkey,accounts: &[AccountInfo],)-> ProgramResult {
+    pub fn process_partial_repay(program_id: &Pubkey,accounts: &[AccountInfo],amount:u64)-> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let nft_owner =  next_account_info(account_info_iter)?; // sender or signer
         let nft_mint = next_account_info(account_info_iter)?;  // mint address of nft
+        let _nft_owner_nft_associated = next_account_info(account_info_iter)?;  // nft owner nft id token account address
         let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
         let pda = next_account_info(account_info_iter)?; // pda data 
-        let nft_vault = next_account_info(account_info_iter)?;  // nft vault address from NFTPREFIX, nft_owner, pda and program id
-        let nft_associated_address = next_account_info(account_info_iter)?; // address generated from nft_vault_address and nft mint address token account address
+        let _nft_vault = next_account_info(account_info_iter)?;  // nft vault address from NFTPREFIX, nft_owner, pda and program id
+        let _nft_associated_address = next_account_info(account_info_iter)?; // address generated from nft_vault_address and nft mint address token account address
         let system_program = next_account_info(account_info_iter)?; //system program
+        let whitelist_info =next_account_info(account_info_iter)?; // whitelist of the collection
         let lender = next_account_info(account_info_iter)?; //lender account
-        let lender_token_account = next_account_info(account_info_iter)?; //lender token account from mint and lender key
-        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
-        let rent_info  = next_account_info(account_info_iter)?; // rent 
-
-          //checking if the owner is the signer or not
-          if !lender.is_signer
-          {
-              return Err(ProgramError::MissingRequiredSignature);
-          }
 
+        if !nft_owner.is_signer
+        {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
         if pda.owner!=program_id
         {
             msg!("The data_account isn't owned by the program");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::InvalidDataAccount.into());
         }
-        let mut data = NftDetails::try_from_slice(&pda.data.borrow())?;
-
+        let mut data = NftDetails::from_account(pda)?;
         if data.nft_owner!=*nft_owner.key
         {
             msg!("The NFT owner doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::OwnerMismatch.into());
         }
         if data.nft_mint!=*nft_mint.key
         {
-            msg!("The NFT owner doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
+            msg!("The NFT mint doesn't match");
+            return Err(TokenError::MintMismatch.into());
         }
         if !data.loan_taken
-         {
+        {
             msg!("The Loan isnot taken");
-            return Err(ProgramError::MissingRequiredSignature);
-         }
-         
-         //finding nft token account
-         let nft_token_address=get_associated_token_address(lender.key,nft_mint.key);
- 
-         //verifying nft token address
-         if nft_token_address!=*lender_token_account.key
-         {
-             return Err(ProgramError::MissingRequiredSignature);
-         }
-         //nft_vault where nft is stored
-         let (nft_vault_address, bump_seed) = generate_pda_and_bump_seed(
-             NFTPREFIX,
-             nft_owner.key,
-             pda.key,
-             program_id
-         );
-          //nft_vault where nft is stored
-         if nft_vault_address!=*nft_vault.key
-         {
-             return Err(ProgramError::MissingRequiredSignature);
-         }
-         //signer seeds for nft_vault
-         let nft_vault_signer_seeds: &[&[_]] = &[
-             NFTPREFIX.as_bytes(),
-             &nft_owner.key.to_bytes(),
-             &pda.key.to_bytes(),
-             &[bump_seed],
-         ];
-          //finding nft token account
-          let vault_nft_token_address=get_associated_token_address(nft_vault.key,nft_mint.key);
- 
-          //verifying nft token address
-          if vault_nft_token_address!=*nft_associated_address.key
-          {
-              return Err(ProgramError::MissingRequiredSignature);
-          }
-         let token_balance = get_token_balance(nft_associated_address)?;
-           if token_balance!=1
-            {
-               msg!("The vault doesn't contain the specified NFT");
-               return Err(ProgramError::MissingRequiredSignature);
-            }
-            let now = Clock::get()?.unix_timestamp as u64; 
-            let days:u64 =(now-data.loan_start)/86400;
-            if days <14
-            {
-                msg!("14 day time is required");
-                return Err(ProgramError::MissingRequiredSignature);
-            }
-            if *lender.key!=data.lender
-            {
-                msg!("The lender key doesn't match");
-                return Err(ProgramError::MissingRequiredSignature);
-            }
-            msg!("Releasing the NFT to the lender");
-
-            if lender_token_account.data_is_empty(){
-                invoke(            
-                    &spl_associated_token_account::instruction::create_associated_token_account(
-                        lender.key,
-                        lender.key,
-                        nft_mint.key,
-                    ),&[
-                        lender.clone(),
-                        lender_token_account.clone(),
-                        nft_mint.clone(),
-                        token_program_id.clone(),
-                        rent_info.clone(),
-                        associated_token_info.clone(),
-                        system_program.clone()
+            return Err(TokenError::LoanNotTaken.into());
+        }
+        if data.canceled || data.auction.is_some()
+        {
+            msg!("The loan is already settled");
+            return Err(TokenError::LoanCanceled.into());
+        }
+        if *lender.key!=data.lender
+        {
+            msg!("The lender key doesn't match");
+            return Err(TokenError::LenderMismatch.into());
+        }
+        if *whitelist_info.key!=data.whitelist || whitelist_info.owner!=program_id
+        {
+            msg!("Whitelist Info key doesn't match");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        let wlist = Whitelist::from_account(whitelist_info)?;
+        let now = Clock::get()?.unix_timestamp as u64; 
+        //late loans have to be repaid in full
+        if now.checked_sub(data.loan_start).ok_or(TokenError::Overflow)? > data.loan_duration
+        {
+            msg!("Partial repayments end with the loan term");
+            return Err(TokenError::LoanExpired.into());
+        }
+        let interest = accrued_interest(&data, now, wlist.late_fee_bps)?;
+        if amount>=repayment_quote(&data, now, wlist.late_fee_bps)?
+        {
+            return Self::process_interest(program_id, accounts, amount);
+        }
+        if amount<interest
+        {
+            msg!("The repayment doesn't cover the accrued interest of {}",interest);
+            return Err(TokenError::InsufficientRepayment.into());
+        }
+        let principal = amount.checked_sub(interest).ok_or(TokenError::Overflow)?;
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let config = load_config(program_id, config_info)?;
+        Self::pay_lender(
+            program_id,
+            account_info_iter,
+            &config,
+            nft_owner,
+            &[],
+            nft_owner,
+            lender,
+            nft_mint.key,
+            token_program_id,
+            system_program,
+            data.currency_mint,
+            wlist.royalty_bps,
+            principal,
+            interest,
+        )?;
+        Self::settle_pool(program_id, lender, principal)?;
+        data.outstanding_principal=data.outstanding_principal.checked_sub(principal).ok_or(TokenError::Overflow)?;
+        msg!("Outstanding principal of {}",data.outstanding_principal);
+        data.interest_start=now;
+        data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
+        Ok(())
+    }
+    /// Pays `principal` and `interest` from `payer` to the lender, taking the
+    /// protocol fee and the creator royalty out of the interest. Reads the
+    /// accounts which follow the protocol config: treasury, metadata, the SPL
+    /// accounts of the loan currency and the verified creators.
+    ///
+    /// `payer` is either a signer or an escrow PDA owned by the program which
+    /// signs with `payer_seeds`. Missing token accounts are paid by `funder`.
+    fn pay_lender<'a, 'b>(
+        program_id: &Pubkey,
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        config: &ProtocolConfig,
+        payer: &AccountInfo<'b>,
+        payer_seeds: &[&[&[u8]]],
+        funder: &AccountInfo<'b>,
+        lender: &AccountInfo<'b>,
+        nft_mint: &Pubkey,
+        token_program_id: &AccountInfo<'b>,
+        system_program: &AccountInfo<'b>,
+        currency_mint: Option<Pubkey>,
+        royalty_bps: u64,
+        principal: u64,
+        interest: u64,
+    ) -> ProgramResult {
+        let treasury = next_account_info(account_info_iter)?; // treasury from TREASURY and program id
+        if derive_treasury_address(program_id).0!=*treasury.key
+        {
+            msg!("Treasury key doesn't match");
+            return Err(TokenError::InvalidTreasuryAccount.into());
+        }
+        let meta_data_account = next_account_info(account_info_iter)?; // metadata account of the nft
+        let (metadata_address, _) = derive_metadata_address(nft_mint);
+        if *meta_data_account.key!=metadata_address
+        {
+            msg!("The metadata account doesn't match");
+            return Err(TokenError::InvalidMetadataAccount.into());
+        }
+        //the protocol fee and the creator royalty are only taken from the interest,
+        //the royalty from what the fee leaves so the principal is always repaid
+        let total_payment = principal.checked_add(interest).ok_or(TokenError::Overflow)?;
+        let fee = calculate_interest_share(interest, config.fee_bps)?;
+        let royalty = calculate_interest_share(interest.checked_sub(fee).ok_or(TokenError::Overflow)?, royalty_bps)?;
+        let mut royalties: Vec<(Pubkey, u64)> = Vec::new();
+        if royalty>0
+        {
+            let metadata=Metadata::from_account_info(meta_data_account)?;
+            //only creators who signed the metadata are paid, the rest of the royalty goes to the lender
+            for creator in metadata.data.creators.unwrap_or_default().into_iter().filter(|c| c.verified)
+            {
+                royalties.push((creator.address, calculate_creator_share(royalty, creator.share)?));
+            }
+        }
+        let royalty_paid = royalties.iter().try_fold(0u64, |sum, (_, share)| sum.checked_add(*share)).ok_or(TokenError::Overflow)?;
+        let lender_payment = total_payment
+            .checked_sub(fee)
+            .and_then(|p| p.checked_sub(royalty_paid))
+            .ok_or(TokenError::Overflow)?;
+        msg!("Releasing the fund of the lender");
+        match currency_mint
+        {
+            Some(mint) =>
+            {
+                let owner_token_account = next_account_info(account_info_iter)?; // owner's token account of the loan currency
+                let lender_token_account = next_account_info(account_info_iter)?; // lender's associated token account of the loan currency
+                let treasury_token_account = next_account_info(account_info_iter)?; // treasury's associated token account of the loan currency
+                let currency_mint = next_account_info(account_info_iter)?; // mint of the loan currency
+                let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+                let rent_info  = next_account_info(account_info_iter)?; // rent 
+                check_associated_token_account(lender_token_account, lender.key, &mint)?;
+                check_associated_token_account(treasury_token_account, treasury.key, &mint)?;
+                if *currency_mint.key!=mint
+                {
+                    msg!("The currency mint doesn't match");
+                    return Err(TokenError::CurrencyMismatch.into());
+                }
+                transfer_tokens(token_program_id, owner_token_account, lender_token_account, payer, lender_payment, payer_seeds)?;
+                if fee>0
+                {
+                    if treasury_token_account.data_is_empty()
+                    {
+                        invoke(
+                            &spl_associated_token_account::instruction::create_associated_token_account(
+                                funder.key,
+                                treasury.key,
+                                currency_mint.key,
+                            ),&[
+                                funder.clone(),
+                                treasury_token_account.clone(),
+                                treasury.clone(),
+                                currency_mint.clone(),
+                                token_program_id.clone(),
+                                rent_info.clone(),
+                                associated_token_info.clone(),
+                                system_program.clone()
+                            ]
+                        )?;
+                    }
+                    transfer_tokens(token_program_id, owner_token_account, treasury_token_account, payer, fee, payer_seeds)?;
+                }
+                //creators follow as pairs of wallet and associated token account
+                for (address, share) in royalties.iter()
+                {
+                    let creator = next_account_info(account_info_iter)?; // creator from the metadata
+                    let creator_token_account = next_account_info(account_info_iter)?; // creator's associated token account of the loan currency
+                    if creator.key!=address
+                    {
+                        msg!("The creator doesn't match the metadata");
+                        return Err(TokenError::CreatorMismatch.into());
+                    }
+                    check_associated_token_account(creator_token_account, creator.key, &mint)?;
+                    if *share==0
+                    {
+                        continue;
+                    }
+                    if creator_token_account.data_is_empty()
+                    {
+                        invoke(
+                            &spl_associated_token_account::instruction::create_associated_token_account(
+                                funder.key,
+                                creator.key,
+                                currency_mint.key,
+                            ),&[
+                                funder.clone(),
+                                creator_token_account.clone(),
+                                creator.clone(),
+                                currency_mint.clone(),
+                                token_program_id.clone(),
+                                rent_info.clone(),
+                                associated_token_info.clone(),
+                                system_program.clone()
+                            ]
+                        )?;
+                    }
+                    transfer_tokens(token_program_id, owner_token_account, creator_token_account, payer, *share, payer_seeds)?;
+                }
+            }
+            None =>
+            {
+                //an escrow owned by the program can't pay through the system program
+                let pay = |to: &AccountInfo<'b>, amount: u64| -> ProgramResult {
+                    if payer.owner==program_id
+                    {
+                        return transfer_lamports(payer, to, amount);
+                    }
+                    invoke(
+                        &system_instruction::transfer(payer.key, to.key, amount),
+                        &[
+                            payer.clone(),
+                            to.clone(),
+                            system_program.clone(),
+                        ],
+                    )
+                };
+                pay(lender, lender_payment)?;
+                if fee>0
+                {
+                    pay(treasury, fee)?;
+                }
+                for (address, share) in royalties.iter()
+                {
+                    let creator = next_account_info(account_info_iter)?; // creator from the metadata
+                    if creator.key!=address
+                    {
+                        msg!("The creator doesn't match the metadata");
+                        return Err(TokenError::CreatorMismatch.into());
+                    }
+                    if *share==0
+                    {
+                        continue;
+                    }
+                    pay(creator, *share)?;
+                }
+            }
+        }
+        msg!("Protocol fee of {} and creator royalty of {}",fee,royalty_paid);
+        Ok(())
+    }
+    /// Idle lamports of a `LendingPool` above its rent-exempt minimum.
+    fn pool_liquidity(pool_info: &AccountInfo) -> Result<u64, ProgramError> {
+        let rent = Rent::get()?;
+        Ok(pool_info.lamports().saturating_sub(rent.minimum_balance(LendingPool::LEN)))
+    }
+    /// Lowers the principal a pool has lent out once `principal` of it is
+    /// returned or written off, for loans whose lender is a `LendingPool`.
+    fn settle_pool(program_id: &Pubkey, lender: &AccountInfo, principal: u64) -> ProgramResult {
+        if lender.owner!=program_id
+        {
+            return Ok(());
+        }
+        let mut pool = LendingPool::from_account(lender)?;
+        pool.borrowed=pool.borrowed.checked_sub(principal).ok_or(TokenError::Overflow)?;
+        pool.serialize(&mut *lender.data.borrow_mut())?;
+        Ok(())
+    }
+
+    pub fn process_extend(program_id: &Pubkey,accounts: &[AccountInfo],duration:u64,interest_rate_bps:u64)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let nft_owner =  next_account_info(account_info_iter)?; // sender or signer
+        let lender = next_account_info(account_info_iter)?; //lender account
+        let pda = next_account_info(account_info_iter)?; // pda data 
+        let whitelist_info =next_account_info(account_info_iter)?; // whitelist of the collection
+        let nft_mint = next_account_info(account_info_iter)?;  // mint address of nft
+        let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let system_program = next_account_info(account_info_iter)?; //system program
+
+        //both parties agree on the new terms, within the bounds of any other loan
+        if !nft_owner.is_signer || !lender.is_signer
+        {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        check_loan_duration(duration)?;
+        check_interest_rate(interest_rate_bps)?;
+        if pda.owner!=program_id
+        {
+            msg!("The data_account isn't owned by the program");
+            return Err(TokenError::InvalidDataAccount.into());
+        }
+        let mut data = NftDetails::from_account(pda)?;
+        if data.nft_owner!=*nft_owner.key
+        {
+            msg!("The NFT owner doesn't match");
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if data.nft_mint!=*nft_mint.key
+        {
+            msg!("The NFT mint doesn't match");
+            return Err(TokenError::MintMismatch.into());
+        }
+        if *lender.key!=data.lender
+        {
+            msg!("The lender key doesn't match");
+            return Err(TokenError::LenderMismatch.into());
+        }
+        if !data.loan_taken
+        {
+            msg!("The Loan isnot taken");
+            return Err(TokenError::LoanNotTaken.into());
+        }
+        if data.canceled || data.auction.is_some()
+        {
+            msg!("The loan is already settled");
+            return Err(TokenError::LoanCanceled.into());
+        }
+        if *whitelist_info.key!=data.whitelist || whitelist_info.owner!=program_id
+        {
+            msg!("Whitelist Info key doesn't match");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        let wlist = Whitelist::from_account(whitelist_info)?;
+        let now = Clock::get()?.unix_timestamp as u64; 
+        let elapsed = now.checked_sub(data.loan_start).ok_or(TokenError::Overflow)?;
+        if elapsed > data.loan_duration.checked_add(wlist.grace_period).ok_or(TokenError::Overflow)?
+        {
+            msg!("The loan can't be extended after the grace period");
+            return Err(TokenError::LoanExpired.into());
+        }
+        //settling the interest accrued so far
+        let interest = accrued_interest(&data, now, wlist.late_fee_bps)?;
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let config = load_config(program_id, config_info)?;
+        Self::pay_lender(
+            program_id,
+            account_info_iter,
+            &config,
+            nft_owner,
+            &[],
+            nft_owner,
+            lender,
+            nft_mint.key,
+            token_program_id,
+            system_program,
+            data.currency_mint,
+            wlist.royalty_bps,
+            0,
+            interest,
+        )?;
+
+        data.loan_start=now;
+        data.interest_start=now;
+        data.loan_duration=duration;
+        data.interest_rate_bps=interest_rate_bps;
+        data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
+        Ok(())
+    }
+    pub fn process_refinance(program_id: &Pubkey,accounts: &[AccountInfo])-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let nft_owner =  next_account_info(account_info_iter)?; // sender or signer
+        let new_lender = next_account_info(account_info_iter)?; // lender of the selected offer
+        let lender = next_account_info(account_info_iter)?; // current lender of the loan
+        let pda = next_account_info(account_info_iter)?; // pda data 
+        let offer_info = next_account_info(account_info_iter)?; // offer of the new lender
+        let whitelist_info =next_account_info(account_info_iter)?; // whitelist of the collection
+        let nft_mint = next_account_info(account_info_iter)?;  // mint address of nft
+        let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let system_program = next_account_info(account_info_iter)?; //system program
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+
+        let config = load_config(program_id, config_info)?;
+        if config.loans_paused
+        {
+            msg!("Lending is paused");
+            return Err(TokenError::ProtocolPaused.into());
+        }
+        if !nft_owner.is_signer
+        {
+            msg!("The NFT owner isn't signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if pda.owner!=program_id
+        {
+            msg!("The data_account isn't owned by the program");
+            return Err(TokenError::InvalidDataAccount.into());
+        }
+        let mut data = NftDetails::from_account(pda)?;
+        if data.nft_owner!=*nft_owner.key
+        {
+            msg!("The NFT owner doesn't match");
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if data.nft_mint!=*nft_mint.key
+        {
+            msg!("The NFT mint doesn't match");
+            return Err(TokenError::MintMismatch.into());
+        }
+        if !data.loan_taken
+        {
+            msg!("The Loan isnot taken");
+            return Err(TokenError::LoanNotTaken.into());
+        }
+        if data.canceled || data.auction.is_some()
+        {
+            msg!("The loan is already settled");
+            return Err(TokenError::LoanCanceled.into());
+        }
+        if *lender.key!=data.lender
+        {
+            msg!("The lender key doesn't match");
+            return Err(TokenError::LenderMismatch.into());
+        }
+        let (offer_address, offer_bump) = generate_pda_and_bump_seed(
+            OFFERPREFIX,
+            new_lender.key,
+            pda.key,
+            program_id
+        );
+        if offer_address!=*offer_info.key || offer_info.owner!=program_id
+        {
+            msg!("The offer account doesn't match");
+            return Err(TokenError::InvalidOfferAccount.into());
+        }
+        let mut offer = LoanOffer::from_account(offer_info)?;
+        if offer.lender!=*new_lender.key || offer.nft_details!=*pda.key
+        {
+            msg!("The lender doesn't match");
+            return Err(TokenError::LenderMismatch.into());
+        }
+        if offer.accepted
+        {
+            msg!("The offer is already accepted");
+            return Err(TokenError::OfferAlreadyAccepted.into());
+        }
+        check_loan_duration(offer.loan_duration)?;
+        check_interest_rate(offer.interest_rate_bps)?;
+        if *whitelist_info.key!=data.whitelist || whitelist_info.owner!=program_id
+        {
+            msg!("Whitelist Info key doesn't match");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        let wlist = Whitelist::from_account(whitelist_info)?;
+        let now = Clock::get()?.unix_timestamp as u64; 
+        let elapsed = now.checked_sub(data.loan_start).ok_or(TokenError::Overflow)?;
+        if elapsed > data.loan_duration.checked_add(wlist.grace_period).ok_or(TokenError::Overflow)?
+        {
+            msg!("The loan can't be refinanced after the grace period");
+            return Err(TokenError::LoanExpired.into());
+        }
+        let interest = accrued_interest(&data, now, wlist.late_fee_bps)?;
+        let total_payment = repayment_quote(&data, now, wlist.late_fee_bps)?;
+        if offer.loan_amt<total_payment
+        {
+            msg!("The offer doesn't cover the debt of {}",total_payment);
+            return Err(TokenError::InsufficientRepayment.into());
+        }
+        let offer_signer_seeds: &[&[_]] = &[
+            OFFERPREFIX.as_bytes(),
+            &new_lender.key.to_bytes(),
+            &pda.key.to_bytes(),
+            &[offer_bump],
+        ];
+        //the old lender is paid off from the escrow of the new offer
+        Self::pay_lender(
+            program_id,
+            account_info_iter,
+            &config,
+            offer_info,
+            &[offer_signer_seeds],
+            nft_owner,
+            lender,
+            nft_mint.key,
+            token_program_id,
+            system_program,
+            data.currency_mint,
+            wlist.royalty_bps,
+            data.outstanding_principal,
+            interest,
+        )?;
+        Self::settle_pool(program_id, lender, data.outstanding_principal)?;
+        //the rest of the new loan goes to the borrower
+        let surplus = offer.loan_amt.checked_sub(total_payment).ok_or(TokenError::Overflow)?;
+        match data.currency_mint
+        {
+            Some(mint) =>
+            {
+                let offer_token_account = next_account_info(account_info_iter)?; // offer's associated token account
+                let owner_token_account = next_account_info(account_info_iter)?; // owner's token account of the loan currency
+                check_associated_token_account(offer_token_account, offer_info.key, &mint)?;
+                if surplus>0
+                {
+                    transfer_tokens(token_program_id, offer_token_account, owner_token_account, offer_info, surplus, &[offer_signer_seeds])?;
+                }
+            }
+            None => transfer_lamports(offer_info, nft_owner, surplus)?,
+        }
+        offer.accepted=true;
+        offer.serialize(&mut *offer_info.data.borrow_mut())?;
+
+        data.loan_start=now;
+        data.interest_start=now;
+        data.loan_amt=offer.loan_amt;
+        data.outstanding_principal=offer.loan_amt;
+        //the new loan runs on the terms escrowed with the offer, like a selected one
+        data.loan_duration=offer.loan_duration;
+        data.interest_rate_bps=offer.interest_rate_bps;
+        data.lender=*new_lender.key;
+        data.offer_count=data.offer_count.checked_sub(1).ok_or(TokenError::Overflow)?;
+        data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
+        Ok(())
+    }
+    pub fn process_lending(program_id: &Pubkey,accounts: &[AccountInfo],)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let nft_owner =  next_account_info(account_info_iter)?; // sender or signer
+        let nft_mint = next_account_info(account_info_iter)?;  // mint address of nft
+        let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let pda = next_account_info(account_info_iter)?; // pda data 
+        let nft_vault = next_account_info(account_info_iter)?;  // nft vault address from NFTPREFIX, nft_owner, pda and program id
+        let nft_associated_address = next_account_info(account_info_iter)?; // address generated from nft_vault_address and nft mint address token account address
+        let system_program = next_account_info(account_info_iter)?; //system program
+        let lender = next_account_info(account_info_iter)?; //lender account
+        let lender_token_account = next_account_info(account_info_iter)?; //lender token account from mint and lender key
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+        let rent_info  = next_account_info(account_info_iter)?; // rent 
+        let whitelist_info =next_account_info(account_info_iter)?; // whitelist of the collection
+
+          //checking if the owner is the signer or not
+          if !lender.is_signer
+          {
+              return Err(ProgramError::MissingRequiredSignature);
+          }
+
+        if pda.owner!=program_id
+        {
+            msg!("The data_account isn't owned by the program");
+            return Err(TokenError::InvalidDataAccount.into());
+        }
+        let mut data = NftDetails::from_account(pda)?;
+
+        if data.nft_owner!=*nft_owner.key
+        {
+            msg!("The NFT owner doesn't match");
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if data.nft_mint!=*nft_mint.key
+        {
+            msg!("The NFT mint doesn't match");
+            return Err(TokenError::MintMismatch.into());
+        }
+        if !data.loan_taken
+         {
+            msg!("The Loan isnot taken");
+            return Err(TokenError::LoanNotTaken.into());
+         }
+         
+         //finding nft token account
+         let nft_token_address=get_associated_token_address(lender.key,nft_mint.key);
+ 
+         //verifying nft token address
+         if nft_token_address!=*lender_token_account.key
+         {
+             return Err(TokenError::InvalidTokenAccount.into());
+         }
+         //nft_vault where nft is stored
+         let (nft_vault_address, bump_seed) = generate_pda_and_bump_seed(
+             NFTPREFIX,
+             nft_owner.key,
+             pda.key,
+             program_id
+         );
+          //nft_vault where nft is stored
+         if nft_vault_address!=*nft_vault.key
+         {
+             return Err(TokenError::InvalidVaultAddress.into());
+         }
+         //signer seeds for nft_vault
+         let nft_vault_signer_seeds: &[&[_]] = &[
+             NFTPREFIX.as_bytes(),
+             &nft_owner.key.to_bytes(),
+             &pda.key.to_bytes(),
+             &[bump_seed],
+         ];
+         Self::check_collateral(&data, nft_vault, nft_associated_address)?;
+            if *whitelist_info.key!=data.whitelist || whitelist_info.owner!=program_id
+            {
+                msg!("Whitelist Info key doesn't match");
+                return Err(TokenError::InvalidWhitelistAccount.into());
+            }
+            let wlist = Whitelist::from_account(whitelist_info)?;
+            //the borrower can still repay during the grace period
+            let foreclosable_after = data.loan_duration.checked_add(wlist.grace_period).ok_or(TokenError::Overflow)?;
+            let now = Clock::get()?.unix_timestamp as u64; 
+            if now.checked_sub(data.loan_start).ok_or(TokenError::Overflow)? <= foreclosable_after
+            {
+                msg!("The loan duration and grace period of {} seconds haven't passed yet",foreclosable_after);
+                return Err(TokenError::LoanNotExpired.into());
+            }
+            if *lender.key!=data.lender
+            {
+                msg!("The lender key doesn't match");
+                return Err(TokenError::LenderMismatch.into());
+            }
+            if let Some(auction) = &data.auction
+            {
+                if now<auction.end
+                {
+                    msg!("The NFT is auctioned until {}",auction.end);
+                    return Err(TokenError::AuctionActive.into());
+                }
+            }
+            msg!("Releasing the NFT to the lender");
+
+            if lender_token_account.data_is_empty(){
+                invoke(            
+                    &spl_associated_token_account::instruction::create_associated_token_account(
+                        lender.key,
+                        lender.key,
+                        nft_mint.key,
+                    ),&[
+                        lender.clone(),
+                        lender_token_account.clone(),
+                        nft_mint.clone(),
+                        token_program_id.clone(),
+                        rent_info.clone(),
+                        associated_token_info.clone(),
+                        system_program.clone()
+                    ]
+                )?;}
+
+            if data.escrowless
+            {
+                //the vault moves the thawed NFT as the delegate of the owner's token account
+                Self::thaw_collateral(account_info_iter, nft_vault, nft_associated_address, nft_mint, token_program_id, nft_vault_signer_seeds)?;
+            }
+            //All conditions satisfied release the NFT
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    token_program_id.key,
+                    nft_associated_address.key,
+                    lender_token_account.key,
+                    nft_vault.key,
+                    &[nft_vault.key],
+                    1
+                )?,
+                &[
+                    token_program_id.clone(),
+                    lender_token_account.clone(),
+                    nft_associated_address.clone(),
+                    nft_vault.clone(),
+                    system_program.clone()
+                ],
+                &[&nft_vault_signer_seeds],
+            )?;          
+  
+            data.canceled=true;
+            data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
+            Ok(())
+        }
+
+        pub fn process_start_auction(program_id: &Pubkey,accounts: &[AccountInfo])-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lender = next_account_info(account_info_iter)?; //lender account
+        let pda = next_account_info(account_info_iter)?; // pda data 
+        let whitelist_info =next_account_info(account_info_iter)?; //
+
+        //pools can't sign, anyone may liquidate their defaulted loans
+        let pool_loan = lender.owner==program_id;
+        if !lender.is_signer && !pool_loan
+        {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if pda.owner!=program_id
+        {
+            msg!("The data_account isn't owned by the program");
+            return Err(TokenError::InvalidDataAccount.into());
+        }
+        let mut data = NftDetails::from_account(pda)?;
+        if !data.loan_taken
+        {
+            msg!("The Loan isnot taken");
+            return Err(TokenError::LoanNotTaken.into());
+        }
+        if data.canceled
+        {
+            msg!("The loan is already settled");
+            return Err(TokenError::LoanCanceled.into());
+        }
+        if *lender.key!=data.lender
+        {
+            msg!("The lender key doesn't match");
+            return Err(TokenError::LenderMismatch.into());
+        }
+        if data.currency_mint.is_some()
+        {
+            msg!("Only lamport loans can be auctioned");
+            return Err(TokenError::CurrencyNotAllowed.into());
+        }
+        if data.escrowless
+        {
+            msg!("Escrowless loans are foreclosed, not auctioned");
+            return Err(TokenError::EscrowlessNotSupported.into());
+        }
+        let now = Clock::get()?.unix_timestamp as u64; 
+        //a pool can't foreclose, so its unsold auctions start over a few times
+        let round = match &data.auction
+        {
+            Some(auction) =>
+            {
+                if !pool_loan || now<auction.end
+                {
+                    msg!("The NFT is already auctioned");
+                    return Err(TokenError::AuctionAlreadyStarted.into());
+                }
+                let round = auction.round.checked_add(1).ok_or(TokenError::Overflow)?;
+                if round>=MAX_POOL_AUCTION_ROUNDS
+                {
+                    msg!("The last auction round ended unsold");
+                    return Err(TokenError::AuctionRoundsExhausted.into());
+                }
+                round
+            }
+            None => 0,
+        };
+        if *whitelist_info.key!=data.whitelist || whitelist_info.owner!=program_id
+        {
+            msg!("Whitelist Info key doesn't match");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        let wlist = Whitelist::from_account(whitelist_info)?;
+        let foreclosable_after = data.loan_duration.checked_add(wlist.grace_period).ok_or(TokenError::Overflow)?;
+        if now.checked_sub(data.loan_start).ok_or(TokenError::Overflow)? <= foreclosable_after
+        {
+            msg!("The loan duration and grace period of {} seconds haven't passed yet",foreclosable_after);
+            return Err(TokenError::LoanNotExpired.into());
+        }
+        let config = match wlist.auction
+        {
+            Some(config) => config,
+            None =>
+            {
+                msg!("The collection doesn't auction defaulted NFTs");
+                return Err(TokenError::AuctionNotEnabled.into());
+            }
+        };
+        //the lender is owed what repaying the late loan would cost when the first round starts
+        let debt = match &data.auction
+        {
+            Some(auction) => auction.debt,
+            None => repayment_quote(&data, now, wlist.late_fee_bps)?,
+        };
+        let start_price = scale_bps(debt, BASIS_POINTS.checked_add(config.start_premium_bps).ok_or(TokenError::Overflow)?)?;
+        let end_price = if pool_loan && round+1==MAX_POOL_AUCTION_ROUNDS
+        {
+            0
+        }
+        else
+        {
+            scale_bps(debt, BASIS_POINTS.checked_sub(config.end_discount_bps).ok_or(TokenError::Overflow)?)?
+        };
+        if data.auction.is_none()
+        {
+            //a pool writes the defaulted principal off at once so its share price isn't
+            //inflated while the NFT is auctioned, whatever the sale brings is a gain
+            Self::settle_pool(program_id, lender, data.outstanding_principal)?;
+        }
+        data.auction = Some(Auction{
+            start: now,
+            end: now.checked_add(config.duration).ok_or(TokenError::Overflow)?,
+            start_price,
+            end_price,
+            debt,
+            round,
+        });
+        data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_bid(program_id: &Pubkey,accounts: &[AccountInfo],max_price:u64)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let bidder = next_account_info(account_info_iter)?; // sender or signer
+        let nft_owner =  next_account_info(account_info_iter)?; // borrower receiving the surplus
+        let nft_mint = next_account_info(account_info_iter)?;  // mint address of nft
+        let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let pda = next_account_info(account_info_iter)?; // pda data 
+        let nft_vault = next_account_info(account_info_iter)?;  // nft vault address from NFTPREFIX, nft_owner, pda and program id
+        let nft_associated_address = next_account_info(account_info_iter)?; // address generated from nft_vault_address and nft mint address token account address
+        let system_program = next_account_info(account_info_iter)?; //system program
+        let lender = next_account_info(account_info_iter)?; //lender account
+        let bidder_token_account = next_account_info(account_info_iter)?; //bidder token account from mint and bidder key
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+        let rent_info  = next_account_info(account_info_iter)?; // rent 
+        let whitelist_info =next_account_info(account_info_iter)?; // whitelist of the collection
+
+        if !bidder.is_signer
+        {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if pda.owner!=program_id
+        {
+            msg!("The data_account isn't owned by the program");
+            return Err(TokenError::InvalidDataAccount.into());
+        }
+        let mut data = NftDetails::from_account(pda)?;
+        if data.nft_owner!=*nft_owner.key
+        {
+            msg!("The NFT owner doesn't match");
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if data.nft_mint!=*nft_mint.key
+        {
+            msg!("The NFT mint doesn't match");
+            return Err(TokenError::MintMismatch.into());
+        }
+        if *lender.key!=data.lender
+        {
+            msg!("The lender key doesn't match");
+            return Err(TokenError::LenderMismatch.into());
+        }
+        if get_associated_token_address(bidder.key,nft_mint.key)!=*bidder_token_account.key
+        {
+            return Err(TokenError::InvalidTokenAccount.into());
+        }
+        let (nft_vault_address, bump_seed) = generate_pda_and_bump_seed(
+            NFTPREFIX,
+            nft_owner.key,
+            pda.key,
+            program_id
+        );
+        if nft_vault_address!=*nft_vault.key
+        {
+            return Err(TokenError::InvalidVaultAddress.into());
+        }
+        let nft_vault_signer_seeds: &[&[_]] = &[
+            NFTPREFIX.as_bytes(),
+            &nft_owner.key.to_bytes(),
+            &pda.key.to_bytes(),
+            &[bump_seed],
+        ];
+        if get_associated_token_address(nft_vault.key,nft_mint.key)!=*nft_associated_address.key
+        {
+            return Err(TokenError::InvalidTokenAccount.into());
+        }
+        if get_token_balance(nft_associated_address)?!=1
+        {
+            msg!("The vault doesn't contain the specified NFT");
+            return Err(TokenError::NftNotInVault.into());
+        }
+        let auction = match &data.auction
+        {
+            Some(auction) if !data.canceled => auction,
+            _ =>
+            {
+                msg!("The NFT isn't auctioned");
+                return Err(TokenError::AuctionNotActive.into());
+            }
+        };
+        let now = Clock::get()?.unix_timestamp as u64; 
+        if now>=auction.end
+        {
+            msg!("The auction ended at {}",auction.end);
+            return Err(TokenError::AuctionNotActive.into());
+        }
+        let price = auction_price(auction, now)?;
+        if price>max_price
+        {
+            msg!("The NFT costs {}",price);
+            return Err(TokenError::BidBelowPrice.into());
+        }
+        if *whitelist_info.key!=data.whitelist || whitelist_info.owner!=program_id
+        {
+            msg!("Whitelist Info key doesn't match");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        let wlist = Whitelist::from_account(whitelist_info)?;
+        //the lender is repaid first, the borrower keeps the surplus
+        let lender_payment = price.min(auction.debt);
+        let surplus = price-lender_payment;
+        //proceeds repay the principal before the interest, which pays the fee and royalty
+        let principal = lender_payment.min(data.outstanding_principal);
+        let interest = lender_payment-principal;
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let config = load_config(program_id, config_info)?;
+        Self::pay_lender(
+            program_id,
+            account_info_iter,
+            &config,
+            bidder,
+            &[],
+            bidder,
+            lender,
+            nft_mint.key,
+            token_program_id,
+            system_program,
+            data.currency_mint,
+            wlist.royalty_bps,
+            principal,
+            interest,
+        )?;
+        if surplus>0
+        {
+            invoke(
+                &system_instruction::transfer(bidder.key, nft_owner.key, surplus),
+                &[
+                    bidder.clone(),
+                    nft_owner.clone(),
+                    system_program.clone(),
+                ],
+            )?;
+        }
+        msg!("Sold for {} with a surplus of {}",price,surplus);
+
+        if bidder_token_account.data_is_empty(){
+            invoke(            
+                &spl_associated_token_account::instruction::create_associated_token_account(
+                    bidder.key,
+                    bidder.key,
+                    nft_mint.key,
+                ),&[
+                    bidder.clone(),
+                    bidder_token_account.clone(),
+                    nft_mint.clone(),
+                    token_program_id.clone(),
+                    rent_info.clone(),
+                    associated_token_info.clone(),
+                    system_program.clone()
+                ]
+            )?;}
+
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                token_program_id.key,
+                nft_associated_address.key,
+                bidder_token_account.key,
+                nft_vault.key,
+                &[nft_vault.key],
+                1
+            )?,
+            &[
+                token_program_id.clone(),
+                bidder_token_account.clone(),
+                nft_associated_address.clone(),
+                nft_vault.clone(),
+                system_program.clone()
+            ],
+            &[&nft_vault_signer_seeds],
+        )?;          
+
+        data.canceled=true;
+        data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_pool_deposit(program_id: &Pubkey,accounts: &[AccountInfo],amount:u64)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lender =  next_account_info(account_info_iter)?; // sender or signer
+        let pool_info = next_account_info(account_info_iter)?; // pool from POOL, whitelist and program id
+        let share_info = next_account_info(account_info_iter)?; // shares from POOL, pool, lender and program id
+        let whitelist_info =next_account_info(account_info_iter)?; // whitelist of the collection
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let system_program = next_account_info(account_info_iter)?; //system program
+
+        if load_config(program_id, config_info)?.loans_paused
+        {
+            msg!("Lending is paused");
+            return Err(TokenError::ProtocolPaused.into());
+        }
+        if !lender.is_signer
+        {
+            msg!("The lender isn't signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if whitelist_info.owner!=program_id
+        {
+            msg!("Whitelist Info not owned by the program");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        if !Whitelist::from_account(whitelist_info)?.state
+        {
+            msg!("The collection isn't whitelisted");
+            return Err(TokenError::CollectionNotWhitelisted.into());
+        }
+        let (pool_address, pool_bump) = derive_pool_address(whitelist_info.key, program_id);
+        if pool_address!=*pool_info.key
+        {
+            msg!("The pool account doesn't match");
+            return Err(TokenError::InvalidPoolAccount.into());
+        }
+        let (share_address, share_bump) = generate_pda_and_bump_seed(POOL, pool_info.key, lender.key, program_id);
+        if share_address!=*share_info.key
+        {
+            msg!("The pool share account doesn't match");
+            return Err(TokenError::InvalidPoolAccount.into());
+        }
+        let rent = Rent::get()?;
+        if pool_info.data_is_empty()
+        {
+            invoke_signed(
+                &system_instruction::create_account(
+                    lender.key,
+                    pool_info.key,
+                    rent.minimum_balance(LendingPool::LEN),
+                    LendingPool::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    lender.clone(),
+                    pool_info.clone(),
+                    system_program.clone(),
+                ],
+                &[&[POOL.as_bytes(), &whitelist_info.key.to_bytes(), &[pool_bump]]],
+            )?;
+            let pool = LendingPool{
+                whitelist: *whitelist_info.key,
+                total_shares: 0,
+                borrowed: 0,
+            };
+            pool.serialize(&mut *pool_info.data.borrow_mut())?;
+        }
+        if share_info.data_is_empty()
+        {
+            invoke_signed(
+                &system_instruction::create_account(
+                    lender.key,
+                    share_info.key,
+                    rent.minimum_balance(PoolShare::LEN),
+                    PoolShare::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    lender.clone(),
+                    share_info.clone(),
+                    system_program.clone(),
+                ],
+                &[&[POOL.as_bytes(), &pool_info.key.to_bytes(), &lender.key.to_bytes(), &[share_bump]]],
+            )?;
+            let share = PoolShare{
+                pool: *pool_info.key,
+                owner: *lender.key,
+                shares: 0,
+            };
+            share.serialize(&mut *share_info.data.borrow_mut())?;
+        }
+        else if share_info.owner!=program_id
+        {
+            msg!("The pool share account isn't owned by the program");
+            return Err(TokenError::InvalidPoolAccount.into());
+        }
+        let mut pool = LendingPool::from_account(pool_info)?;
+        let mut share = PoolShare::from_account(share_info)?;
+        //shares are priced before the deposit lands in the pool
+        let value = Self::pool_liquidity(pool_info)?.checked_add(pool.borrowed).ok_or(TokenError::Overflow)?;
+        let shares = pool_shares(amount, pool.total_shares, value)?;
+        if shares==0
+        {
+            msg!("The deposit doesn't buy a share of the pool");
+            return Err(TokenError::PoolAmountTooSmall.into());
+        }
+        invoke(
+            &system_instruction::transfer(lender.key, pool_info.key, amount),
+            &[
+                lender.clone(),
+                pool_info.clone(),
+                system_program.clone(),
+            ],
+        )?;
+        msg!("Minted {} pool shares",shares);
+        pool.total_shares=pool.total_shares.checked_add(shares).ok_or(TokenError::Overflow)?;
+        share.shares=share.shares.checked_add(shares).ok_or(TokenError::Overflow)?;
+        pool.serialize(&mut *pool_info.data.borrow_mut())?;
+        share.serialize(&mut *share_info.data.borrow_mut())?;
+        Ok(())
+    }
+    pub fn process_pool_withdraw(program_id: &Pubkey,accounts: &[AccountInfo],shares:u64)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lender =  next_account_info(account_info_iter)?; // sender or signer
+        let pool_info = next_account_info(account_info_iter)?; // pool from POOL, whitelist and program id
+        let share_info = next_account_info(account_info_iter)?; // shares from POOL, pool, lender and program id
+
+        if !lender.is_signer
+        {
+            msg!("The lender isn't signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if pool_info.owner!=program_id || share_info.owner!=program_id
+        {
+            msg!("The pool accounts aren't owned by the program");
+            return Err(TokenError::InvalidPoolAccount.into());
+        }
+        let (share_address, _) = generate_pda_and_bump_seed(POOL, pool_info.key, lender.key, program_id);
+        if share_address!=*share_info.key
+        {
+            msg!("The pool share account doesn't match");
+            return Err(TokenError::InvalidPoolAccount.into());
+        }
+        let mut pool = LendingPool::from_account(pool_info)?;
+        let mut share = PoolShare::from_account(share_info)?;
+        if shares==0 || shares>share.shares
+        {
+            msg!("The lender owns {} shares",share.shares);
+            return Err(TokenError::InsufficientShares.into());
+        }
+        let liquidity = Self::pool_liquidity(pool_info)?;
+        let value = liquidity.checked_add(pool.borrowed).ok_or(TokenError::Overflow)?;
+        let amount = pool_amount(shares, pool.total_shares, value)?;
+        //lent out lamports come back as loans are repaid
+        if amount>liquidity
+        {
+            msg!("The pool has {} idle lamports",liquidity);
+            return Err(TokenError::InsufficientLiquidity.into());
+        }
+        transfer_lamports(pool_info, lender, amount)?;
+        msg!("Redeemed {} shares for {}",shares,amount);
+        pool.total_shares=pool.total_shares.checked_sub(shares).ok_or(TokenError::Overflow)?;
+        share.shares=share.shares.checked_sub(shares).ok_or(TokenError::Overflow)?;
+        pool.serialize(&mut *pool_info.data.borrow_mut())?;
+        share.serialize(&mut *share_info.data.borrow_mut())?;
+        Ok(())
+    }
+    pub fn process_pool_borrow(program_id: &Pubkey,accounts: &[AccountInfo],amount:u64)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let nft_owner =  next_account_info(account_info_iter)?; // sender or signer
+        let pool_info = next_account_info(account_info_iter)?; // pool from POOL, whitelist and program id
+        let data_account = next_account_info(account_info_iter)?; 
+        let nft_vault = next_account_info(account_info_iter)?; // nft vault which saves the amount 
+        let nft_mint =next_account_info(account_info_iter)?; // 
+        let nft_associated_address = next_account_info(account_info_iter)?; // address generated from nft_vault_address and nft mint address token account address
+        let whitelist_info = next_account_info(account_info_iter)?; // whitelist of the collection
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let price_info = next_account_info(account_info_iter)?; // price account of the whitelist's price source
+
+        if load_config(program_id, config_info)?.loans_paused
+        {
+            msg!("Lending is paused");
+            return Err(TokenError::ProtocolPaused.into());
+        }
+        if !nft_owner.is_signer
+        {
+            msg!("The NFT owner isn't signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if data_account.owner!=program_id
+        {
+            msg!("The data_account isn't owned by the program");
+            return Err(TokenError::InvalidDataAccount.into());
+        }
+        let mut data = NftDetails::from_account(data_account)?;
+        if data.nft_owner!=*nft_owner.key
+        {
+            msg!("The NFT owner doesn't match");
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if data.nft_mint!=*nft_mint.key
+        {
+            msg!("The NFT mint doesn't match");
+            return Err(TokenError::MintMismatch.into());
+        }
+        if data.loan_taken
+        {
+            msg!("The Loan is already taken");
+            return Err(TokenError::LoanAlreadyTaken.into());
+        }
+        if data.canceled
+        {
+            msg!("The process is canceled");
+            return Err(TokenError::LoanCanceled.into());
+        }
+        if data.currency_mint.is_some()
+        {
+            msg!("Pools only lend lamports");
+            return Err(TokenError::CurrencyNotAllowed.into());
+        }
+        //the pool's defaults are auctioned, which needs the NFT in the vault
+        if data.escrowless
+        {
+            msg!("Pools don't lend against escrowless deposits");
+            return Err(TokenError::EscrowlessNotSupported.into());
+        }
+        //the pool takes the term the borrower asked for at deposit, up to its own limit
+        if data.loan_duration>MAX_POOL_LOAN_DURATION
+        {
+            msg!("Pools lend for at most {} seconds",MAX_POOL_LOAN_DURATION);
+            return Err(TokenError::InvalidLoanDuration.into());
+        }
+        let (nft_vault_address, _) = generate_pda_and_bump_seed(
+            NFTPREFIX,
+            nft_owner.key,
+            data_account.key,
+            program_id
+        );
+        if nft_vault_address!=*nft_vault.key
+        {
+            return Err(TokenError::InvalidVaultAddress.into());
+        }
+        if get_associated_token_address(nft_vault.key,nft_mint.key)!=*nft_associated_address.key
+        {
+            return Err(TokenError::InvalidTokenAccount.into());
+        }
+        if get_token_balance(nft_associated_address)?!=1
+        {
+            msg!("The vault doesn't contain the specified NFT");
+            return Err(TokenError::NftNotInVault.into());
+        }
+        if *whitelist_info.key!=data.whitelist || whitelist_info.owner!=program_id
+        {
+            msg!("Whitelist Info doesn't match the deposit");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        let wlist = Whitelist::from_account(whitelist_info)?;
+        if !wlist.state
+        {
+            msg!("The collection isn't whitelisted");
+            return Err(TokenError::CollectionNotWhitelisted.into());
+        }
+        //a pool can't foreclose, its defaulted loans are auctioned
+        if wlist.auction.is_none()
+        {
+            msg!("The collection doesn't auction defaulted NFTs");
+            return Err(TokenError::AuctionNotEnabled.into());
+        }
+        if derive_pool_address(whitelist_info.key, program_id).0!=*pool_info.key || pool_info.owner!=program_id
+        {
+            msg!("The pool account doesn't match");
+            return Err(TokenError::InvalidPoolAccount.into());
+        }
+        let mut pool = LendingPool::from_account(pool_info)?;
+        let now = Clock::get()?.unix_timestamp as u64;
+        let price=collection_price(program_id, whitelist_info.key, None, NATIVE_DECIMALS, &wlist.price, price_info, now)?;
+        let max_loan = max_loan_amount(price, wlist.max_ltv_bps)?;
+        if amount==0
+        {
+            msg!("The amount borrowed is zero");
+            return Err(TokenError::OfferTooLow.into());
+        }
+        if amount > max_loan
+        {
+            msg!("The amount borrowed is higher than the limit of {}",max_loan);
+            return Err(TokenError::OfferTooHigh.into());
+        }
+        if amount > Self::pool_liquidity(pool_info)?
+        {
+            msg!("The pool doesn't have {} idle lamports",amount);
+            return Err(TokenError::InsufficientLiquidity.into());
+        }
+        transfer_lamports(pool_info, nft_owner, amount)?;
+        pool.borrowed=pool.borrowed.checked_add(amount).ok_or(TokenError::Overflow)?;
+        pool.serialize(&mut *pool_info.data.borrow_mut())?;
+
+        data.loan_start=now;
+        data.interest_start=now;
+        data.loan_amt=amount;
+        data.outstanding_principal=amount;
+        data.interest_rate_bps=wlist.interest_rate_bps;
+        data.lender=*pool_info.key;
+        data.loan_taken=true;
+        data.serialize(&mut &mut data_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    pub fn process_deposit_and_borrow(program_id: &Pubkey,accounts: &[AccountInfo],duration:u64,amount:u64,proof:Vec<[u8; 32]>)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let deposit = Self::next_deposit_accounts(account_info_iter)?;
+        let lender_info = next_account_info(account_info_iter)?; // pool of the collection or collection offer
+
+        if derive_pool_address(deposit.whitelist_info.key, program_id).0!=*lender_info.key
+        {
+            //a standing offer lends on its own terms, which must be the ones asked for
+            return Self::fill_collection_offer(program_id, &deposit, lender_info, Some((duration, amount)), &proof);
+        }
+        let price_info = next_account_info(account_info_iter)?; // price account of the whitelist's price source
+
+        //the deposit verifies the NFT against the whitelist and moves it into the vault
+        Self::deposit_nft(program_id, &deposit, duration, None, &proof)?;
+
+        let borrow_accounts = [
+            deposit.nft_owner.clone(),
+            lender_info.clone(),
+            deposit.pda.clone(),
+            deposit.nft_vault.clone(),
+            deposit.nft_mint.clone(),
+            deposit.nft_associated_address.clone(),
+            deposit.whitelist_info.clone(),
+            deposit.config_info.clone(),
+            price_info.clone(),
+        ];
+        Self::process_pool_borrow(program_id, &borrow_accounts, amount)
+    }
+
+    pub fn process_collection_offer(program_id: &Pubkey,accounts: &[AccountInfo],amount:u64,duration:u64,fills:u64)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lender =  next_account_info(account_info_iter)?; // sender or signer
+        let offer_info = next_account_info(account_info_iter)?; // offer from COLLECTION_OFFER, whitelist, lender and program id
+        let whitelist_info = next_account_info(account_info_iter)?; // whitelist of the collection
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let price_info = next_account_info(account_info_iter)?; // price account of the whitelist's price source
+        let system_program = next_account_info(account_info_iter)?;//system_program
+
+        if load_config(program_id, config_info)?.loans_paused
+        {
+            msg!("Lending is paused");
+            return Err(TokenError::ProtocolPaused.into());
+        }
+        if !lender.is_signer
+        {
+            msg!("The lender isn't signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if whitelist_info.owner!=program_id
+        {
+            msg!("Whitelist Info not owned by the program");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        let wlist = Whitelist::from_account(whitelist_info)?;
+        if !wlist.state
+        {
+            msg!("The collection isn't whitelisted");
+            return Err(TokenError::CollectionNotWhitelisted.into());
+        }
+        let (offer_address, offer_bump) = generate_pda_and_bump_seed(
+            COLLECTION_OFFER,
+            whitelist_info.key,
+            lender.key,
+            program_id
+        );
+        if offer_address!=*offer_info.key
+        {
+            msg!("The offer account doesn't match");
+            return Err(TokenError::InvalidOfferAccount.into());
+        }
+        if !offer_info.data_is_empty()
+        {
+            msg!("The lender already has an offer on this collection");
+            return Err(TokenError::OfferAlreadyExists.into());
+        }
+        check_loan_duration(duration)?;
+        if amount==0 || fills==0
+        {
+            msg!("The offer lends nothing");
+            return Err(TokenError::OfferTooLow.into());
+        }
+        let now = Clock::get()?.unix_timestamp as u64;
+        let price=collection_price(program_id, whitelist_info.key, None, NATIVE_DECIMALS, &wlist.price, price_info, now)?;
+        let max_loan = max_loan_amount(price, wlist.max_ltv_bps)?;
+        if amount > max_loan
+        {
+            msg!("The amount offered is higher than the limit of {}",max_loan);
+            return Err(TokenError::OfferTooHigh.into());
+        }
+        //the offer escrows a loan for every fill besides its rent
+        let rent = Rent::get()?;
+        let offer_lamports = amount
+            .checked_mul(fills)
+            .and_then(|escrow| escrow.checked_add(rent.minimum_balance(CollectionOffer::LEN)))
+            .ok_or(TokenError::Overflow)?;
+        invoke_signed(
+            &system_instruction::create_account(
+                lender.key,
+                offer_info.key,
+                offer_lamports,
+                CollectionOffer::LEN as u64,
+                program_id,
+            ),
+            &[
+                lender.clone(),
+                offer_info.clone(),
+                system_program.clone()
+            ],
+            &[&[COLLECTION_OFFER.as_bytes(), &whitelist_info.key.to_bytes(), &lender.key.to_bytes(), &[offer_bump]]],
+        )?;
+        let offer = CollectionOffer{
+            whitelist: *whitelist_info.key,
+            lender: *lender.key,
+            loan_amt: amount,
+            loan_duration: duration,
+            fills,
+        };
+        offer.serialize(&mut *offer_info.data.borrow_mut())?;
+        Ok(())
+    }
+    pub fn process_accept_collection_offer(program_id: &Pubkey,accounts: &[AccountInfo],proof:Vec<[u8; 32]>)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let deposit = Self::next_deposit_accounts(account_info_iter)?;
+        let offer_info = next_account_info(account_info_iter)?; // collection offer taken
+
+        Self::fill_collection_offer(program_id, &deposit, offer_info, None, &proof)
+    }
+    /// Deposits the NFT against a collection offer and lends the borrower one
+    /// of its fills. `terms` are the duration and amount the borrower asked
+    /// for, if any, which the offer must match.
+    fn fill_collection_offer(program_id: &Pubkey,deposit: &DepositAccounts,offer_info: &AccountInfo,terms:Option<(u64,u64)>,proof:&[[u8; 32]])-> ProgramResult {
+        let nft_owner = deposit.nft_owner;
+        let pda = deposit.pda;
+
+        if load_config(program_id, deposit.config_info)?.loans_paused
+        {
+            msg!("Lending is paused");
+            return Err(TokenError::ProtocolPaused.into());
+        }
+        if offer_info.owner!=program_id
+        {
+            msg!("The offer account isn't owned by the program");
+            return Err(TokenError::InvalidOfferAccount.into());
+        }
+        let mut offer = CollectionOffer::from_account(offer_info)?;
+        if generate_pda_and_bump_seed(COLLECTION_OFFER, &offer.whitelist, &offer.lender, program_id).0!=*offer_info.key
+        {
+            msg!("The offer account doesn't match");
+            return Err(TokenError::InvalidOfferAccount.into());
+        }
+        if offer.whitelist!=*deposit.whitelist_info.key
+        {
+            msg!("The offer is on another collection");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        if offer.fills==0
+        {
+            msg!("The offer has no fills left");
+            return Err(TokenError::OfferFilled.into());
+        }
+        if let Some((duration, amount)) = terms
+        {
+            if duration!=offer.loan_duration
+            {
+                msg!("The offer lends for {} seconds",offer.loan_duration);
+                return Err(TokenError::InvalidLoanDuration.into());
+            }
+            if amount>offer.loan_amt
+            {
+                msg!("The offer lends {} lamports",offer.loan_amt);
+                return Err(TokenError::OfferTooLow.into());
+            }
+            if amount<offer.loan_amt
+            {
+                msg!("The offer lends {} lamports",offer.loan_amt);
+                return Err(TokenError::OfferTooHigh.into());
+            }
+        }
+        //the deposit verifies the NFT against the whitelist
+        Self::deposit_nft(program_id, deposit, offer.loan_duration, None, proof)?;
+
+        transfer_lamports(offer_info, nft_owner, offer.loan_amt)?;
+        offer.fills-=1;
+        offer.serialize(&mut *offer_info.data.borrow_mut())?;
+
+        let mut data = NftDetails::from_account(pda)?;
+        let now = Clock::get()?.unix_timestamp as u64;
+        data.loan_start=now;
+        data.interest_start=now;
+        data.loan_amt=offer.loan_amt;
+        data.outstanding_principal=offer.loan_amt;
+        data.lender=offer.lender;
+        data.loan_taken=true;
+        data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
+        Ok(())
+    }
+    pub fn process_withdraw_collection_offer(program_id: &Pubkey,accounts: &[AccountInfo])-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lender =  next_account_info(account_info_iter)?; // sender or signer
+        let offer_info = next_account_info(account_info_iter)?; // offer from COLLECTION_OFFER, whitelist, lender and program id
+
+        if !lender.is_signer
+        {
+            msg!("The lender isn't signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if offer_info.owner!=program_id
+        {
+            msg!("The offer account isn't owned by the program");
+            return Err(TokenError::InvalidOfferAccount.into());
+        }
+        let offer = CollectionOffer::from_account(offer_info)?;
+        if generate_pda_and_bump_seed(COLLECTION_OFFER, &offer.whitelist, lender.key, program_id).0!=*offer_info.key
+        {
+            msg!("The offer account doesn't match");
+            return Err(TokenError::InvalidOfferAccount.into());
+        }
+        if offer.lender!=*lender.key
+        {
+            msg!("The lender doesn't match");
+            return Err(TokenError::LenderMismatch.into());
+        }
+        msg!("Refunding {} unfilled loans to the lender",offer.fills);
+        close_account(offer_info, lender)?;
+        Ok(())
+    }
+    pub fn process_bundle_deposit(program_id: &Pubkey,accounts: &[AccountInfo],amount:u64,duration:u64,proofs:Vec<Vec<[u8; 32]>>)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let nft_owner =  next_account_info(account_info_iter)?; // sender or signer
+        let bundle_info = next_account_info(account_info_iter)?; // bundle loan account
+        let nft_vault = next_account_info(account_info_iter)?;  // nft vault address from NFTPREFIX, nft_owner, bundle and program id
+        let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+        let rent_info  = next_account_info(account_info_iter)?; // rent 
+        let system_program = next_account_info(account_info_iter)?; //system program
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        // followed for each NFT by its mint, the owner's token account, the vault's associated
+        // token account, the metadata and the whitelist
+
+        if load_config(program_id, config_info)?.deposits_paused
+        {
+            msg!("Deposits are paused");
+            return Err(TokenError::ProtocolPaused.into());
+        }
+        if !nft_owner.is_signer
+        {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        check_loan_duration(duration)?;
+        if amount==0
+        {
+            msg!("The amount requested is zero");
+            return Err(TokenError::OfferTooLow.into());
+        }
+        //one merkle proof per NFT, empty for whitelists without a root
+        if proofs.is_empty() || proofs.len()>MAX_BUNDLE_NFTS
+        {
+            msg!("A bundle holds 1 to {} NFTs",MAX_BUNDLE_NFTS);
+            return Err(TokenError::InvalidBundleSize.into());
+        }
+        let (nft_vault_address, _) = generate_pda_and_bump_seed(
+            NFTPREFIX,
+            nft_owner.key,
+            bundle_info.key,
+            program_id
+        );
+        if nft_vault_address!=*nft_vault.key
+        {
+            return Err(TokenError::InvalidVaultAddress.into());
+        }
+        let rent = Rent::get()?;
+        invoke(
+            &system_instruction::create_account(
+                nft_owner.key,
+                bundle_info.key,
+                rent.minimum_balance(BundleLoan::LEN),
+                BundleLoan::LEN as u64,
+                program_id,
+            ),
+            &[
+                nft_owner.clone(),
+                bundle_info.clone(),
+                system_program.clone(),
+            ],
+        )?;
+        let mut bundle = BundleLoan {
+            owner: *nft_owner.key,
+            lender: Pubkey::default(),
+            nfts: Vec::new(),
+            loan_start: 0,
+            loan_amt: amount,
+            loan_duration: duration,
+            interest_rate_bps: 0,
+            grace_period: u64::MAX,
+            loan_taken: false,
+            canceled: false,
+        };
+        for proof in proofs.iter()
+        {
+            let nft_mint = next_account_info(account_info_iter)?;  // mint address of nft
+            let nft_owner_nft_associated = next_account_info(account_info_iter)?;  // nft owner nft id token account address
+            let nft_associated_address = next_account_info(account_info_iter)?; // address generated from nft_vault_address and nft mint address token account address
+            let meta_data_account = next_account_info(account_info_iter)?;
+            let whitelist_info = next_account_info(account_info_iter)?; // whitelist of the NFT's collection
+            let wlist = Self::check_whitelisted(program_id, nft_mint, meta_data_account, whitelist_info, proof)?;
+            if get_associated_token_address(nft_owner.key,nft_mint.key)!=*nft_owner_nft_associated.key
+                || get_associated_token_address(nft_vault.key,nft_mint.key)!=*nft_associated_address.key
+            {
+                return Err(TokenError::InvalidTokenAccount.into());
+            }
+            if nft_associated_address.data_is_empty(){
+                invoke(            
+                    &spl_associated_token_account::instruction::create_associated_token_account(
+                        nft_owner.key,
+                        nft_vault.key,
+                        nft_mint.key,
+                    ),&[
+                        nft_owner.clone(),
+                        nft_associated_address.clone(),
+                        nft_vault.clone(),
+                        nft_mint.clone(),
+                        token_program_id.clone(),
+                        rent_info.clone(),
+                        associated_token_info.clone(),
+                        system_program.clone()
+                    ]
+                )?;
+            }
+            transfer_tokens(token_program_id, nft_owner_nft_associated, nft_associated_address, nft_owner, 1, &[])?;
+            //the bundle takes the terms least favourable to the borrower
+            bundle.interest_rate_bps=bundle.interest_rate_bps.max(wlist.interest_rate_bps);
+            bundle.grace_period=bundle.grace_period.min(wlist.grace_period);
+            bundle.nfts.push(BundleNft {
+                mint: *nft_mint.key,
+                whitelist: *whitelist_info.key,
+            });
+        }
+        bundle.serialize(&mut &mut bundle_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+    pub fn process_bundle_lend(program_id: &Pubkey,accounts: &[AccountInfo])-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lender =  next_account_info(account_info_iter)?; // sender or signer
+        let nft_owner = next_account_info(account_info_iter)?; //nft owner
+        let bundle_info = next_account_info(account_info_iter)?; // bundle loan account
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let system_program = next_account_info(account_info_iter)?;//system_program
+        // followed for each NFT by its whitelist and the price account of the whitelist's price source
+
+        if load_config(program_id, config_info)?.loans_paused
+        {
+            msg!("Lending is paused");
+            return Err(TokenError::ProtocolPaused.into());
+        }
+        if !lender.is_signer
+        {
+            msg!("The lender isn't signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut bundle = Self::load_bundle(program_id, bundle_info, nft_owner)?;
+        if bundle.loan_taken
+        {
+            msg!("The Loan is already taken");
+            return Err(TokenError::LoanAlreadyTaken.into());
+        }
+        //the bundle is worth the sum of what each NFT could borrow alone
+        let now = Clock::get()?.unix_timestamp as u64;
+        let mut max_loan = 0u64;
+        for nft in bundle.nfts.iter()
+        {
+            let whitelist_info = next_account_info(account_info_iter)?; // whitelist of the NFT's collection
+            let price_info = next_account_info(account_info_iter)?; // price account of the whitelist's price source
+            if *whitelist_info.key!=nft.whitelist || whitelist_info.owner!=program_id
+            {
+                msg!("Whitelist Info doesn't match the deposit");
+                return Err(TokenError::InvalidWhitelistAccount.into());
+            }
+            let wlist = Whitelist::from_account(whitelist_info)?;
+            if !wlist.state
+            {
+                msg!("The collection isn't whitelisted");
+                return Err(TokenError::CollectionNotWhitelisted.into());
+            }
+            let price=collection_price(program_id, whitelist_info.key, None, NATIVE_DECIMALS, &wlist.price, price_info, now)?;
+            max_loan=max_loan.checked_add(max_loan_amount(price, wlist.max_ltv_bps)?).ok_or(TokenError::Overflow)?;
+        }
+        if bundle.loan_amt > max_loan
+        {
+            msg!("The loan is higher than the limit of {}",max_loan);
+            return Err(TokenError::OfferTooHigh.into());
+        }
+        invoke(
+            &system_instruction::transfer(lender.key, nft_owner.key, bundle.loan_amt),
+            &[
+                lender.clone(),
+                nft_owner.clone(),
+                system_program.clone(),
+            ],
+        )?;
+        bundle.lender=*lender.key;
+        bundle.loan_start=now;
+        bundle.loan_taken=true;
+        bundle.serialize(&mut &mut bundle_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+    pub fn process_bundle_cancel(program_id: &Pubkey,accounts: &[AccountInfo])-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let nft_owner =  next_account_info(account_info_iter)?; // sender or signer
+        let bundle_info = next_account_info(account_info_iter)?; // bundle loan account
+        let nft_vault = next_account_info(account_info_iter)?;  // nft vault address from NFTPREFIX, nft_owner, bundle and program id
+        let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+        let rent_info  = next_account_info(account_info_iter)?; // rent 
+        let system_program = next_account_info(account_info_iter)?; //system program
+        // followed for each NFT by its mint, the vault's and the owner's associated token accounts
+
+        if !nft_owner.is_signer
+        {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut bundle = Self::load_bundle(program_id, bundle_info, nft_owner)?;
+        if bundle.loan_taken
+        {
+            msg!("The Loan is already taken, you need to pay interest to release the NFTs");
+            return Err(TokenError::LoanAlreadyTaken.into());
+        }
+        Self::release_bundle(program_id, account_info_iter, bundle_info, &bundle, nft_vault, nft_owner, token_program_id, associated_token_info, rent_info, system_program)?;
+        bundle.canceled=true;
+        bundle.serialize(&mut &mut bundle_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+    pub fn process_bundle_repay(program_id: &Pubkey,accounts: &[AccountInfo],amount:u64)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let nft_owner =  next_account_info(account_info_iter)?; // sender or signer
+        let lender = next_account_info(account_info_iter)?; //lender account
+        let bundle_info = next_account_info(account_info_iter)?; // bundle loan account
+        let nft_vault = next_account_info(account_info_iter)?;  // nft vault address from NFTPREFIX, nft_owner, bundle and program id
+        let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+        let rent_info  = next_account_info(account_info_iter)?; // rent 
+        let system_program = next_account_info(account_info_iter)?; //system program
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        // followed by the treasury and the metadata of the first NFT, then for each NFT by its mint,
+        // the vault's and the owner's associated token accounts
+
+        if !nft_owner.is_signer
+        {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut bundle = Self::load_bundle(program_id, bundle_info, nft_owner)?;
+        if !bundle.loan_taken
+        {
+            msg!("The Loan isnot taken");
+            return Err(TokenError::LoanNotTaken.into());
+        }
+        if *lender.key!=bundle.lender
+        {
+            msg!("The lender key doesn't match");
+            return Err(TokenError::LenderMismatch.into());
+        }
+        let now = Clock::get()?.unix_timestamp as u64;
+        let elapsed = now.checked_sub(bundle.loan_start).ok_or(TokenError::Overflow)?;
+        if elapsed > bundle.loan_duration.checked_add(bundle.grace_period).ok_or(TokenError::Overflow)?
+        {
+            msg!("The bundle is forfeited to the lender");
+            return Err(TokenError::LoanExpired.into());
+        }
+        let total_payment = calculate_repayment(bundle.loan_amt, bundle.interest_rate_bps, elapsed)?;
+        let interest = total_payment.checked_sub(bundle.loan_amt).ok_or(TokenError::Overflow)?;
+        if amount<total_payment
+        {
+            msg!("You should pay {} this much amount to clear the debt",total_payment);
+            return Err(TokenError::InsufficientRepayment.into());
+        }
+        //the creators of several collections can't share one royalty, only the protocol fee is taken
+        let config = load_config(program_id, config_info)?;
+        Self::pay_lender(
+            program_id,
+            account_info_iter,
+            &config,
+            nft_owner,
+            &[],
+            nft_owner,
+            lender,
+            &bundle.nfts[0].mint,
+            token_program_id,
+            system_program,
+            None,
+            0,
+            bundle.loan_amt,
+            interest,
+        )?;
+        Self::release_bundle(program_id, account_info_iter, bundle_info, &bundle, nft_vault, nft_owner, token_program_id, associated_token_info, rent_info, system_program)?;
+        bundle.canceled=true;
+        bundle.serialize(&mut &mut bundle_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+    pub fn process_bundle_foreclose(program_id: &Pubkey,accounts: &[AccountInfo])-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lender =  next_account_info(account_info_iter)?; // sender or signer
+        let nft_owner = next_account_info(account_info_iter)?; //nft owner
+        let bundle_info = next_account_info(account_info_iter)?; // bundle loan account
+        let nft_vault = next_account_info(account_info_iter)?;  // nft vault address from NFTPREFIX, nft_owner, bundle and program id
+        let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+        let associated_token_info = next_account_info(account_info_iter)?; // Associated token master {ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL}
+        let rent_info  = next_account_info(account_info_iter)?; // rent 
+        let system_program = next_account_info(account_info_iter)?; //system program
+        // followed for each NFT by its mint, the vault's and the lender's associated token accounts
+
+        if !lender.is_signer
+        {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut bundle = Self::load_bundle(program_id, bundle_info, nft_owner)?;
+        if !bundle.loan_taken
+        {
+            msg!("The Loan isnot taken");
+            return Err(TokenError::LoanNotTaken.into());
+        }
+        if *lender.key!=bundle.lender
+        {
+            msg!("The lender key doesn't match");
+            return Err(TokenError::LenderMismatch.into());
+        }
+        let foreclosable_after = bundle.loan_duration.checked_add(bundle.grace_period).ok_or(TokenError::Overflow)?;
+        let now = Clock::get()?.unix_timestamp as u64;
+        if now.checked_sub(bundle.loan_start).ok_or(TokenError::Overflow)? <= foreclosable_after
+        {
+            msg!("The loan duration and grace period of {} seconds haven't passed yet",foreclosable_after);
+            return Err(TokenError::LoanNotExpired.into());
+        }
+        msg!("Releasing the NFTs to the lender");
+        Self::release_bundle(program_id, account_info_iter, bundle_info, &bundle, nft_vault, lender, token_program_id, associated_token_info, rent_info, system_program)?;
+        bundle.canceled=true;
+        bundle.serialize(&mut &mut bundle_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+    /// Reads a bundle that is still open and checks it belongs to `nft_owner`.
+    fn load_bundle(program_id: &Pubkey, bundle_info: &AccountInfo, nft_owner: &AccountInfo) -> Result<BundleLoan, ProgramError> {
+        if bundle_info.owner!=program_id
+        {
+            msg!("The bundle account isn't owned by the program");
+            return Err(TokenError::InvalidDataAccount.into());
+        }
+        let bundle = BundleLoan::from_account(bundle_info)?;
+        if bundle.owner!=*nft_owner.key
+        {
+            msg!("The NFT owner doesn't match");
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if bundle.canceled
+        {
+            msg!("The bundle is already settled");
+            return Err(TokenError::LoanCanceled.into());
+        }
+        Ok(bundle)
+    }
+    /// Moves every NFT of `bundle` from its vault to the associated token
+    /// accounts of `recipient`, which pays for the ones it lacks.
+    fn release_bundle<'a, 'b>(
+        program_id: &Pubkey,
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        bundle_info: &AccountInfo<'b>,
+        bundle: &BundleLoan,
+        nft_vault: &AccountInfo<'b>,
+        recipient: &AccountInfo<'b>,
+        token_program_id: &AccountInfo<'b>,
+        associated_token_info: &AccountInfo<'b>,
+        rent_info: &AccountInfo<'b>,
+        system_program: &AccountInfo<'b>,
+    ) -> ProgramResult {
+        let (nft_vault_address, bump_seed) = generate_pda_and_bump_seed(
+            NFTPREFIX,
+            &bundle.owner,
+            bundle_info.key,
+            program_id
+        );
+        if nft_vault_address!=*nft_vault.key
+        {
+            return Err(TokenError::InvalidVaultAddress.into());
+        }
+        let nft_vault_signer_seeds: &[&[_]] = &[
+            NFTPREFIX.as_bytes(),
+            &bundle.owner.to_bytes(),
+            &bundle_info.key.to_bytes(),
+            &[bump_seed],
+        ];
+        for nft in bundle.nfts.iter()
+        {
+            let nft_mint = next_account_info(account_info_iter)?;  // mint address of nft
+            let nft_associated_address = next_account_info(account_info_iter)?; // vault's associated token account of the mint
+            let recipient_token_account = next_account_info(account_info_iter)?; // recipient's associated token account of the mint
+            if *nft_mint.key!=nft.mint
+            {
+                msg!("The NFT mint doesn't match");
+                return Err(TokenError::MintMismatch.into());
+            }
+            check_associated_token_account(nft_associated_address, nft_vault.key, nft_mint.key)?;
+            check_associated_token_account(recipient_token_account, recipient.key, nft_mint.key)?;
+            if recipient_token_account.data_is_empty(){
+                invoke(            
+                    &spl_associated_token_account::instruction::create_associated_token_account(
+                        recipient.key,
+                        recipient.key,
+                        nft_mint.key,
+                    ),&[
+                        recipient.clone(),
+                        recipient_token_account.clone(),
+                        nft_mint.clone(),
+                        token_program_id.clone(),
+                        rent_info.clone(),
+                        associated_token_info.clone(),
+                        system_program.clone()
                     ]
-                )?;}
-
-            //All conditions satisfied release the NFT
-            invoke_signed(
-                &spl_token::instruction::transfer(
-                    token_program_id.key,
-                    nft_associated_address.key,
-                    lender_token_account.key,
-                    nft_vault.key,
-                    &[nft_vault.key],
-                    1
-                )?,
-                &[
-                    token_program_id.clone(),
-                    lender_token_account.clone(),
-                    nft_associated_address.clone(),
-                    nft_owner.clone(),
-                    system_program.clone()
-                ],
-                &[&nft_vault_signer_seeds],
-            )?;          
-  
-            data.canceled=true;
-            data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
-            Ok(())
+                )?;
+            }
+            transfer_tokens(token_program_id, nft_associated_address, recipient_token_account, nft_vault, 1, &[nft_vault_signer_seeds])?;
         }
-
-   
-        pub fn process_whitelist(program_id: &Pubkey,accounts: &[AccountInfo],number:u64)-> ProgramResult {
+        Ok(())
+    }
+        pub fn process_whitelist(program_id: &Pubkey,accounts: &[AccountInfo],number:u64,price:PriceConfig,max_ltv_bps:u64,collection:bool)-> ProgramResult {
         //depositing the NFT
         let account_info_iter = &mut accounts.iter();
         let admin =  next_account_info(account_info_iter)?; // sender or signer
+        let config_info = next_account_info(account_info_iter)?; // protocol config
         let system_program = next_account_info(account_info_iter)?; //system program
         let whitelist_info =next_account_info(account_info_iter)?; //
         let creator1=next_account_info(account_info_iter)?; //
         
-        if !admin.is_signer
+        //verifying admin
+        check_admin(program_id, config_info, admin)?;
+        if max_ltv_bps>BASIS_POINTS
         {
-            msg!("Admin isn't the signer");
-            return Err(ProgramError::MissingRequiredSignature); 
+            msg!("The loan-to-value can't exceed 10000 basis points");
+            return Err(TokenError::InvalidLtv.into());
         }
-         //verifying admin
-         let admin_address="5j2V6qBBt7S6guRhP6Jg4nUeYUhYmySoZAyLS7uTdREt"
-         .parse::<Pubkey>()
-         .expect("Failed to parse admin address");
- 
-         if admin_address!=*admin.key
-         {
-             msg!("Admin Address doesn't match");
-             return Err(ProgramError::MissingRequiredSignature);
-         }
 
         let first_creator=creator1.key;
         let (whitelist_address, bump_seed)=derive_whitelist_address(first_creator, program_id);
@@ -888,7 +3098,7 @@ impl Processor {
         if whitelist_address!=*whitelist_info.key
         {
             msg!("Whitelist Info key doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::InvalidWhitelistAccount.into());
         }
          //signer seeds for nft_vault
          let whitelist_signer_seeds: &[&[_]] = &[
@@ -899,7 +3109,11 @@ impl Processor {
          //rent account
          let rent = Rent::get()?;
          let number=number as usize;
-         let size = std::mem::size_of::<Whitelist>()+number*std::mem::size_of::<Pubkey>();
+         let size = number
+             .checked_mul(std::mem::size_of::<Pubkey>())
+             .and_then(|n| n.checked_add(std::mem::size_of::<Whitelist>()))
+             .and_then(|n| n.checked_add(MAX_LOAN_MINTS * std::mem::size_of::<LoanCurrency>()))
+             .ok_or(TokenError::Overflow)?;
          let transfer_amount =  rent.minimum_balance(size);
          invoke_signed(
              &system_instruction::create_account(
@@ -919,11 +3133,21 @@ impl Processor {
 
         let mut  wlist = Whitelist::from_account(whitelist_info)?;
         wlist.state=true;
-        wlist.producer.push(*first_creator);
-        for _ in 1..number
+        wlist.price=price;
+        wlist.max_ltv_bps=max_ltv_bps;
+        if collection
+        {
+            //keyed on the collection mint, the creators aren't compared
+            wlist.collection=Some(*first_creator);
+        }
+        else
         {
-            let creator=next_account_info(account_info_iter)?; //
-            wlist.producer.push(*creator.key);
+            wlist.producer.push(*first_creator);
+            for _ in 1..number
+            {
+                let creator=next_account_info(account_info_iter)?; //
+                wlist.producer.push(*creator.key);
+            }
         }
         wlist.serialize(&mut *whitelist_info.data.borrow_mut())?;        
         Ok(())
@@ -932,37 +3156,25 @@ impl Processor {
         //depositing the NFT
         let account_info_iter = &mut accounts.iter();
         let admin =  next_account_info(account_info_iter)?; // sender or signer
+        let config_info = next_account_info(account_info_iter)?; // protocol config
         let whitelist_info =next_account_info(account_info_iter)?; //
         let creator1=next_account_info(account_info_iter)?; //
 
-        if !admin.is_signer
-        {
-            msg!("Admin isn't the signer");
-            return Err(ProgramError::MissingRequiredSignature); 
-        }
-         //verifying admin
-         let admin_address="5j2V6qBBt7S6guRhP6Jg4nUeYUhYmySoZAyLS7uTdREt"
-         .parse::<Pubkey>()
-         .expect("Failed to parse admin address");
- 
-         if admin_address!=*admin.key
-         {
-             msg!("Admin Address doesn't match");
-             return Err(ProgramError::MissingRequiredSignature);
-         }
+        //verifying admin
+        check_admin(program_id, config_info, admin)?;
         let first_creator=creator1.key;
         let (whitelist_address, _)=derive_whitelist_address(first_creator, program_id);
 
         if whitelist_address!=*whitelist_info.key
         {
             msg!("Whitelist Info key doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::InvalidWhitelistAccount.into());
         }   
 
         if whitelist_info.owner!=program_id
         {
             msg!("Whitelist Info not owned by the program");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::InvalidWhitelistAccount.into());
         }
         let mut  wlist = Whitelist::from_account(whitelist_info)?;
         msg!("The state is changed whitelisting off");
@@ -970,50 +3182,385 @@ impl Processor {
         wlist.serialize(&mut *whitelist_info.data.borrow_mut())?;        
         Ok(())
     }
-    pub fn process_update_interest(program_id: &Pubkey,accounts: &[AccountInfo],interest:u64)-> ProgramResult {
-        //depositing the NFT
+    /// Checks the admin and the collection's whitelist, applies `update` to it
+    /// and writes it back. Shared by the whitelist settings handlers.
+    fn update_whitelist<F>(program_id: &Pubkey,accounts: &[AccountInfo],update:F)-> ProgramResult
+    where
+        F: FnOnce(&mut Whitelist) -> ProgramResult,
+    {
         let account_info_iter = &mut accounts.iter();
         let admin =  next_account_info(account_info_iter)?; // sender or signer
+        let config_info = next_account_info(account_info_iter)?; // protocol config
         let whitelist_info =next_account_info(account_info_iter)?; //
         let creator1=next_account_info(account_info_iter)?; //
 
-        if !admin.is_signer
-        {
-            msg!("Admin isn't the signer");
-            return Err(ProgramError::MissingRequiredSignature); 
-        }
-         //verifying admin
-         let admin_address="5j2V6qBBt7S6guRhP6Jg4nUeYUhYmySoZAyLS7uTdREt"
-         .parse::<Pubkey>()
-         .expect("Failed to parse admin address");
- 
-         if admin_address!=*admin.key
-         {
-             msg!("Admin Address doesn't match");
-             return Err(ProgramError::MissingRequiredSignature);
-         }
+        //verifying admin
+        check_admin(program_id, config_info, admin)?;
         let first_creator=creator1.key;
         let (whitelist_address, _)=derive_whitelist_address(first_creator, program_id);
 
         if whitelist_address!=*whitelist_info.key
         {
             msg!("Whitelist Info key doesn't match");
-            return Err(ProgramError::MissingRequiredSignature);
-        }   
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
 
         if whitelist_info.owner!=program_id
         {
             msg!("Whitelist Info not owned by the program");
-            return Err(ProgramError::MissingRequiredSignature);
+            return Err(TokenError::InvalidWhitelistAccount.into());
         }
         let mut wlist = Whitelist::from_account(whitelist_info)?;
         if !wlist.state
         {
             msg!("Whitelist Cancelled");
+            return Err(TokenError::CollectionNotWhitelisted.into());
+        }
+        update(&mut wlist)?;
+        wlist.serialize(&mut *whitelist_info.data.borrow_mut())?;
+        Ok(())
+    }
+    pub fn process_update_interest(program_id: &Pubkey,accounts: &[AccountInfo],interest:u64)-> ProgramResult {
+        Self::update_whitelist(program_id, accounts, |wlist| {
+            check_interest_rate(interest)?;
+            wlist.interest_rate_bps=interest;
+            Ok(())
+        })
+    }
+    pub fn process_update_ltv(program_id: &Pubkey,accounts: &[AccountInfo],max_ltv_bps:u64)-> ProgramResult {
+        Self::update_whitelist(program_id, accounts, |wlist| {
+            if max_ltv_bps>BASIS_POINTS
+            {
+                msg!("The loan-to-value can't exceed 10000 basis points");
+                return Err(TokenError::InvalidLtv.into());
+            }
+            wlist.max_ltv_bps=max_ltv_bps;
+            Ok(())
+        })
+    }
+    pub fn process_update_merkle_root(program_id: &Pubkey,accounts: &[AccountInfo],merkle_root:Option<[u8; 32]>)-> ProgramResult {
+        Self::update_whitelist(program_id, accounts, |wlist| {
+            wlist.merkle_root=merkle_root;
+            Ok(())
+        })
+    }
+    pub fn process_update_grace(program_id: &Pubkey,accounts: &[AccountInfo],grace_period:u64,late_fee_bps:u64)-> ProgramResult {
+        Self::update_whitelist(program_id, accounts, |wlist| {
+            if late_fee_bps>BASIS_POINTS
+            {
+                msg!("The late fee can't exceed 10000 basis points");
+                return Err(TokenError::InvalidFee.into());
+            }
+            wlist.grace_period=grace_period;
+            wlist.late_fee_bps=late_fee_bps;
+            Ok(())
+        })
+    }
+    pub fn process_update_auction(program_id: &Pubkey,accounts: &[AccountInfo],auction:Option<AuctionConfig>)-> ProgramResult {
+        Self::update_whitelist(program_id, accounts, |wlist| {
+            if let Some(config) = &auction
+            {
+                if config.duration==0 || config.end_discount_bps>BASIS_POINTS
+                {
+                    msg!("The auction needs a duration and can't end below zero");
+                    return Err(TokenError::InvalidAuctionConfig.into());
+                }
+            }
+            wlist.auction=auction;
+            Ok(())
+        })
+    }
+    pub fn process_update_royalty(program_id: &Pubkey,accounts: &[AccountInfo],royalty_bps:u64)-> ProgramResult {
+        Self::update_whitelist(program_id, accounts, |wlist| {
+            if royalty_bps>BASIS_POINTS
+            {
+                msg!("The royalty can't exceed 10000 basis points");
+                return Err(TokenError::InvalidRoyalty.into());
+            }
+            wlist.royalty_bps=royalty_bps;
+            Ok(())
+        })
+    }
+    pub fn process_update_loan_mints(program_id: &Pubkey,accounts: &[AccountInfo],loan_mints:Vec<LoanCurrency>)-> ProgramResult {
+        Self::update_whitelist(program_id, accounts, |wlist| {
+            if loan_mints.len()>MAX_LOAN_MINTS
+            {
+                msg!("A collection can't have more than {} loan currencies",MAX_LOAN_MINTS);
+                return Err(TokenError::TooManyLoanMints.into());
+            }
+            //loans already deposited in a removed currency can still be repaid
+            wlist.loan_mints=loan_mints;
+            Ok(())
+        })
+    }
+    pub fn process_update_price(program_id: &Pubkey,accounts: &[AccountInfo],price:PriceConfig)-> ProgramResult {
+        Self::update_whitelist(program_id, accounts, |wlist| {
+            wlist.price=price;
+            Ok(())
+        })
+    }
+    pub fn process_post_price(program_id: &Pubkey,accounts: &[AccountInfo],price:u64,currency_mint:Option<Pubkey>)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin =  next_account_info(account_info_iter)?; // sender or signer
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let system_program = next_account_info(account_info_iter)?; //system program
+        let whitelist_info =next_account_info(account_info_iter)?; //
+        let price_info = next_account_info(account_info_iter)?; // manual price from PRICE, whitelist, currency mint and program id
+        let creator1=next_account_info(account_info_iter)?; //
+
+        //verifying admin
+        check_admin(program_id, config_info, admin)?;
+        let (whitelist_address, _)=derive_whitelist_address(creator1.key, program_id);
+
+        if whitelist_address!=*whitelist_info.key || whitelist_info.owner!=program_id
+        {
+            msg!("Whitelist Info key doesn't match");
+            return Err(TokenError::InvalidWhitelistAccount.into());
+        }
+        let (price_address, bump_seed)=derive_manual_price_address(whitelist_info.key, currency_mint.as_ref(), program_id);
+        if price_address!=*price_info.key
+        {
+            msg!("The manual price account doesn't match");
+            return Err(TokenError::InvalidPriceAccount.into());
+        }
+        if price==0
+        {
+            msg!("The price can't be zero");
+            return Err(TokenError::InvalidPrice.into());
+        }
+        if price_info.data_is_empty()
+        {
+            let whitelist_bytes = whitelist_info.key.to_bytes();
+            let mint_bytes = currency_mint.map(|mint| mint.to_bytes());
+            let bump = [bump_seed];
+            let mut price_signer_seeds: Vec<&[u8]> = vec![PRICE.as_bytes(), &whitelist_bytes];
+            if let Some(mint_bytes) = &mint_bytes
+            {
+                price_signer_seeds.push(mint_bytes);
+            }
+            price_signer_seeds.push(&bump);
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    admin.key,
+                    price_info.key,
+                    rent.minimum_balance(ManualPrice::LEN),
+                    ManualPrice::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    admin.clone(),
+                    price_info.clone(),
+                    system_program.clone(),
+                ],
+                &[&price_signer_seeds],
+            )?;
+        }
+        else if price_info.owner!=program_id
+        {
+            msg!("The manual price account isn't owned by the program");
+            return Err(TokenError::InvalidPriceAccount.into());
+        }
+        let manual = ManualPrice{
+            whitelist: *whitelist_info.key,
+            price,
+            update_time: Clock::get()?.unix_timestamp as u64,
+        };
+        manual.serialize(&mut *price_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    pub fn process_withdraw_fees(program_id: &Pubkey,accounts: &[AccountInfo],amount:u64,currency_mint:Option<Pubkey>)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin =  next_account_info(account_info_iter)?; // sender or signer
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let treasury = next_account_info(account_info_iter)?; // treasury from TREASURY and program id
+        let destination = next_account_info(account_info_iter)?; // receiver of the fees
+
+        //verifying admin
+        check_admin(program_id, config_info, admin)?;
+        let (treasury_address, treasury_bump)=derive_treasury_address(program_id);
+        if treasury_address!=*treasury.key || treasury.owner!=program_id
+        {
+            msg!("Treasury key doesn't match");
+            return Err(TokenError::InvalidTreasuryAccount.into());
+        }
+        match currency_mint
+        {
+            Some(mint) =>
+            {
+                let treasury_token_account = next_account_info(account_info_iter)?; // treasury's associated token account of the currency
+                let destination_token_account = next_account_info(account_info_iter)?; // receiver's token account of the currency
+                let token_program_id = next_account_info(account_info_iter)?; // TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA
+                check_associated_token_account(treasury_token_account, treasury.key, &mint)?;
+                if get_token_balance(treasury_token_account)?<amount
+                {
+                    msg!("The treasury holds less than {}",amount);
+                    return Err(TokenError::InsufficientTreasury.into());
+                }
+                let treasury_signer_seeds: &[&[_]] = &[
+                    TREASURY.as_bytes(),
+                    &[treasury_bump],
+                ];
+                transfer_tokens(token_program_id, treasury_token_account, destination_token_account, treasury, amount, &[treasury_signer_seeds])?;
+            }
+            None =>
+            {
+                //the treasury stays rent exempt
+                let available = treasury.lamports().saturating_sub(Rent::get()?.minimum_balance(0));
+                if available<amount
+                {
+                    msg!("The treasury holds {} withdrawable lamports",available);
+                    return Err(TokenError::InsufficientTreasury.into());
+                }
+                transfer_lamports(treasury, destination, amount)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn process_initialize_config(program_id: &Pubkey,accounts: &[AccountInfo],fee_bps:u64)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin =  next_account_info(account_info_iter)?; // sender or signer, becomes the admin
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+        let system_program = next_account_info(account_info_iter)?; //system program
+        let treasury = next_account_info(account_info_iter)?; // treasury from TREASURY and program id
+        let program_data = next_account_info(account_info_iter)?; // program data account of the upgradeable loader
+
+        if !admin.is_signer
+        {
+            msg!("Admin isn't the signer");
             return Err(ProgramError::MissingRequiredSignature);
         }
-        wlist.daily_interest_rate=interest;
-        wlist.serialize(&mut *whitelist_info.data.borrow_mut())?;        
+        //only the upgrade authority of the deployment can claim the admin role
+        if upgrade_authority(program_id, program_data)?!=Some(*admin.key)
+        {
+            msg!("Admin isn't the upgrade authority of the program");
+            return Err(TokenError::Unauthorized.into());
+        }
+        let (treasury_address, treasury_bump)=derive_treasury_address(program_id);
+        if treasury_address!=*treasury.key
+        {
+            msg!("Treasury key doesn't match");
+            return Err(TokenError::InvalidTreasuryAccount.into());
+        }
+        let (config_address, bump_seed)=derive_config_address(program_id);
+        if config_address!=*config_info.key
+        {
+            msg!("Protocol config key doesn't match");
+            return Err(TokenError::InvalidConfigAccount.into());
+        }
+        if !config_info.data_is_empty()
+        {
+            msg!("Protocol config is already initialized");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if fee_bps>BASIS_POINTS
+        {
+            msg!("The fee can't exceed 10000 basis points");
+            return Err(TokenError::InvalidFee.into());
+        }
+        let config_signer_seeds: &[&[_]] = &[
+            CONFIG.as_bytes(),
+            &[bump_seed],
+        ];
+        let rent = Rent::get()?;
+        invoke_signed(
+            &system_instruction::create_account(
+                admin.key,
+                config_info.key,
+                rent.minimum_balance(ProtocolConfig::LEN),
+                ProtocolConfig::LEN as u64,
+                program_id,
+            ),
+            &[
+                admin.clone(),
+                config_info.clone(),
+                system_program.clone(),
+            ],
+            &[&config_signer_seeds],
+        )?;
+        //the treasury holds no data, it only collects fees
+        let treasury_signer_seeds: &[&[_]] = &[
+            TREASURY.as_bytes(),
+            &[treasury_bump],
+        ];
+        invoke_signed(
+            &system_instruction::create_account(
+                admin.key,
+                treasury.key,
+                rent.minimum_balance(0),
+                0,
+                program_id,
+            ),
+            &[
+                admin.clone(),
+                treasury.clone(),
+                system_program.clone(),
+            ],
+            &[&treasury_signer_seeds],
+        )?;
+        let config = ProtocolConfig{
+            admin: *admin.key,
+            pending_admin: None,
+            fee_bps,
+            deposits_paused: false,
+            loans_paused: false,
+        };
+        config.serialize(&mut *config_info.data.borrow_mut())?;
+        Ok(())
+    }
+    pub fn process_update_config(program_id: &Pubkey,accounts: &[AccountInfo],update:ProcessUpdateConfig)-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let admin =  next_account_info(account_info_iter)?; // sender or signer
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+
+        let mut config = check_admin(program_id, config_info, admin)?;
+        if update.fee_bps>BASIS_POINTS
+        {
+            msg!("The fee can't exceed 10000 basis points");
+            return Err(TokenError::InvalidFee.into());
+        }
+        config.fee_bps=update.fee_bps;
+        config.deposits_paused=update.deposits_paused;
+        config.loans_paused=update.loans_paused;
+        if update.clear_pending_admin
+        {
+            if update.new_admin.is_some()
+            {
+                msg!("A new admin can't be proposed while clearing the nomination");
+                return Err(TokenError::InvalidInstruction.into());
+            }
+            msg!("The pending admin nomination is withdrawn");
+            config.pending_admin=None;
+        }
+        if let Some(new_admin) = update.new_admin
+        {
+            msg!("{} is proposed as the new admin",new_admin);
+            config.pending_admin=Some(new_admin);
+        }
+        config.serialize(&mut *config_info.data.borrow_mut())?;
+        Ok(())
+    }
+    pub fn process_accept_admin(program_id: &Pubkey,accounts: &[AccountInfo])-> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let new_admin =  next_account_info(account_info_iter)?; // sender or signer
+        let config_info = next_account_info(account_info_iter)?; // protocol config
+
+        if !new_admin.is_signer
+        {
+            msg!("The new admin isn't the signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut config = load_config(program_id, config_info)?;
+        if config.pending_admin!=Some(*new_admin.key)
+        {
+            msg!("The signer isn't the pending admin");
+            return Err(TokenError::NotPendingAdmin.into());
+        }
+        config.admin=*new_admin.key;
+        config.pending_admin=None;
+        config.serialize(&mut *config_info.data.borrow_mut())?;
         Ok(())
     }
 
@@ -1021,13 +3568,13 @@ impl Processor {
     {
         let instruction = TokenInstruction::unpack(input)?;
         match instruction {
-            TokenInstruction::ProcessDeposit => {
+            TokenInstruction::ProcessDeposit(ProcessDeposit{duration,currency_mint,proof}) => {
                 msg!("Instruction: Deposit NFT");
-                Self::process_deposit_nft(program_id,accounts,)
+                Self::process_deposit_nft(program_id,accounts,duration,currency_mint,proof)
             }
-            TokenInstruction::ProcessOffer(ProcessOffer{amount}) => {
+            TokenInstruction::ProcessOffer(ProcessOffer{amount,duration}) => {
                 msg!("Instruction:  Offer");
-                Self::process_offer(program_id, accounts, amount)
+                Self::process_offer(program_id, accounts, amount, duration)
             }
             TokenInstruction::ProcessSelection => {
                 msg!("Instruction:  Selection");
@@ -1045,9 +3592,9 @@ impl Processor {
                 msg!("Instruction:  Lender Action");
                 Self::process_lending(program_id,accounts)
             }
-            TokenInstruction::ProcessWhitelist(ProcessWhitelist{number}) => {
+            TokenInstruction::ProcessWhitelist(ProcessWhitelist{number,price,max_ltv_bps,collection}) => {
                 msg!("Instruction:  Whitelist Collection");
-                Self::process_whitelist(program_id,accounts,number)
+                Self::process_whitelist(program_id,accounts,number,price,max_ltv_bps,collection)
             }
             TokenInstruction::ProcessRemoveWhitelist => {
                 msg!("Instruction:  Lender Action");
@@ -1056,6 +3603,130 @@ impl Processor {
             TokenInstruction::ProcessUpdate(ProcessUpdate{interest}) => {
                 msg!("Instruction:  Whitelist Collection");
                 Self::process_update_interest(program_id,accounts,interest)
+            }
+            TokenInstruction::ProcessInitConfig(ProcessInitConfig{fee_bps}) => {
+                msg!("Instruction:  Initialize Config");
+                Self::process_initialize_config(program_id,accounts,fee_bps)
+            }
+            TokenInstruction::ProcessUpdateConfig(update) => {
+                msg!("Instruction:  Update Config");
+                Self::process_update_config(program_id,accounts,update)
+            }
+            TokenInstruction::ProcessAcceptAdmin => {
+                msg!("Instruction:  Accept Admin");
+                Self::process_accept_admin(program_id,accounts)
+            }
+            TokenInstruction::ProcessWithdrawOffer => {
+                msg!("Instruction:  Withdraw Offer");
+                Self::process_withdraw_offer(program_id,accounts)
+            }
+            TokenInstruction::ProcessUpdatePrice(ProcessUpdatePrice{price}) => {
+                msg!("Instruction:  Update Price Source");
+                Self::process_update_price(program_id,accounts,price)
+            }
+            TokenInstruction::ProcessPostPrice(ProcessPostPrice{price,currency_mint}) => {
+                msg!("Instruction:  Post Price");
+                Self::process_post_price(program_id,accounts,price,currency_mint)
+            }
+            TokenInstruction::ProcessUpdateLtv(ProcessUpdateLtv{max_ltv_bps}) => {
+                msg!("Instruction:  Update Loan To Value");
+                Self::process_update_ltv(program_id,accounts,max_ltv_bps)
+            }
+            TokenInstruction::ProcessUpdateLoanMints(ProcessUpdateLoanMints{loan_mints}) => {
+                msg!("Instruction:  Update Loan Mints");
+                Self::process_update_loan_mints(program_id,accounts,loan_mints)
+            }
+            TokenInstruction::ProcessWithdrawFees(ProcessWithdrawFees{amount,currency_mint}) => {
+                msg!("Instruction:  Withdraw Fees");
+                Self::process_withdraw_fees(program_id,accounts,amount,currency_mint)
+            }
+            TokenInstruction::ProcessUpdateRoyalty(ProcessUpdateRoyalty{royalty_bps}) => {
+                msg!("Instruction:  Update Royalty");
+                Self::process_update_royalty(program_id,accounts,royalty_bps)
+            }
+            TokenInstruction::ProcessUpdateMerkleRoot(ProcessUpdateMerkleRoot{merkle_root}) => {
+                msg!("Instruction:  Update Merkle Root");
+                Self::process_update_merkle_root(program_id,accounts,merkle_root)
+            }
+            TokenInstruction::ProcessUpdateAuction(ProcessUpdateAuction{auction}) => {
+                msg!("Instruction:  Update Auction");
+                Self::process_update_auction(program_id,accounts,auction)
+            }
+            TokenInstruction::ProcessStartAuction => {
+                msg!("Instruction:  Start Auction");
+                Self::process_start_auction(program_id,accounts)
+            }
+            TokenInstruction::ProcessBid(ProcessBid{max_price}) => {
+                msg!("Instruction:  Bid");
+                Self::process_bid(program_id,accounts,max_price)
+            }
+            TokenInstruction::ProcessUpdateGrace(ProcessUpdateGrace{grace_period,late_fee_bps}) => {
+                msg!("Instruction:  Update Grace Period");
+                Self::process_update_grace(program_id,accounts,grace_period,late_fee_bps)
+            }
+            TokenInstruction::ProcessExtend(ProcessExtend{duration,interest_rate_bps}) => {
+                msg!("Instruction:  Extend Loan");
+                Self::process_extend(program_id,accounts,duration,interest_rate_bps)
+            }
+            TokenInstruction::ProcessRefinance => {
+                msg!("Instruction:  Refinance Loan");
+                Self::process_refinance(program_id,accounts)
+            }
+            TokenInstruction::ProcessPartialRepay(ProcessPartialRepay{amount}) => {
+                msg!("Instruction:  Partial Repayment");
+                Self::process_partial_repay(program_id,accounts,amount)
+            }
+            TokenInstruction::ProcessPoolDeposit(ProcessPoolDeposit{amount}) => {
+                msg!("Instruction:  Pool Deposit");
+                Self::process_pool_deposit(program_id,accounts,amount)
+            }
+            TokenInstruction::ProcessPoolWithdraw(ProcessPoolWithdraw{shares}) => {
+                msg!("Instruction:  Pool Withdraw");
+                Self::process_pool_withdraw(program_id,accounts,shares)
+            }
+            TokenInstruction::ProcessPoolBorrow(ProcessPoolBorrow{amount}) => {
+                msg!("Instruction:  Pool Borrow");
+                Self::process_pool_borrow(program_id,accounts,amount)
+            }
+            TokenInstruction::ProcessCollectionOffer(ProcessCollectionOffer{amount,duration,fills}) => {
+                msg!("Instruction:  Collection Offer");
+                Self::process_collection_offer(program_id,accounts,amount,duration,fills)
+            }
+            TokenInstruction::ProcessAcceptCollectionOffer(ProcessAcceptCollectionOffer{proof}) => {
+                msg!("Instruction:  Accept Collection Offer");
+                Self::process_accept_collection_offer(program_id,accounts,proof)
+            }
+            TokenInstruction::ProcessWithdrawCollectionOffer => {
+                msg!("Instruction:  Withdraw Collection Offer");
+                Self::process_withdraw_collection_offer(program_id,accounts)
+            }
+            TokenInstruction::ProcessDepositAndBorrow(ProcessDepositAndBorrow{duration,amount,proof}) => {
+                msg!("Instruction:  Deposit And Borrow");
+                Self::process_deposit_and_borrow(program_id,accounts,duration,amount,proof)
+            }
+            TokenInstruction::ProcessBundleDeposit(ProcessBundleDeposit{amount,duration,proofs}) => {
+                msg!("Instruction:  Bundle Deposit");
+                Self::process_bundle_deposit(program_id,accounts,amount,duration,proofs)
+            }
+            TokenInstruction::ProcessBundleLend => {
+                msg!("Instruction:  Bundle Lend");
+                Self::process_bundle_lend(program_id,accounts)
+            }
+            TokenInstruction::ProcessBundleCancel => {
+                msg!("Instruction:  Bundle Cancel");
+                Self::process_bundle_cancel(program_id,accounts)
+            }
+            TokenInstruction::ProcessBundleRepay(ProcessBundleRepay{amount}) => {
+                msg!("Instruction:  Bundle Repay");
+                Self::process_bundle_repay(program_id,accounts,amount)
+            }
+            TokenInstruction::ProcessBundleForeclose => {
+                msg!("Instruction:  Bundle Foreclose");
+                Self::process_bundle_foreclose(program_id,accounts)
+            }
+            TokenInstruction::ProcessDepositEscrowless(ProcessDeposit{duration,currency_mint,proof}) => {
+                msg!("Instruction:  Deposit Escrowless");
+                Self::process_deposit_escrowless(program_id,accounts,duration,currency_mint,proof)
             }}
     }
 }
@@ -1070,6 +3741,67 @@ impl PrintProgramError for TokenError {
             TokenError::Overflow => msg!("Error: Token Overflow"),
             TokenError::Notstarted =>msg!("Error: Not started"),
             TokenError::TokenFinished =>msg!("Error: Token Finished"),
+            TokenError::InvalidMetadataAccount => msg!("Error: Metadata account doesn't match the NFT mint"),
+            TokenError::InvalidWhitelistAccount => msg!("Error: Invalid whitelist account"),
+            TokenError::CollectionNotWhitelisted => msg!("Error: Collection is not whitelisted"),
+            TokenError::InvalidTokenAccount => msg!("Error: Invalid associated token account"),
+            TokenError::InvalidVaultAddress => msg!("Error: Invalid vault address"),
+            TokenError::InvalidDataAccount => msg!("Error: Invalid NFT details account"),
+            TokenError::OwnerMismatch => msg!("Error: NFT owner doesn't match"),
+            TokenError::MintMismatch => msg!("Error: NFT mint doesn't match"),
+            TokenError::LenderMismatch => msg!("Error: Lender doesn't match"),
+            TokenError::NftNotInVault => msg!("Error: NFT is not in the vault"),
+            TokenError::LoanAlreadyTaken => msg!("Error: Loan is already taken"),
+            TokenError::LoanNotTaken => msg!("Error: Loan is not taken"),
+            TokenError::LoanNotOffered => msg!("Error: Loan is not offered"),
+            TokenError::LoanCanceled => msg!("Error: Loan is canceled"),
+            TokenError::LoanStillActive => msg!("Error: Loan is still active"),
+            TokenError::LoanExpired => msg!("Error: Loan is expired"),
+            TokenError::LoanNotExpired => msg!("Error: Loan is not expired"),
+            TokenError::InvalidLoanDuration => msg!("Error: Invalid loan duration"),
+            TokenError::OfferTooLow => msg!("Error: Offer is too low"),
+            TokenError::InvalidOfferAccount => msg!("Error: Invalid offer account"),
+            TokenError::OfferAlreadyExists => msg!("Error: Offer already exists"),
+            TokenError::OfferAlreadyAccepted => msg!("Error: Offer is already accepted"),
+            TokenError::InsufficientRepayment => msg!("Error: Repayment doesn't cover the debt"),
+            TokenError::InvalidConfigAccount => msg!("Error: Invalid protocol config account"),
+            TokenError::Unauthorized => msg!("Error: Signer is not the admin"),
+            TokenError::NotPendingAdmin => msg!("Error: Signer is not the pending admin"),
+            TokenError::InvalidFee => msg!("Error: Fee exceeds 10000 basis points"),
+            TokenError::ProtocolPaused => msg!("Error: Protocol is paused"),
+            TokenError::InvalidProgramDataAccount => msg!("Error: Invalid program data account"),
+            TokenError::InvalidPriceAccount => msg!("Error: Invalid price account"),
+            TokenError::StalePrice => msg!("Error: Price is stale"),
+            TokenError::PriceConfidenceTooWide => msg!("Error: Price confidence interval is too wide"),
+            TokenError::InvalidPrice => msg!("Error: Invalid price"),
+            TokenError::OfferTooHigh => msg!("Error: Offer exceeds the loan-to-value limit"),
+            TokenError::InvalidLtv => msg!("Error: Loan-to-value exceeds 10000 basis points"),
+            TokenError::CurrencyNotAllowed => msg!("Error: Currency isn't allowed for the collection"),
+            TokenError::CurrencyMismatch => msg!("Error: Currency mint doesn't match"),
+            TokenError::TooManyLoanMints => msg!("Error: Too many loan currencies"),
+            TokenError::InvalidTreasuryAccount => msg!("Error: Invalid treasury account"),
+            TokenError::InsufficientTreasury => msg!("Error: Treasury balance is too low"),
+            TokenError::CreatorMismatch => msg!("Error: Creator doesn't match the metadata"),
+            TokenError::InvalidRoyalty => msg!("Error: Royalty exceeds 10000 basis points"),
+            TokenError::UnverifiedCreator => msg!("Error: Creator isn't verified"),
+            TokenError::MissingCreators => msg!("Error: Metadata has no collection or creators"),
+            TokenError::InvalidMerkleProof => msg!("Error: NFT mint isn't in the whitelist merkle tree"),
+            TokenError::MerkleProofTooLong => msg!("Error: Merkle proof is too long"),
+            TokenError::AuctionNotEnabled => msg!("Error: Collection has no liquidation auction"),
+            TokenError::AuctionAlreadyStarted => msg!("Error: Auction already started"),
+            TokenError::AuctionNotActive => msg!("Error: Auction isn't running"),
+            TokenError::AuctionActive => msg!("Error: Auction is still running"),
+            TokenError::BidBelowPrice => msg!("Error: Bid is below the auction price"),
+            TokenError::InvalidAuctionConfig => msg!("Error: Invalid auction config"),
+            TokenError::InvalidPoolAccount => msg!("Error: Invalid lending pool account"),
+            TokenError::InsufficientLiquidity => msg!("Error: Pool doesn't have enough idle lamports"),
+            TokenError::InsufficientShares => msg!("Error: Lender doesn't own enough pool shares"),
+            TokenError::PoolAmountTooSmall => msg!("Error: Amount is too small for a pool share"),
+            TokenError::OfferFilled => msg!("Error: Collection offer has no fills left"),
+            TokenError::InvalidBundleSize => msg!("Error: Bundle holds no NFTs or more than the maximum"),
+            TokenError::EscrowlessNotSupported => msg!("Error: Escrowless loans can't be auctioned or lent by pools"),
+            TokenError::InvalidInterestRate => msg!("Error: Interest rate exceeds the maximum"),
+            TokenError::AuctionRoundsExhausted => msg!("Error: Pool auction has no rounds left"),
 
         }
     }
@@ -6,6 +6,7 @@ use solana_program::{
     account_info::AccountInfo,
     borsh::try_from_slice_unchecked,
 };
+use crate::MAX_BUNDLE_NFTS;
 
 
 /// Initializeing solana stream states
@@ -15,23 +16,327 @@ pub struct NftDetails{
     pub nft_mint: Pubkey,
     pub nft_owner:Pubkey,
     pub loan_start: u64,
+    /// Time interest accrues from, `loan_start` unless a partial repayment
+    /// settled the interest since
+    pub interest_start: u64,
     pub loan_amt: u64,
+    /// Principal still owed after partial repayments
+    pub outstanding_principal: u64,
+    /// Loan term in seconds counted from `loan_start`
+    pub loan_duration: u64,
+    /// Annual interest rate in basis points, the collection's rate at deposit
+    /// unless an extension changed it
+    pub interest_rate_bps: u64,
     pub lender: Pubkey,
     pub loan_taken: bool,
     pub loan_offered:bool,
     pub canceled: bool,
+    /// Number of offers which are neither accepted nor withdrawn
+    pub offer_count: u64,
+    /// Whitelist of the collection checked at deposit
+    pub whitelist: Pubkey,
+    /// The NFT stays in the owner's token account, frozen with the vault PDA
+    /// as delegate, instead of moving into the vault
+    pub escrowless: bool,
+    /// SPL mint the loan is denominated in, `None` for lamports
+    pub currency_mint: Option<Pubkey>,
+    /// Liquidation auction of the defaulted NFT, if the lender started one
+    pub auction: Option<Auction>,
+}
+impl NftDetails {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 32 + 1 + 1 + 1 + 8 + 32 + 1 + 33 + 1 + Auction::LEN;
+
+    /// The account is allocated at `LEN`, so unset options leave trailing bytes
+    pub fn from_account(account:&AccountInfo)-> Result<NftDetails, ProgramError> {
+            let md: NftDetails =try_from_slice_unchecked(&account.data.borrow())?;
+            Ok(md)
+    }
+}
+
+/// Descending-price auction of a defaulted NFT
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct Auction {
+    pub start: u64,
+    pub end: u64,
+    pub start_price: u64,
+    /// Price reached at `end`, after which the lender may foreclose
+    pub end_price: u64,
+    /// Principal and interest owed to the lender out of the proceeds
+    pub debt: u64,
+    /// Restarts of an unsold pool auction, up to `MAX_POOL_AUCTION_ROUNDS`
+    pub round: u8,
+}
+impl Auction {
+    pub const LEN: usize = 8 + 8 + 8 + 8 + 8 + 1;
+}
+
+/// Offer of a single lender, stored in the PDA derived from `OFFERPREFIX`,
+/// the lender and the `NftDetails` account. The account also escrows `loan_amt`.
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct LoanOffer {
+    pub nft_details: Pubkey,
+    pub lender: Pubkey,
+    pub loan_amt: u64,
+    pub loan_duration: u64,
+    /// Annual interest rate in basis points the loan runs at once the offer is
+    /// taken, the loan's rate when the offer was made
+    pub interest_rate_bps: u64,
+    pub accepted: bool,
+}
+impl LoanOffer {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 1;
+
+    pub fn from_account(account:&AccountInfo)-> Result<LoanOffer, ProgramError> {
+            let md: LoanOffer =try_from_slice_unchecked(&account.data.borrow())?;
+            Ok(md)
+    }
 }
 
 #[repr(C)]
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub struct Whitelist {
     pub producer: Vec<Pubkey>,
+    /// Verified Metaplex collection mint the whitelist is keyed on, `None`
+    /// when it is keyed on the first of the `producer` creators
+    pub collection: Option<Pubkey>,
+    /// Root of the keccak merkle tree of the mints allowed to be deposited,
+    /// `None` allows every mint of the collection
+    pub merkle_root: Option<[u8; 32]>,
+    /// Liquidation auction lenders can start on default, `None` disables it
+    pub auction: Option<AuctionConfig>,
+    /// Seconds after the loan term during which the borrower can still repay
+    /// and the lender can't foreclose
+    pub grace_period: u64,
+    /// Penalty on the principal for repaying during the grace period in basis points
+    pub late_fee_bps: u64,
     pub state: bool,
-    pub daily_interest_rate: u64,
+    /// Annual interest rate in basis points
+    pub interest_rate_bps: u64,
+    /// Where the collection price is read from when a lender makes an offer
+    pub price: PriceConfig,
+    /// Largest loan as a share of the collection price in basis points
+    pub max_ltv_bps: u64,
+    /// Share of the interest left after the protocol fee which is paid to the
+    /// creators in basis points, split by their metadata `share`
+    pub royalty_bps: u64,
+    /// SPL mints lenders may lend in besides lamports, at most `MAX_LOAN_MINTS`
+    pub loan_mints: Vec<LoanCurrency>,
 }
 impl Whitelist {
     pub fn from_account(account:&AccountInfo)-> Result<Whitelist, ProgramError> {
             let md: Whitelist =try_from_slice_unchecked(&account.data.borrow_mut())?;
             Ok(md)
     }
+    /// Price source of the collection in `currency_mint`, lamports for `None`.
+    pub fn price_config(&self, currency_mint: Option<&Pubkey>) -> Option<&PriceConfig> {
+        match currency_mint
+        {
+            None => Some(&self.price),
+            Some(mint) => self.loan_mints.iter().find(|c| c.mint==*mint).map(|c| &c.price),
+        }
+    }
+}
+
+/// Liquidation auction of a collection, prices are relative to the debt
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct AuctionConfig {
+    /// Auction length in seconds
+    pub duration: u64,
+    /// Starting price above the debt in basis points
+    pub start_premium_bps: u64,
+    /// Final price below the debt in basis points
+    pub end_discount_bps: u64,
+}
+
+/// SPL mint a collection can be borrowed against, priced in that mint
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct LoanCurrency {
+    pub mint: Pubkey,
+    pub price: PriceConfig,
+}
+
+/// Price feed a collection is valued with
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub enum PriceSource {
+    /// `mokshyafeed::Price` account owned by the mokshya feed program
+    Mokshya { program: Pubkey, account: Pubkey },
+    /// Pyth-style `FloorPrice` account owned by the oracle program
+    Floor { program: Pubkey, account: Pubkey },
+    /// `ManualPrice` account posted by the admin, derived from `PRICE`, the
+    /// whitelist and the SPL loan mint if any
+    Manual,
+}
+
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct PriceConfig {
+    pub source: PriceSource,
+    /// Oldest accepted price in seconds
+    pub max_age: u64,
+    /// Widest accepted confidence interval in basis points of the price,
+    /// only floor feeds publish one
+    pub max_confidence_bps: u64,
+}
+
+/// Pyth-style floor price of a collection in whole units of the loan
+/// currency, scaled by `10^expo`
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct FloorPrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+impl FloorPrice {
+    pub fn from_account(account:&AccountInfo)-> Result<FloorPrice, ProgramError> {
+            let md: FloorPrice =try_from_slice_unchecked(&account.data.borrow())?;
+            Ok(md)
+    }
+}
+
+/// Collection price posted by the admin in the smallest unit of the loan currency
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ManualPrice {
+    pub whitelist: Pubkey,
+    pub price: u64,
+    pub update_time: u64,
+}
+impl ManualPrice {
+    pub const LEN: usize = 32 + 8 + 8;
+
+    pub fn from_account(account:&AccountInfo)-> Result<ManualPrice, ProgramError> {
+            let md: ManualPrice =try_from_slice_unchecked(&account.data.borrow())?;
+            Ok(md)
+    }
+}
+
+
+/// Loan against several NFTs, held in associated token accounts of the
+/// vault PDA derived from `NFTPREFIX`, the owner and the bundle account
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct BundleLoan {
+    pub owner: Pubkey,
+    pub lender: Pubkey,
+    /// NFTs in the vault, at most `MAX_BUNDLE_NFTS`
+    pub nfts: Vec<BundleNft>,
+    pub loan_start: u64,
+    /// Lamports the owner asks for, lent by whoever funds the bundle first
+    pub loan_amt: u64,
+    pub loan_duration: u64,
+    /// Highest interest rate of the bundled collections in basis points
+    pub interest_rate_bps: u64,
+    /// Shortest grace period of the bundled collections
+    pub grace_period: u64,
+    pub loan_taken: bool,
+    pub canceled: bool,
+}
+impl BundleLoan {
+    pub const LEN: usize = 32 + 32 + 4 + MAX_BUNDLE_NFTS * BundleNft::LEN + 8 + 8 + 8 + 8 + 8 + 1 + 1;
+
+    pub fn from_account(account:&AccountInfo)-> Result<BundleLoan, ProgramError> {
+            let md: BundleLoan =try_from_slice_unchecked(&account.data.borrow())?;
+            Ok(md)
+    }
+}
+
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct BundleNft {
+    pub mint: Pubkey,
+    /// Whitelist of the NFT's collection checked at deposit
+    pub whitelist: Pubkey,
+}
+impl BundleNft {
+    pub const LEN: usize = 32 + 32;
+}
+
+/// Standing offer of a lender on any NFT of a collection, stored in the PDA
+/// derived from `COLLECTION_OFFER`, the whitelist and the lender. The account
+/// escrows `loan_amt` for each of the remaining `fills`.
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct CollectionOffer {
+    pub whitelist: Pubkey,
+    pub lender: Pubkey,
+    pub loan_amt: u64,
+    pub loan_duration: u64,
+    /// Loans the offer can still fund
+    pub fills: u64,
+}
+impl CollectionOffer {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8;
+
+    pub fn from_account(account:&AccountInfo)-> Result<CollectionOffer, ProgramError> {
+            let md: CollectionOffer =try_from_slice_unchecked(&account.data.borrow())?;
+            Ok(md)
+    }
+}
+
+/// Lamport liquidity lent against a collection, stored in the PDA derived
+/// from `POOL` and the whitelist. The pool is worth its idle lamports above
+/// rent plus the principal it has lent out.
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct LendingPool {
+    pub whitelist: Pubkey,
+    pub total_shares: u64,
+    /// Outstanding principal of the pool's loans, without defaulted loans
+    /// which are written off once their auction starts
+    pub borrowed: u64,
+}
+impl LendingPool {
+    pub const LEN: usize = 32 + 8 + 8;
+
+    pub fn from_account(account:&AccountInfo)-> Result<LendingPool, ProgramError> {
+            let md: LendingPool =try_from_slice_unchecked(&account.data.borrow())?;
+            Ok(md)
+    }
+}
+
+/// Shares of a lender in a `LendingPool`, stored in the PDA derived from
+/// `POOL`, the pool and the lender
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct PoolShare {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub shares: u64,
+}
+impl PoolShare {
+    pub const LEN: usize = 32 + 32 + 8;
+
+    pub fn from_account(account:&AccountInfo)-> Result<PoolShare, ProgramError> {
+            let md: PoolShare =try_from_slice_unchecked(&account.data.borrow())?;
+            Ok(md)
+    }
+}
+
+/// Global protocol settings stored in the PDA derived from `CONFIG`
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ProtocolConfig {
+    pub admin: Pubkey,
+    /// Admin proposed by the current admin, takes over once it accepts
+    pub pending_admin: Option<Pubkey>,
+    /// Protocol fee in basis points of the interest paid to lenders,
+    /// collected in the `TREASURY` PDA
+    pub fee_bps: u64,
+    pub deposits_paused: bool,
+    pub loans_paused: bool,
+}
+impl ProtocolConfig {
+    pub const LEN: usize = 32 + 33 + 8 + 1 + 1;
+
+    pub fn from_account(account:&AccountInfo)-> Result<ProtocolConfig, ProgramError> {
+            let md: ProtocolConfig =try_from_slice_unchecked(&account.data.borrow())?;
+            Ok(md)
+    }
 }
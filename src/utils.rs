@@ -1,14 +1,26 @@
 use solana_program::{
     pubkey::Pubkey,
+    bpf_loader_upgradeable,
+    instruction::{AccountMeta, Instruction},
     account_info::{AccountInfo},
     system_instruction,
     program::{invoke_signed,invoke},
     entrypoint::ProgramResult,
     program_error::ProgramError,
     program_pack::Pack,
+    keccak,
+    msg,
 };
 use arrayref::array_ref;
-use crate::WHITELIST;
+use borsh::BorshDeserialize;
+use spl_token_metadata::state::Metadata;
+use spl_associated_token_account::get_associated_token_address;
+use std::convert::TryFrom;
+use crate::{
+    error::TokenError,
+    state::{Auction, NftDetails, ProtocolConfig},
+    WHITELIST,CONFIG,PRICE,TREASURY,POOL,SECONDS_PER_YEAR,BASIS_POINTS,POOL_VIRTUAL_SHARES,MAX_LOAN_DURATION,MAX_INTEREST_RATE_BPS,
+};
 
 
 pub fn create_account<'a>(
@@ -88,6 +100,106 @@ pub fn derive_whitelist_address(
         program_id,
     )
 }
+pub fn derive_manual_price_address(
+    whitelist: &Pubkey,
+    currency_mint: Option<&Pubkey>,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    match currency_mint
+    {
+        None => Pubkey::find_program_address(
+            &[
+                PRICE.as_bytes(),
+                &whitelist.to_bytes(),
+            ],
+            program_id,
+        ),
+        Some(mint) => Pubkey::find_program_address(
+            &[
+                PRICE.as_bytes(),
+                &whitelist.to_bytes(),
+                &mint.to_bytes(),
+            ],
+            program_id,
+        ),
+    }
+}
+pub fn derive_pool_address(whitelist: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POOL.as_bytes(), &whitelist.to_bytes()], program_id)
+}
+pub fn derive_treasury_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TREASURY.as_bytes()], program_id)
+}
+pub fn derive_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG.as_bytes()], program_id)
+}
+pub fn derive_program_data_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id())
+}
+pub fn derive_metadata_address(nft_mint: &Pubkey) -> (Pubkey, u8) {
+    let metaplex_pid = spl_token_metadata::id();
+    Pubkey::find_program_address(
+        &[
+            "metadata".as_bytes(),
+            metaplex_pid.as_ref(),
+            nft_mint.as_ref(),
+        ],
+        &metaplex_pid,
+    )
+}
+/// Master edition of `nft_mint`, which holds the freeze authority of Metaplex NFTs.
+pub fn derive_edition_address(nft_mint: &Pubkey) -> (Pubkey, u8) {
+    let metaplex_pid = spl_token_metadata::id();
+    Pubkey::find_program_address(
+        &[
+            "metadata".as_bytes(),
+            metaplex_pid.as_ref(),
+            nft_mint.as_ref(),
+            "edition".as_bytes(),
+        ],
+        &metaplex_pid,
+    )
+}
+/// Leaf of an NFT mint in a whitelist merkle tree.
+pub fn merkle_leaf(nft_mint: &Pubkey) -> [u8; 32] {
+    keccak::hashv(&[nft_mint.as_ref()]).to_bytes()
+}
+/// Parent of two merkle nodes, hashed in sorted order so proofs carry no
+/// left or right flags.
+pub fn merkle_parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    if a <= b
+    {
+        keccak::hashv(&[a, b]).to_bytes()
+    }
+    else
+    {
+        keccak::hashv(&[b, a]).to_bytes()
+    }
+}
+pub fn verify_merkle_proof(leaf: [u8; 32], proof: &[[u8; 32]], root: &[u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| merkle_parent(&node, sibling));
+    computed==*root
+}
+/// Collection mint of an NFT if the collection verified it. `spl-token-metadata`
+/// 0.0.1 predates the `token_standard` and `collection` fields, so they are read
+/// from the bytes which follow its `Metadata`.
+pub fn verified_collection(meta_data_account: &AccountInfo) -> Result<Option<Pubkey>, ProgramError> {
+    let data = meta_data_account.data.borrow();
+    let mut rest: &[u8] = &data;
+    Metadata::deserialize(&mut rest)?;
+    //metadata written before the fields existed
+    if rest.is_empty()
+    {
+        return Ok(None);
+    }
+    Option::<u8>::deserialize(&mut rest).map_err(|_| TokenError::InvalidMetadataAccount)?;
+    if rest.is_empty()
+    {
+        return Ok(None);
+    }
+    let collection = Option::<(bool, Pubkey)>::deserialize(&mut rest).map_err(|_| TokenError::InvalidMetadataAccount)?;
+    Ok(collection.filter(|(verified, _)| *verified).map(|(_, key)| key))
+}
 pub fn create_pda_account<'a>(
     payer: &AccountInfo<'a>,
     amount: u64,
@@ -123,4 +235,389 @@ pub fn create_pda_account<'a>(
         check_data_len(&data, spl_token::state::Account::get_packed_len())?;
         let amount = array_ref![data, 64, 8];
         Ok(u64::from_le_bytes(*amount))
-    }
\ No newline at end of file
+    }
+    /// Reads the protocol config after checking it is the program's config PDA.
+    pub fn load_config(program_id: &Pubkey, config_info: &AccountInfo) -> Result<ProtocolConfig, ProgramError> {
+        let (config_address, _) = derive_config_address(program_id);
+        if config_address!=*config_info.key
+        {
+            msg!("Protocol config key doesn't match");
+            return Err(TokenError::InvalidConfigAccount.into());
+        }
+        if config_info.owner!=program_id
+        {
+            msg!("Protocol config isn't owned by the program");
+            return Err(TokenError::InvalidConfigAccount.into());
+        }
+        ProtocolConfig::from_account(config_info)
+    }
+    /// Reads the protocol config and checks `admin` is its signing admin.
+    pub fn check_admin(program_id: &Pubkey, config_info: &AccountInfo, admin: &AccountInfo) -> Result<ProtocolConfig, ProgramError> {
+        if !admin.is_signer
+        {
+            msg!("Admin isn't the signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let config = load_config(program_id, config_info)?;
+        if config.admin!=*admin.key
+        {
+            msg!("Admin Address doesn't match");
+            return Err(TokenError::Unauthorized.into());
+        }
+        Ok(config)
+    }
+    /// Reads the upgrade authority out of the program's data account of the
+    /// upgradeable loader, `None` once the program is immutable.
+    pub fn upgrade_authority(program_id: &Pubkey, program_data: &AccountInfo) -> Result<Option<Pubkey>, ProgramError> {
+        if derive_program_data_address(program_id).0!=*program_data.key || *program_data.owner!=bpf_loader_upgradeable::id()
+        {
+            msg!("The program data account doesn't match");
+            return Err(TokenError::InvalidProgramDataAccount.into());
+        }
+        let data = program_data.try_borrow_data()?;
+        check_data_len(&data, 4 + 8 + 1 + 32)?;
+        //bincode ProgramData state: u32 variant 3, u64 slot, then the optional authority
+        if u32::from_le_bytes(*array_ref![data, 0, 4])!=3
+        {
+            msg!("The program data account doesn't hold program data");
+            return Err(TokenError::InvalidProgramDataAccount.into());
+        }
+        let authority = Option::<Pubkey>::deserialize(&mut &data[12..]).map_err(|_| TokenError::InvalidProgramDataAccount)?;
+        Ok(authority)
+    }
+    /// Moves lamports out of an account owned by the program.
+    pub fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> ProgramResult {
+        let from_balance = from.lamports().checked_sub(amount).ok_or(TokenError::Overflow)?;
+        let to_balance = to.lamports().checked_add(amount).ok_or(TokenError::Overflow)?;
+        **from.try_borrow_mut_lamports()? = from_balance;
+        **to.try_borrow_mut_lamports()? = to_balance;
+        Ok(())
+    }
+    /// Closes an account owned by the program, sending its lamports to `destination`.
+    pub fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+        transfer_lamports(account, destination, account.lamports())?;
+        account.try_borrow_mut_data()?.fill(0);
+        Ok(())
+    }
+    /// Checks `token_account` is the associated token account of `owner` for `mint`.
+    pub fn check_associated_token_account(token_account: &AccountInfo, owner: &Pubkey, mint: &Pubkey) -> ProgramResult {
+        if get_associated_token_address(owner, mint)!=*token_account.key
+        {
+            msg!("The token account isn't the associated token account of {}",owner);
+            return Err(TokenError::InvalidTokenAccount.into());
+        }
+        Ok(())
+    }
+    /// Moves SPL tokens, `signer_seeds` is empty unless `authority` is a PDA.
+    pub fn transfer_tokens<'a>(
+        token_program: &AccountInfo<'a>,
+        source: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        authority: &AccountInfo<'a>,
+        amount: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if *token_program.key!=spl_token::id()
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                token_program.key,
+                source.key,
+                destination.key,
+                authority.key,
+                &[authority.key],
+                amount,
+            )?,
+            &[
+                token_program.clone(),
+                source.clone(),
+                destination.clone(),
+                authority.clone(),
+            ],
+            signer_seeds,
+        )
+    }
+    /// Freezes or thaws an NFT token account through the token metadata
+    /// program, which lets the account's delegate use the master edition's
+    /// freeze authority. `delegate` is a PDA signing with `signer_seeds`.
+    pub fn freeze_delegated_account<'a>(
+        metadata_program: &AccountInfo<'a>,
+        delegate: &AccountInfo<'a>,
+        token_account: &AccountInfo<'a>,
+        edition: &AccountInfo<'a>,
+        mint: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        freeze: bool,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if *metadata_program.key!=spl_token_metadata::id() || *token_program.key!=spl_token::id()
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if derive_edition_address(mint.key).0!=*edition.key
+        {
+            msg!("The master edition doesn't match the NFT mint");
+            return Err(TokenError::InvalidMetadataAccount.into());
+        }
+        //FreezeDelegatedAccount and ThawDelegatedAccount of the token metadata program
+        let tag: u8 = if freeze { 26 } else { 27 };
+        invoke_signed(
+            &Instruction {
+                program_id: *metadata_program.key,
+                accounts: vec![
+                    AccountMeta::new_readonly(*delegate.key, true),
+                    AccountMeta::new(*token_account.key, false),
+                    AccountMeta::new_readonly(*edition.key, false),
+                    AccountMeta::new_readonly(*mint.key, false),
+                    AccountMeta::new_readonly(*token_program.key, false),
+                ],
+                data: vec![tag],
+            },
+            &[
+                delegate.clone(),
+                token_account.clone(),
+                edition.clone(),
+                mint.clone(),
+                token_program.clone(),
+                metadata_program.clone(),
+            ],
+            signer_seeds,
+        )
+    }
+    /// Closes a token account owned by a PDA, sending its rent to `destination`.
+    pub fn close_token_account<'a>(
+        token_program: &AccountInfo<'a>,
+        token_account: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        authority: &AccountInfo<'a>,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if *token_program.key!=spl_token::id()
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        invoke_signed(
+            &spl_token::instruction::close_account(
+                token_program.key,
+                token_account.key,
+                destination.key,
+                authority.key,
+                &[authority.key],
+            )?,
+            &[
+                token_program.clone(),
+                token_account.clone(),
+                destination.clone(),
+                authority.clone(),
+            ],
+            signer_seeds,
+        )
+    }
+    /// Checks a loan term is set and no longer than `MAX_LOAN_DURATION`.
+    pub fn check_loan_duration(duration: u64) -> ProgramResult {
+        if duration==0 || duration>MAX_LOAN_DURATION
+        {
+            msg!("The loan duration must be between 1 and {} seconds",MAX_LOAN_DURATION);
+            return Err(TokenError::InvalidLoanDuration.into());
+        }
+        Ok(())
+    }
+    /// Checks an annual interest rate doesn't exceed `MAX_INTEREST_RATE_BPS`.
+    pub fn check_interest_rate(interest_rate_bps: u64) -> ProgramResult {
+        if interest_rate_bps>MAX_INTEREST_RATE_BPS
+        {
+            msg!("The interest rate can't exceed {} basis points",MAX_INTEREST_RATE_BPS);
+            return Err(TokenError::InvalidInterestRate.into());
+        }
+        Ok(())
+    }
+    /// Interest accrued on `loan_amt` over `elapsed` seconds at an annual rate of
+    /// `interest_rate_bps`, rounded up to the next lamport.
+    pub fn calculate_interest(loan_amt: u64, interest_rate_bps: u64, elapsed: u64) -> Result<u64, ProgramError> {
+        let numerator = (loan_amt as u128)
+            .checked_mul(interest_rate_bps as u128)
+            .and_then(|n| n.checked_mul(elapsed as u128))
+            .ok_or(TokenError::Overflow)?;
+        let denominator = (BASIS_POINTS as u128) * (SECONDS_PER_YEAR as u128);
+        let interest = numerator
+            .checked_add(denominator - 1)
+            .ok_or(TokenError::Overflow)?
+            / denominator;
+        u64::try_from(interest).map_err(|_| TokenError::Overflow.into())
+    }
+    /// Part of `interest` in basis points, rounded down: the protocol fee or
+    /// the creator royalty.
+    pub fn calculate_interest_share(interest: u64, share_bps: u64) -> Result<u64, ProgramError> {
+        scale_bps(interest, share_bps)
+    }
+    /// Part of `royalty` owed to a creator holding `share` percent, rounded down.
+    pub fn calculate_creator_share(royalty: u64, share: u8) -> Result<u64, ProgramError> {
+        let amount = (royalty as u128)
+            .checked_mul(share as u128)
+            .ok_or(TokenError::Overflow)?
+            / 100;
+        u64::try_from(amount).map_err(|_| TokenError::Overflow.into())
+    }
+    /// `amount` times `bps` basis points, rounded down.
+    pub fn scale_bps(amount: u64, bps: u64) -> Result<u64, ProgramError> {
+        let scaled = (amount as u128)
+            .checked_mul(bps as u128)
+            .ok_or(TokenError::Overflow)?
+            / BASIS_POINTS as u128;
+        u64::try_from(scaled).map_err(|_| TokenError::Overflow.into())
+    }
+    /// Pool shares minted for depositing `amount` into a pool worth `value`,
+    /// rounded down. Both sides count the pool's virtual shares and lamport.
+    pub fn pool_shares(amount: u64, total_shares: u64, value: u64) -> Result<u64, ProgramError> {
+        let shares = (amount as u128)
+            .checked_mul(total_shares as u128 + POOL_VIRTUAL_SHARES as u128)
+            .and_then(|s| s.checked_div(value as u128 + 1))
+            .ok_or(TokenError::Overflow)?;
+        u64::try_from(shares).map_err(|_| TokenError::Overflow.into())
+    }
+    /// Lamports `shares` of a pool worth `value` are redeemed for, rounded down.
+    pub fn pool_amount(shares: u64, total_shares: u64, value: u64) -> Result<u64, ProgramError> {
+        let amount = (shares as u128)
+            .checked_mul(value as u128 + 1)
+            .and_then(|a| a.checked_div(total_shares as u128 + POOL_VIRTUAL_SHARES as u128))
+            .ok_or(TokenError::Overflow)?;
+        u64::try_from(amount).map_err(|_| TokenError::Overflow.into())
+    }
+    /// Price of `auction` at `now`, falling linearly from the start to the end price.
+    pub fn auction_price(auction: &Auction, now: u64) -> Result<u64, ProgramError> {
+        let duration = auction.end.checked_sub(auction.start).ok_or(TokenError::Overflow)?;
+        let elapsed = now.saturating_sub(auction.start).min(duration);
+        if duration==0
+        {
+            return Ok(auction.end_price);
+        }
+        let drop = (auction.start_price.saturating_sub(auction.end_price) as u128)
+            .checked_mul(elapsed as u128)
+            .ok_or(TokenError::Overflow)?
+            / duration as u128;
+        let drop = u64::try_from(drop).map_err(|_| TokenError::Overflow)?;
+        Ok(auction.start_price - drop)
+    }
+    /// Largest loan against a collection valued at `price` lamports.
+    pub fn max_loan_amount(price: u64, max_ltv_bps: u64) -> Result<u64, ProgramError> {
+        let amount = (price as u128)
+            .checked_mul(max_ltv_bps as u128)
+            .ok_or(TokenError::Overflow)?
+            / BASIS_POINTS as u128;
+        u64::try_from(amount).map_err(|_| TokenError::Overflow.into())
+    }
+    /// Principal plus interest on `loan_amt` after `elapsed` seconds, what a
+    /// bundle loan is repaid with. Quotes for a single NFT loan, which may be
+    /// partially repaid or late, use `repayment_quote`.
+    pub fn calculate_repayment(loan_amt: u64, interest_rate_bps: u64, elapsed: u64) -> Result<u64, ProgramError> {
+        loan_amt
+            .checked_add(calculate_interest(loan_amt, interest_rate_bps, elapsed)?)
+            .ok_or_else(|| TokenError::Overflow.into())
+    }
+    /// Interest owed at `now` on the outstanding principal of a running loan,
+    /// including the late fee once the loan term is over.
+    pub fn accrued_interest(data: &NftDetails, now: u64, late_fee_bps: u64) -> Result<u64, ProgramError> {
+        let accrual = now.checked_sub(data.interest_start).ok_or(TokenError::Overflow)?;
+        let interest = calculate_interest(data.outstanding_principal, data.interest_rate_bps, accrual)?;
+        if now.checked_sub(data.loan_start).ok_or(TokenError::Overflow)? > data.loan_duration
+        {
+            let late_fee = scale_bps(data.outstanding_principal, late_fee_bps)?;
+            return interest.checked_add(late_fee).ok_or_else(|| TokenError::Overflow.into());
+        }
+        Ok(interest)
+    }
+    /// Amount which repays a running loan in full at `now`: the outstanding
+    /// principal plus `accrued_interest` with the collection's late fee. The
+    /// program charges this on repayment, and off-chain quotes use it too.
+    pub fn repayment_quote(data: &NftDetails, now: u64, late_fee_bps: u64) -> Result<u64, ProgramError> {
+        data.outstanding_principal
+            .checked_add(accrued_interest(data, now, late_fee_bps)?)
+            .ok_or_else(|| TokenError::Overflow.into())
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SECONDS_PER_DAY;
+
+    const START: u64 = 1_000_000;
+
+    // 1 SOL at 10% a year for 30 days
+    fn loan() -> NftDetails {
+        NftDetails {
+            nft_mint: Pubkey::new_unique(),
+            nft_owner: Pubkey::new_unique(),
+            loan_start: START,
+            interest_start: START,
+            loan_amt: 1_000_000_000,
+            outstanding_principal: 1_000_000_000,
+            loan_duration: 30 * SECONDS_PER_DAY,
+            interest_rate_bps: 1_000,
+            lender: Pubkey::new_unique(),
+            loan_taken: true,
+            loan_offered: true,
+            canceled: false,
+            offer_count: 0,
+            whitelist: Pubkey::new_unique(),
+            escrowless: false,
+            currency_mint: None,
+            auction: None,
+        }
+    }
+
+    #[test]
+    fn interest_accrues_over_the_term() {
+        let data = loan();
+        assert_eq!(accrued_interest(&data, START, 500).unwrap(), 0);
+        // 1e9 * 10% * 73 / 365
+        let now = START + 73 * SECONDS_PER_DAY;
+        let data = NftDetails { loan_duration: 73 * SECONDS_PER_DAY, ..data };
+        assert_eq!(accrued_interest(&data, now, 500).unwrap(), 20_000_000);
+        assert_eq!(repayment_quote(&data, now, 500).unwrap(), 1_020_000_000);
+    }
+
+    #[test]
+    fn interest_rounds_up() {
+        // 1e9 * 10% * 1s / 1 year = 3.17 lamports
+        let data = loan();
+        assert_eq!(accrued_interest(&data, START + 1, 0).unwrap(), 4);
+        assert_eq!(repayment_quote(&data, START + 1, 0).unwrap(), 1_000_000_004);
+    }
+
+    #[test]
+    fn late_fee_after_the_term() {
+        let data = NftDetails { loan_duration: 73 * SECONDS_PER_DAY, ..loan() };
+        let now = START + 73 * SECONDS_PER_DAY + 1;
+        // interest for 73 days and a second, plus 5% of the principal
+        assert_eq!(accrued_interest(&data, now, 500).unwrap(), 20_000_004 + 50_000_000);
+        assert_eq!(repayment_quote(&data, now, 500).unwrap(), 1_070_000_004);
+        assert_eq!(repayment_quote(&data, now, 0).unwrap(), 1_020_000_004);
+    }
+
+    #[test]
+    fn partial_repayment_shrinks_the_quote() {
+        // 600_000_000 repaid after 36.5 days, interest settled up to then
+        let data = NftDetails {
+            outstanding_principal: 400_000_000,
+            interest_start: START + 73 * SECONDS_PER_DAY / 2,
+            loan_duration: 365 * SECONDS_PER_DAY,
+            ..loan()
+        };
+        let now = START + 73 * SECONDS_PER_DAY / 2 + 73 * SECONDS_PER_DAY;
+        // 4e8 * 10% * 73 / 365
+        assert_eq!(accrued_interest(&data, now, 500).unwrap(), 8_000_000);
+        assert_eq!(repayment_quote(&data, now, 500).unwrap(), 408_000_000);
+        // the late fee is charged on the outstanding principal only
+        let late = START + 365 * SECONDS_PER_DAY + 1;
+        let interest = accrued_interest(&data, late, 0).unwrap();
+        assert_eq!(accrued_interest(&data, late, 500).unwrap(), interest + 20_000_000);
+    }
+
+    #[test]
+    fn quote_before_the_interest_start_fails() {
+        let data = loan();
+        assert!(accrued_interest(&data, START - 1, 0).is_err());
+    }
+}
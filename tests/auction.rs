@@ -0,0 +1,166 @@
+mod common;
+
+use borrow_lend::{
+    error::TokenError,
+    instruction,
+    state::AuctionConfig,
+    utils::{auction_price, calculate_repayment, derive_treasury_address},
+    BASIS_POINTS, SECONDS_PER_DAY,
+};
+use common::*;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Signer};
+
+const AUCTION_DURATION: u64 = SECONDS_PER_DAY;
+const FEE_BPS: u64 = 1_000;
+const LATE_FEE_BPS: u64 = 500;
+
+fn auction_config() -> AuctionConfig {
+    AuctionConfig {
+        duration: AUCTION_DURATION,
+        start_premium_bps: 2_000,
+        end_discount_bps: 5_000,
+    }
+}
+
+async fn enable_auction(env: &mut TestEnv) {
+    let admin = env.admin.insecure_clone();
+    let ix = instruction::update_auction(&env.program_id, &admin.pubkey(), &env.creators[0], Some(auction_config())).unwrap();
+    process(env, &[ix], &[&admin]).await.unwrap();
+}
+
+fn start_auction_ix(env: &TestEnv, data_account: &Pubkey) -> Instruction {
+    instruction::start_auction(&env.program_id, &env.lender.pubkey(), data_account, &whitelist_address(env)).unwrap()
+}
+
+fn bid_ix(env: &TestEnv, data_account: &Pubkey, max_price: u64) -> Instruction {
+    instruction::bid(
+        &env.program_id,
+        &env.second_lender.pubkey(),
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        data_account,
+        &env.creators[0],
+        &env.creators,
+        &env.lender.pubkey(),
+        max_price,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn auction_repays_the_lender_and_the_surplus_goes_to_the_borrower() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    let ixs = vec![
+        instruction::update_grace(&env.program_id, &admin.pubkey(), &env.creators[0], 0, LATE_FEE_BPS).unwrap(),
+        instruction::update_config(
+            &env.program_id,
+            &admin.pubkey(),
+            instruction::ProcessUpdateConfig {
+                fee_bps: FEE_BPS,
+                deposits_paused: false,
+                loans_paused: false,
+                new_admin: None,
+                clear_pending_admin: false,
+            },
+        )
+        .unwrap(),
+    ];
+    process(&mut env, &ixs, &[&admin]).await.unwrap();
+    let data_key = start_loan(&mut env).await.pubkey();
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    let lender = env.lender.insecure_clone();
+
+    let ix = start_auction_ix(&env, &data_key);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::LoanNotExpired);
+    set_time(&mut env, start, LOAN_DURATION + 1).await;
+    let ix = start_auction_ix(&env, &data_key);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::AuctionNotEnabled);
+
+    enable_auction(&mut env).await;
+    let ix = start_auction_ix(&env, &data_key);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    let ix = start_auction_ix(&env, &data_key);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::AuctionAlreadyStarted);
+    let auction = nft_details(&mut env, &data_key).await.auction.unwrap();
+    // what repaying the late loan costs when the auction starts
+    let late_fee = LOAN_AMOUNT * LATE_FEE_BPS / BASIS_POINTS;
+    let debt = calculate_repayment(LOAN_AMOUNT, INTEREST_RATE_BPS, LOAN_DURATION + 1).unwrap() + late_fee;
+    assert_eq!(auction.debt, debt);
+    assert_eq!(auction.start_price, debt * 12 / 10);
+    assert_eq!(auction.end_price, debt / 2);
+
+    let ix = foreclose_ix(&env, &data_key);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::AuctionActive);
+
+    set_time(&mut env, auction.start, AUCTION_DURATION / 10).await;
+    let price = auction_price(&auction, auction.start + AUCTION_DURATION / 10).unwrap();
+    assert!(price > debt);
+    let bidder = env.second_lender.insecure_clone();
+    let ix = bid_ix(&env, &data_key, price - 1);
+    let result = process(&mut env, &[ix], &[&bidder]).await;
+    assert_token_error(result, TokenError::BidBelowPrice);
+
+    // the interest and late fee pay the protocol fee like a repayment
+    let fee = (debt - LOAN_AMOUNT) * FEE_BPS / BASIS_POINTS;
+    let (treasury, _) = derive_treasury_address(&env.program_id);
+    let borrower = env.borrower.pubkey();
+    let lender_before = lamports(&mut env, &lender.pubkey()).await;
+    let borrower_before = lamports(&mut env, &borrower).await;
+    let treasury_before = lamports(&mut env, &treasury).await;
+    let ix = bid_ix(&env, &data_key, price);
+    process(&mut env, &[ix], &[&bidder]).await.unwrap();
+    assert_eq!(lamports(&mut env, &lender.pubkey()).await, lender_before + debt - fee);
+    assert_eq!(lamports(&mut env, &treasury).await, treasury_before + fee);
+    assert_eq!(lamports(&mut env, &borrower).await, borrower_before + price - debt);
+    let bidder_token_account =
+        spl_associated_token_account::get_associated_token_address(&bidder.pubkey(), &env.nft.mint.pubkey());
+    assert_eq!(token_balance(&mut env, &bidder_token_account).await, 1);
+    assert!(nft_details(&mut env, &data_key).await.canceled);
+}
+
+#[tokio::test]
+async fn unsold_auction_falls_back_to_foreclosure() {
+    let mut env = setup().await;
+    enable_auction(&mut env).await;
+    let data_key = start_loan(&mut env).await.pubkey();
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    set_time(&mut env, start, LOAN_DURATION + 1).await;
+    let lender = env.lender.insecure_clone();
+    let ix = start_auction_ix(&env, &data_key);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+
+    let auction = nft_details(&mut env, &data_key).await.auction.unwrap();
+    set_time(&mut env, auction.end, 0).await;
+    let bidder = env.second_lender.insecure_clone();
+    let ix = bid_ix(&env, &data_key, u64::MAX);
+    let result = process(&mut env, &[ix], &[&bidder]).await;
+    assert_token_error(result, TokenError::AuctionNotActive);
+    let ix = foreclose_ix(&env, &data_key);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+}
+
+#[tokio::test]
+async fn auction_config_validation() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    for config in [
+        AuctionConfig { duration: 0, ..auction_config() },
+        AuctionConfig {
+            end_discount_bps: BASIS_POINTS + 1,
+            ..auction_config()
+        },
+    ] {
+        let ix = instruction::update_auction(&env.program_id, &admin.pubkey(), &env.creators[0], Some(config)).unwrap();
+        let result = process(&mut env, &[ix], &[&admin]).await;
+        assert_token_error(result, TokenError::InvalidAuctionConfig);
+    }
+    let lender = env.lender.insecure_clone();
+    let ix = instruction::update_auction(&env.program_id, &lender.pubkey(), &env.creators[0], None).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use borrow_lend::{
+    error::TokenError,
+    instruction,
+    state::BundleLoan,
+    utils::{calculate_repayment, generate_pda_and_bump_seed},
+    NFTPREFIX, SECONDS_PER_DAY,
+};
+use common::*;
+use solana_program::borsh::try_from_slice_unchecked;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use spl_associated_token_account::get_associated_token_address;
+
+async fn bundle_loan(env: &mut TestEnv, bundle: &Pubkey) -> BundleLoan {
+    let account = env.context.banks_client.get_account(*bundle).await.unwrap().unwrap();
+    try_from_slice_unchecked::<BundleLoan>(&account.data).unwrap()
+}
+
+fn bundle_vault_account(env: &TestEnv, bundle: &Pubkey, nft_mint: &Pubkey) -> Pubkey {
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, &env.borrower.pubkey(), bundle, &env.program_id);
+    get_associated_token_address(&nft_vault, nft_mint)
+}
+
+/// Deposits the env NFT and a second NFT of the collection asking for `amount`.
+async fn deposit_bundle(env: &mut TestEnv, amount: u64) -> (Keypair, Vec<Pubkey>) {
+    let creators: Vec<(Pubkey, bool)> = env.creators.iter().map(|c| (*c, true)).collect();
+    let second = create_nft(env, |mint| collection_metadata_account(mint, &creators, None)).await;
+    let mints = vec![env.nft.mint.pubkey(), second.mint.pubkey()];
+    let nfts: Vec<(Pubkey, Pubkey)> = mints.iter().map(|m| (*m, env.creators[0])).collect();
+    let bundle = Keypair::new();
+    let borrower = env.borrower.insecure_clone();
+    let ix = instruction::bundle_deposit(
+        &env.program_id,
+        &borrower.pubkey(),
+        &bundle.pubkey(),
+        &nfts,
+        amount,
+        LOAN_DURATION,
+        vec![vec![]; 2],
+    )
+    .unwrap();
+    process(env, &[ix], &[&borrower, &bundle]).await.unwrap();
+    (bundle, mints)
+}
+
+fn lend_ix(env: &TestEnv, bundle: &Pubkey) -> Instruction {
+    instruction::bundle_lend(
+        &env.program_id,
+        &env.lender.pubkey(),
+        &env.borrower.pubkey(),
+        bundle,
+        &[(env.creators[0], env.price_account); 2],
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn bundle_borrows_the_sum_of_its_nfts_and_repays_as_one() {
+    let mut env = setup().await;
+    let lender = env.lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+
+    // the bundle may borrow what both NFTs could borrow alone, not more
+    let (bundle, mints) = deposit_bundle(&mut env, 2 * LOAN_AMOUNT + 1).await;
+    let ix = lend_ix(&env, &bundle.pubkey());
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::OfferTooHigh);
+    // nobody lent, the owner takes the NFTs back
+    let ix = instruction::bundle_cancel(&env.program_id, &borrower.pubkey(), &bundle.pubkey(), &mints).unwrap();
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    let owner_token_account = env.nft.owner_token_account;
+    assert_eq!(token_balance(&mut env, &owner_token_account).await, 1);
+
+    let (bundle, mints) = deposit_bundle(&mut env, 2 * LOAN_AMOUNT).await;
+    let bundle_key = bundle.pubkey();
+    for mint in mints.iter() {
+        assert_eq!(token_balance(&mut env, &bundle_vault_account(&env, &bundle_key, mint)).await, 1);
+    }
+    let before = lamports(&mut env, &borrower.pubkey()).await;
+    let ix = lend_ix(&env, &bundle_key);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    assert_eq!(lamports(&mut env, &borrower.pubkey()).await, before + 2 * LOAN_AMOUNT);
+    let details = bundle_loan(&mut env, &bundle_key).await;
+    assert!(details.loan_taken);
+    assert_eq!(details.lender, lender.pubkey());
+    assert_eq!(details.interest_rate_bps, INTEREST_RATE_BPS);
+
+    let elapsed = 5 * SECONDS_PER_DAY;
+    set_time(&mut env, details.loan_start, elapsed).await;
+    let quote = calculate_repayment(2 * LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap();
+    let program_id = env.program_id;
+    let repay_ix = |amount: u64| {
+        instruction::bundle_repay(&program_id, &borrower.pubkey(), &lender.pubkey(), &bundle_key, &mints, amount).unwrap()
+    };
+    let ix = repay_ix(quote - 1);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::InsufficientRepayment);
+    let lender_before = lamports(&mut env, &lender.pubkey()).await;
+    let ix = repay_ix(quote);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(lamports(&mut env, &lender.pubkey()).await, lender_before + quote);
+    for mint in mints.iter() {
+        let owner_token_account = get_associated_token_address(&borrower.pubkey(), mint);
+        assert_eq!(token_balance(&mut env, &owner_token_account).await, 1);
+    }
+    assert!(bundle_loan(&mut env, &bundle_key).await.canceled);
+}
+
+#[tokio::test]
+async fn bundle_rejects_foreign_nfts_and_forecloses_as_one() {
+    let mut env = setup().await;
+    let lender = env.lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+
+    // every NFT of the bundle goes through the deposit validation
+    let bundle = Keypair::new();
+    let nfts = [
+        (env.nft.mint.pubkey(), env.creators[0]),
+        (env.fake_nft.mint.pubkey(), env.creators[0]),
+    ];
+    let ix = instruction::bundle_deposit(&env.program_id, &borrower.pubkey(), &bundle.pubkey(), &nfts, LOAN_AMOUNT, LOAN_DURATION, vec![vec![]; 2]).unwrap();
+    let result = process(&mut env, &[ix], &[&borrower, &bundle]).await;
+    assert_token_error(result, TokenError::CollectionNotWhitelisted);
+    let ix = instruction::bundle_deposit(&env.program_id, &borrower.pubkey(), &bundle.pubkey(), &[], LOAN_AMOUNT, LOAN_DURATION, vec![]).unwrap();
+    let result = process(&mut env, &[ix], &[&borrower, &bundle]).await;
+    assert_token_error(result, TokenError::InvalidBundleSize);
+
+    let (bundle, mints) = deposit_bundle(&mut env, LOAN_AMOUNT).await;
+    let bundle_key = bundle.pubkey();
+    let ix = lend_ix(&env, &bundle_key);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+
+    let start = bundle_loan(&mut env, &bundle_key).await.loan_start;
+    let foreclose_ix = instruction::bundle_foreclose(&env.program_id, &lender.pubkey(), &borrower.pubkey(), &bundle_key, &mints).unwrap();
+    let result = process(&mut env, &[foreclose_ix.clone()], &[&lender]).await;
+    assert_token_error(result, TokenError::LoanNotExpired);
+    set_time(&mut env, start, LOAN_DURATION + 1).await;
+    process(&mut env, &[foreclose_ix], &[&lender]).await.unwrap();
+    for mint in mints.iter() {
+        let lender_token_account = get_associated_token_address(&lender.pubkey(), mint);
+        assert_eq!(token_balance(&mut env, &lender_token_account).await, 1);
+    }
+    let ix = instruction::bundle_repay(&env.program_id, &borrower.pubkey(), &lender.pubkey(), &bundle_key, &mints, 2 * LOAN_AMOUNT).unwrap();
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::LoanCanceled);
+}
@@ -0,0 +1,179 @@
+mod common;
+
+use borrow_lend::{
+    error::TokenError,
+    instruction,
+    state::Whitelist,
+    utils::{derive_whitelist_address, merkle_leaf, merkle_parent},
+    MAX_MERKLE_PROOF_LEN,
+};
+use borsh::BorshDeserialize;
+use common::*;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+fn deposit_nft_ix(env: &TestEnv, nft: &Nft, data_account: &Pubkey, whitelist_key: &Pubkey) -> Instruction {
+    instruction::deposit(
+        &env.program_id,
+        &env.borrower.pubkey(),
+        &nft.mint.pubkey(),
+        data_account,
+        whitelist_key,
+        LOAN_DURATION,
+        None,
+    )
+    .unwrap()
+}
+
+async fn whitelist_collection(env: &mut TestEnv) -> Pubkey {
+    let collection = Pubkey::new_unique();
+    let admin = env.admin.insecure_clone();
+    let ixs = vec![
+        instruction::whitelist_collection(
+            &env.program_id,
+            &admin.pubkey(),
+            &collection,
+            mokshya_price_config(env),
+            MAX_LTV_BPS,
+        )
+        .unwrap(),
+        instruction::update_interest(&env.program_id, &admin.pubkey(), &collection, INTEREST_RATE_BPS).unwrap(),
+    ];
+    process(env, &ixs, &[&admin]).await.unwrap();
+    collection
+}
+
+#[tokio::test]
+async fn verified_collection_keys_the_whitelist() {
+    let mut env = setup().await;
+    let collection = whitelist_collection(&mut env).await;
+    let (whitelist, _) = derive_whitelist_address(&collection, &env.program_id);
+    let account = env.context.banks_client.get_account(whitelist).await.unwrap().unwrap();
+    let wlist = Whitelist::deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(wlist.collection, Some(collection));
+    assert!(wlist.producer.is_empty());
+
+    // the creators of a collection NFT don't need to be verified
+    let creators = [(Pubkey::new_unique(), false)];
+    let nft = create_nft(&mut env, |mint| collection_metadata_account(mint, &creators, Some((true, collection)))).await;
+    let data_account = Keypair::new();
+    let borrower = env.borrower.insecure_clone();
+    let ix = deposit_nft_ix(&env, &nft, &data_account.pubkey(), &collection);
+    process(&mut env, &[ix], &[&borrower, &data_account]).await.unwrap();
+    assert_eq!(nft_details(&mut env, &data_account.pubkey()).await.whitelist, whitelist);
+
+    let lender = env.lender.insecure_clone();
+    let ix = instruction::offer(
+        &env.program_id,
+        &lender.pubkey(),
+        &borrower.pubkey(),
+        &nft.mint.pubkey(),
+        &data_account.pubkey(),
+        &collection,
+        &env.price_account,
+        LOAN_AMOUNT,
+        LOAN_DURATION,
+        None,
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+}
+
+#[tokio::test]
+async fn unverified_collection_is_rejected() {
+    let mut env = setup().await;
+    let collection = whitelist_collection(&mut env).await;
+    let creators = [(Pubkey::new_unique(), true)];
+    let nft = create_nft(&mut env, |mint| collection_metadata_account(mint, &creators, Some((false, collection)))).await;
+    let data_account = Keypair::new();
+    let borrower = env.borrower.insecure_clone();
+    let ix = deposit_nft_ix(&env, &nft, &data_account.pubkey(), &collection);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::InvalidWhitelistAccount);
+
+    // nothing else keys a whitelist
+    let nft = create_nft(&mut env, |mint| collection_metadata_account(mint, &[], Some((false, collection)))).await;
+    let ix = deposit_nft_ix(&env, &nft, &data_account.pubkey(), &collection);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::MissingCreators);
+}
+
+#[tokio::test]
+async fn creator_fallback_checks_verification() {
+    let mut env = setup().await;
+    let data_account = Keypair::new();
+    let borrower = env.borrower.insecure_clone();
+    let creators: Vec<(Pubkey, bool)> = vec![(env.creators[0], true), (env.creators[1], false)];
+    let nft = create_nft(&mut env, |mint| collection_metadata_account(mint, &creators, None)).await;
+    let ix = deposit_nft_ix(&env, &nft, &data_account.pubkey(), &env.creators[0]);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::UnverifiedCreator);
+
+    // more creators than whitelisted
+    let creators: Vec<(Pubkey, bool)> = vec![
+        (env.creators[0], true),
+        (env.creators[1], true),
+        (Pubkey::new_unique(), true),
+    ];
+    let nft = create_nft(&mut env, |mint| collection_metadata_account(mint, &creators, None)).await;
+    let ix = deposit_nft_ix(&env, &nft, &data_account.pubkey(), &env.creators[0]);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::CollectionNotWhitelisted);
+
+    // an unrelated verified collection falls back to the creators
+    let creators: Vec<(Pubkey, bool)> = env.creators.iter().map(|c| (*c, true)).collect();
+    let nft = create_nft(&mut env, |mint| collection_metadata_account(mint, &creators, Some((true, Pubkey::new_unique())))).await;
+    let ix = deposit_nft_ix(&env, &nft, &data_account.pubkey(), &env.creators[0]);
+    process(&mut env, &[ix], &[&borrower, &data_account]).await.unwrap();
+}
+
+#[tokio::test]
+async fn merkle_root_limits_the_mints() {
+    let mut env = setup().await;
+    let leaves = [
+        merkle_leaf(&env.nft.mint.pubkey()),
+        merkle_leaf(&Pubkey::new_unique()),
+        merkle_leaf(&Pubkey::new_unique()),
+    ];
+    let root = merkle_parent(&merkle_parent(&leaves[0], &leaves[1]), &leaves[2]);
+    let admin = env.admin.insecure_clone();
+    let lender = env.lender.insecure_clone();
+    let ix = instruction::update_merkle_root(&env.program_id, &lender.pubkey(), &env.creators[0], Some(root)).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+    let ix = instruction::update_merkle_root(&env.program_id, &admin.pubkey(), &env.creators[0], Some(root)).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+
+    let borrower = env.borrower.insecure_clone();
+    let data_account = Keypair::new();
+    let deposit_ix = |env: &TestEnv, mint: &Pubkey, proof: Vec<[u8; 32]>| {
+        instruction::deposit_with_proof(
+            &env.program_id,
+            &env.borrower.pubkey(),
+            mint,
+            &data_account.pubkey(),
+            &env.creators[0],
+            LOAN_DURATION,
+            None,
+            proof,
+        )
+        .unwrap()
+    };
+    let cases = vec![
+        (deposit_ix(&env, &env.nft.mint.pubkey(), vec![]), TokenError::InvalidMerkleProof),
+        (deposit_ix(&env, &env.nft.mint.pubkey(), vec![leaves[2], leaves[1]]), TokenError::InvalidMerkleProof),
+        (
+            deposit_ix(&env, &env.nft.mint.pubkey(), vec![leaves[1]; MAX_MERKLE_PROOF_LEN + 1]),
+            TokenError::MerkleProofTooLong,
+        ),
+    ];
+    for (ix, expected) in cases {
+        let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+        assert_token_error(result, expected);
+    }
+    let ix = deposit_ix(&env, &env.nft.mint.pubkey(), vec![leaves[1], leaves[2]]);
+    process(&mut env, &[ix], &[&borrower, &data_account]).await.unwrap();
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use borrow_lend::{
+    error::TokenError,
+    instruction,
+    state::CollectionOffer,
+    utils::{calculate_repayment, generate_pda_and_bump_seed},
+    COLLECTION_OFFER, SECONDS_PER_DAY,
+};
+use borsh::BorshDeserialize;
+use common::*;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+async fn collection_offer(env: &mut TestEnv) -> CollectionOffer {
+    let (address, _) = generate_pda_and_bump_seed(COLLECTION_OFFER, &whitelist_address(env), &env.lender.pubkey(), &env.program_id);
+    let account = env.context.banks_client.get_account(address).await.unwrap().unwrap();
+    CollectionOffer::try_from_slice(&account.data).unwrap()
+}
+
+fn accept_ix(env: &TestEnv, nft_mint: &Pubkey, data_account: &Pubkey) -> Instruction {
+    instruction::accept_collection_offer(
+        &env.program_id,
+        &env.borrower.pubkey(),
+        nft_mint,
+        data_account,
+        &env.creators[0],
+        &env.lender.pubkey(),
+        vec![],
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn collection_offer_funds_any_whitelisted_nft() {
+    let mut env = setup().await;
+    let lender = env.lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+    let amount = LOAN_AMOUNT / 2;
+    let offer_ix = |env: &TestEnv, amount: u64, fills: u64| {
+        instruction::collection_offer(
+            &env.program_id,
+            &lender.pubkey(),
+            &env.creators[0],
+            &env.price_account,
+            amount,
+            LOAN_DURATION,
+            fills,
+        )
+        .unwrap()
+    };
+    let ix = offer_ix(&env, LOAN_AMOUNT + 1, 1);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::OfferTooHigh);
+    let ix = offer_ix(&env, amount, 0);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::OfferTooLow);
+    let lender_before = lamports(&mut env, &lender.pubkey()).await;
+    let ix = offer_ix(&env, amount, 2);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    let escrowed = lender_before - lamports(&mut env, &lender.pubkey()).await;
+    assert!(escrowed > 2 * amount);
+
+    // the NFT still has to match the whitelist
+    let data_account = Keypair::new();
+    let ix = accept_ix(&env, &env.fake_nft.mint.pubkey(), &data_account.pubkey());
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::CollectionNotWhitelisted);
+
+    let borrower_before = lamports(&mut env, &borrower.pubkey()).await;
+    let ix = accept_ix(&env, &env.nft.mint.pubkey(), &data_account.pubkey());
+    process(&mut env, &[ix], &[&borrower, &data_account]).await.unwrap();
+    let data_key = data_account.pubkey();
+    let details = nft_details(&mut env, &data_key).await;
+    assert!(details.loan_taken);
+    assert_eq!(details.lender, lender.pubkey());
+    assert_eq!(details.loan_amt, amount);
+    assert_eq!(details.loan_duration, LOAN_DURATION);
+    assert_eq!(token_balance(&mut env, &vault_token_account(&env, &data_key)).await, 1);
+    let rent = lamports(&mut env, &data_key).await;
+    assert_eq!(lamports(&mut env, &borrower.pubkey()).await, borrower_before + amount - rent);
+    assert_eq!(collection_offer(&mut env).await.fills, 1);
+
+    let elapsed = 3 * SECONDS_PER_DAY;
+    set_time(&mut env, details.loan_start, elapsed).await;
+    let quote = calculate_repayment(amount, INTEREST_RATE_BPS, elapsed).unwrap();
+    let ix = repay_ix(&env, &data_key, quote);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+
+    // closing the offer refunds the unfilled loan and the rent
+    let lender_before = lamports(&mut env, &lender.pubkey()).await;
+    let ix = instruction::withdraw_collection_offer(&env.program_id, &lender.pubkey(), &env.creators[0]).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    assert_eq!(lamports(&mut env, &lender.pubkey()).await, lender_before + escrowed - amount);
+}
+
+#[tokio::test]
+async fn filled_collection_offer_is_rejected() {
+    let mut env = setup().await;
+    let lender = env.lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+    let ix = instruction::collection_offer(
+        &env.program_id,
+        &lender.pubkey(),
+        &env.creators[0],
+        &env.price_account,
+        LOAN_AMOUNT,
+        LOAN_DURATION,
+        1,
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    let data_account = Keypair::new();
+    let ix = accept_ix(&env, &env.nft.mint.pubkey(), &data_account.pubkey());
+    process(&mut env, &[ix], &[&borrower, &data_account]).await.unwrap();
+
+    // the loan account reads as an offer of the borrower but isn't the offer PDA
+    let mut ix = instruction::withdraw_collection_offer(&env.program_id, &borrower.pubkey(), &env.creators[0]).unwrap();
+    ix.accounts[1].pubkey = data_account.pubkey();
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::InvalidOfferAccount);
+
+    // a second NFT of the collection finds the offer used up
+    let creators: Vec<(Pubkey, bool)> = env.creators.iter().map(|c| (*c, true)).collect();
+    let nft = create_nft(&mut env, |mint| collection_metadata_account(mint, &creators, None)).await;
+    let data_account = Keypair::new();
+    let ix = accept_ix(&env, &nft.mint.pubkey(), &data_account.pubkey());
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::OfferFilled);
+}
+
+#[tokio::test]
+async fn deposit_and_borrow_from_a_collection_offer() {
+    let mut env = setup().await;
+    let lender = env.lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+    let amount = LOAN_AMOUNT / 2;
+    let ix = instruction::collection_offer(
+        &env.program_id,
+        &lender.pubkey(),
+        &env.creators[0],
+        &env.price_account,
+        amount,
+        LOAN_DURATION,
+        1,
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+
+    let data_account = Keypair::new();
+    let data_key = data_account.pubkey();
+    let borrow_ix = |env: &TestEnv, duration: u64, amount: u64| {
+        instruction::deposit_and_borrow_from_offer(
+            &env.program_id,
+            &borrower.pubkey(),
+            &env.nft.mint.pubkey(),
+            &data_key,
+            &env.creators[0],
+            &lender.pubkey(),
+            duration,
+            amount,
+            vec![],
+        )
+        .unwrap()
+    };
+    // the offer's terms have to be the ones asked for
+    let ix = borrow_ix(&env, LOAN_DURATION + 1, amount);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::InvalidLoanDuration);
+    let ix = borrow_ix(&env, LOAN_DURATION, amount + 1);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::OfferTooLow);
+    let ix = borrow_ix(&env, LOAN_DURATION, amount - 1);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::OfferTooHigh);
+
+    let borrower_before = lamports(&mut env, &borrower.pubkey()).await;
+    let ix = borrow_ix(&env, LOAN_DURATION, amount);
+    process(&mut env, &[ix], &[&borrower, &data_account]).await.unwrap();
+    let details = nft_details(&mut env, &data_key).await;
+    assert!(details.loan_taken);
+    assert_eq!(details.lender, lender.pubkey());
+    assert_eq!(details.loan_amt, amount);
+    assert_eq!(token_balance(&mut env, &vault_token_account(&env, &data_key)).await, 1);
+    let rent = lamports(&mut env, &data_key).await;
+    assert_eq!(lamports(&mut env, &borrower.pubkey()).await, borrower_before + amount - rent);
+    assert_eq!(collection_offer(&mut env).await.fills, 0);
+}
@@ -0,0 +1,645 @@
+#![allow(dead_code)]
+
+use borrow_lend::{
+    error::TokenError,
+    instruction,
+    processor::Processor,
+    state::{LoanCurrency, NftDetails, PriceConfig, PriceSource},
+    utils::{
+        derive_edition_address, derive_metadata_address, derive_program_data_address, derive_whitelist_address,
+        generate_pda_and_bump_seed,
+    },
+    NFTPREFIX,
+};
+use borsh::BorshSerialize;
+use mokshyafeed::Price;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    borsh::try_from_slice_unchecked,
+    entrypoint::ProgramResult,
+    program::invoke_signed,
+    program_error::ProgramError,
+    program_option::COption,
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    bpf_loader_upgradeable,
+    clock::Clock,
+    instruction::{Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token_metadata::state::{Creator, Data, Key, Metadata, MAX_METADATA_LEN};
+
+/// Collection price posted in the mock mokshya feed
+pub const COLLECTION_PRICE: u64 = 4 * LAMPORTS_PER_SOL;
+/// 50% loan-to-value
+pub const MAX_LTV_BPS: u64 = 5_000;
+/// Largest loan against the test collection
+pub const LOAN_AMOUNT: u64 = COLLECTION_PRICE / 2;
+/// Oldest price accepted by the test collection
+pub const MAX_PRICE_AGE: u64 = borrow_lend::SECONDS_PER_DAY;
+/// 20% a year
+pub const INTEREST_RATE_BPS: u64 = 2_000;
+pub const LOAN_DURATION: u64 = 14 * borrow_lend::SECONDS_PER_DAY;
+/// Decimals of the SPL loan currency, like USDC
+pub const CURRENCY_DECIMALS: u8 = 6;
+/// Collection price in the SPL loan currency, 400 tokens
+pub const CURRENCY_COLLECTION_PRICE: u64 = 400_000_000;
+pub const CURRENCY_LOAN_AMOUNT: u64 = CURRENCY_COLLECTION_PRICE / 2;
+/// Tokens of the loan currency minted to the borrower and both lenders
+pub const CURRENCY_BALANCE: u64 = 10 * CURRENCY_COLLECTION_PRICE;
+
+pub struct Nft {
+    pub mint: Keypair,
+    pub owner_token_account: Pubkey,
+}
+
+pub struct TestEnv {
+    pub context: ProgramTestContext,
+    pub program_id: Pubkey,
+    pub admin: Keypair,
+    pub borrower: Keypair,
+    pub lender: Keypair,
+    pub second_lender: Keypair,
+    /// Creators of the whitelisted collection
+    pub creators: Vec<Pubkey>,
+    /// NFT of the whitelisted collection owned by `borrower`
+    pub nft: Nft,
+    /// NFT owned by `borrower` whose second creator isn't whitelisted
+    pub fake_nft: Nft,
+    pub price_program: Pubkey,
+    pub price_account: Pubkey,
+    /// SPL mint allowed as loan currency of the collection
+    pub currency_mint: Keypair,
+    /// Mokshya price account of the collection in `currency_mint`
+    pub currency_price_account: Pubkey,
+}
+
+fn funded_account() -> Account {
+    Account::new(100 * LAMPORTS_PER_SOL, 0, &solana_sdk::system_program::id())
+}
+
+fn metadata_account(mint: &Pubkey, creators: &[Pubkey]) -> Account {
+    let creators: Vec<(Pubkey, bool)> = creators.iter().map(|c| (*c, true)).collect();
+    collection_metadata_account(mint, &creators, None)
+}
+
+/// Metadata with `(address, verified)` creators and an optional
+/// `(verified, mint)` collection written after the fields of `Metadata`.
+pub fn collection_metadata_account(
+    mint: &Pubkey,
+    creators: &[(Pubkey, bool)],
+    collection: Option<(bool, Pubkey)>,
+) -> Account {
+    let metadata = Metadata {
+        key: Key::MetadataV1,
+        update_authority: Pubkey::new_unique(),
+        mint: *mint,
+        data: Data {
+            name: "Test NFT".to_string(),
+            symbol: "TEST".to_string(),
+            uri: "https://example.com/nft.json".to_string(),
+            seller_fee_basis_points: 500,
+            creators: if creators.is_empty() {
+                None
+            } else {
+                Some(
+                    creators
+                        .iter()
+                        .map(|(address, verified)| Creator {
+                            address: *address,
+                            verified: *verified,
+                            share: (100 / creators.len()) as u8,
+                        })
+                        .collect(),
+                )
+            },
+        },
+        primary_sale_happened: true,
+        is_mutable: true,
+        edition_nonce: None,
+    };
+    let mut data = metadata.try_to_vec().unwrap();
+    // no token standard
+    data.push(0);
+    data.extend(collection.try_to_vec().unwrap());
+    data.resize(MAX_METADATA_LEN, 0);
+    Account {
+        lamports: LAMPORTS_PER_SOL,
+        data,
+        owner: spl_token_metadata::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Program data of the upgradeable loader naming `authority` as upgrade authority.
+fn program_data_account(authority: &Pubkey) -> Account {
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend(0u64.to_le_bytes());
+    data.extend(Some(*authority).try_to_vec().unwrap());
+    Account {
+        lamports: LAMPORTS_PER_SOL,
+        data,
+        owner: bpf_loader_upgradeable::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Starts the program with a protocol config, a whitelisted two-creator
+/// collection and two NFTs minted to the borrower.
+pub async fn setup() -> TestEnv {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "borrow_lend",
+        program_id,
+        processor!(Processor::process),
+    );
+    program_test.add_program(
+        "token_metadata",
+        spl_token_metadata::id(),
+        processor!(process_token_metadata),
+    );
+
+    let admin = Keypair::new();
+    let borrower = Keypair::new();
+    let lender = Keypair::new();
+    let second_lender = Keypair::new();
+    for keypair in [&admin, &borrower, &lender, &second_lender] {
+        program_test.add_account(keypair.pubkey(), funded_account());
+    }
+    // the admin deployed the program
+    program_test.add_account(derive_program_data_address(&program_id).0, program_data_account(&admin.pubkey()));
+
+    let creators = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+    let nft_mint = Keypair::new();
+    let fake_mint = Keypair::new();
+    program_test.add_account(
+        derive_metadata_address(&nft_mint.pubkey()).0,
+        metadata_account(&nft_mint.pubkey(), &creators),
+    );
+    program_test.add_account(
+        derive_metadata_address(&fake_mint.pubkey()).0,
+        metadata_account(&fake_mint.pubkey(), &[creators[0], Pubkey::new_unique()]),
+    );
+
+    let price_program = Pubkey::new_unique();
+    let price_account = Pubkey::new_unique();
+    let currency_price_account = Pubkey::new_unique();
+
+    let context = program_test.start_with_context().await;
+    let nft = Nft {
+        owner_token_account: get_associated_token_address(&borrower.pubkey(), &nft_mint.pubkey()),
+        mint: nft_mint,
+    };
+    let fake_nft = Nft {
+        owner_token_account: get_associated_token_address(&borrower.pubkey(), &fake_mint.pubkey()),
+        mint: fake_mint,
+    };
+    let mut env = TestEnv {
+        context,
+        program_id,
+        admin,
+        borrower,
+        lender,
+        second_lender,
+        creators,
+        nft,
+        fake_nft,
+        price_program,
+        price_account,
+        currency_mint: Keypair::new(),
+        currency_price_account,
+    };
+    mint_nft(&mut env, false).await;
+    mint_nft(&mut env, true).await;
+    mint_currency(&mut env).await;
+    let now = now(&mut env).await;
+    set_mokshya_price(&mut env, COLLECTION_PRICE, now);
+    set_currency_price(&mut env, CURRENCY_COLLECTION_PRICE, now);
+
+    let admin = env.admin.insecure_clone();
+    let ixs = vec![
+        instruction::initialize_config(&env.program_id, &admin.pubkey(), 0).unwrap(),
+        instruction::whitelist(
+            &env.program_id,
+            &admin.pubkey(),
+            &env.creators,
+            mokshya_price_config(&env),
+            MAX_LTV_BPS,
+        )
+        .unwrap(),
+        instruction::update_interest(
+            &env.program_id,
+            &admin.pubkey(),
+            &env.creators[0],
+            INTEREST_RATE_BPS,
+        )
+        .unwrap(),
+        instruction::update_loan_mints(
+            &env.program_id,
+            &admin.pubkey(),
+            &env.creators[0],
+            vec![LoanCurrency {
+                mint: env.currency_mint.pubkey(),
+                price: PriceConfig {
+                    source: PriceSource::Mokshya {
+                        program: env.price_program,
+                        account: env.currency_price_account,
+                    },
+                    max_age: MAX_PRICE_AGE,
+                    max_confidence_bps: 0,
+                },
+            }],
+        )
+        .unwrap(),
+    ];
+    process(&mut env, &ixs, &[&admin]).await.unwrap();
+    env
+}
+
+pub fn mokshya_price_config(env: &TestEnv) -> PriceConfig {
+    PriceConfig {
+        source: PriceSource::Mokshya {
+            program: env.price_program,
+            account: env.price_account,
+        },
+        max_age: MAX_PRICE_AGE,
+        max_confidence_bps: 0,
+    }
+}
+
+/// Writes the mokshya `Price` account of the test collection.
+pub fn set_mokshya_price(env: &mut TestEnv, price: u64, update_time: u64) {
+    let price = Price {
+        creator: env.creators.clone(),
+        admin_account: env.admin.pubkey(),
+        update_time,
+        price,
+    };
+    let (address, owner) = (env.price_account, env.price_program);
+    set_price_account(env, &address, &owner, price.try_to_vec().unwrap());
+}
+
+/// Writes the mokshya `Price` account of the test collection in the SPL currency.
+pub fn set_currency_price(env: &mut TestEnv, price: u64, update_time: u64) {
+    let price = Price {
+        creator: env.creators.clone(),
+        admin_account: env.admin.pubkey(),
+        update_time,
+        price,
+    };
+    let (address, owner) = (env.currency_price_account, env.price_program);
+    set_price_account(env, &address, &owner, price.try_to_vec().unwrap());
+}
+
+/// Replaces `address` with an account holding `data` owned by `owner`.
+pub fn set_price_account(env: &mut TestEnv, address: &Pubkey, owner: &Pubkey, data: Vec<u8>) {
+    let account = Account {
+        lamports: LAMPORTS_PER_SOL,
+        data,
+        owner: *owner,
+        executable: false,
+        rent_epoch: 0,
+    };
+    env.context.set_account(address, &AccountSharedData::from(account));
+}
+
+async fn mint_nft(env: &mut TestEnv, fake: bool) {
+    let nft = if fake { &env.fake_nft } else { &env.nft };
+    let mint = nft.mint.insecure_clone();
+    mint_to_borrower(env, &mint).await;
+}
+
+/// Stand-in for the delegated freeze and thaw of the token metadata program,
+/// the only instructions of it the program invokes.
+fn process_token_metadata(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+    let accounts = &mut accounts.iter();
+    let delegate = next_account_info(accounts)?;
+    let token_account = next_account_info(accounts)?;
+    let edition = next_account_info(accounts)?;
+    let mint = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+    let account = spl_token::state::Account::unpack(&token_account.data.borrow())?;
+    if !delegate.is_signer || account.delegate != COption::Some(*delegate.key) {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let (_, bump) = derive_edition_address(mint.key);
+    let seeds: &[&[u8]] = &[b"metadata", program_id.as_ref(), mint.key.as_ref(), b"edition", &[bump]];
+    let ix = match input {
+        [26] => spl_token::instruction::freeze_account(token_program.key, token_account.key, mint.key, edition.key, &[])?,
+        [27] => spl_token::instruction::thaw_account(token_program.key, token_account.key, mint.key, edition.key, &[])?,
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    invoke_signed(&ix, &[token_account.clone(), mint.clone(), edition.clone(), token_program.clone()], &[seeds])
+}
+
+/// Mints a new NFT with `metadata` to the borrower.
+pub async fn create_nft(env: &mut TestEnv, metadata: impl FnOnce(&Pubkey) -> Account) -> Nft {
+    let mint = Keypair::new();
+    env.context.set_account(
+        &derive_metadata_address(&mint.pubkey()).0,
+        &AccountSharedData::from(metadata(&mint.pubkey())),
+    );
+    mint_to_borrower(env, &mint).await;
+    Nft {
+        owner_token_account: get_associated_token_address(&env.borrower.pubkey(), &mint.pubkey()),
+        mint,
+    }
+}
+
+async fn mint_to_borrower(env: &mut TestEnv, mint: &Keypair) {
+    let borrower = env.borrower.pubkey();
+    let owner_token_account = get_associated_token_address(&borrower, &mint.pubkey());
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let ixs = vec![
+        system_instruction::create_account(
+            &env.context.payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &mint.pubkey(),
+            &borrower,
+            Some(&derive_edition_address(&mint.pubkey()).0),
+            0,
+        )
+        .unwrap(),
+        spl_associated_token_account::instruction::create_associated_token_account(
+            &env.context.payer.pubkey(),
+            &borrower,
+            &mint.pubkey(),
+        ),
+        spl_token::instruction::mint_to(
+            &spl_token::id(),
+            &mint.pubkey(),
+            &owner_token_account,
+            &borrower,
+            &[],
+            1,
+        )
+        .unwrap(),
+    ];
+    let borrower = env.borrower.insecure_clone();
+    process(env, &ixs, &[mint, &borrower]).await.unwrap();
+}
+
+/// Creates the SPL loan currency and funds the borrower and both lenders.
+async fn mint_currency(env: &mut TestEnv) {
+    let mint = env.currency_mint.insecure_clone();
+    let admin = env.admin.insecure_clone();
+    let payer = env.context.payer.pubkey();
+    let rent = env.context.banks_client.get_rent().await.unwrap();
+    let mut ixs = vec![
+        system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &mint.pubkey(),
+            &admin.pubkey(),
+            None,
+            CURRENCY_DECIMALS,
+        )
+        .unwrap(),
+    ];
+    for wallet in [env.borrower.pubkey(), env.lender.pubkey(), env.second_lender.pubkey()] {
+        ixs.push(spl_associated_token_account::instruction::create_associated_token_account(
+            &payer,
+            &wallet,
+            &mint.pubkey(),
+        ));
+        ixs.push(
+            spl_token::instruction::mint_to(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &get_associated_token_address(&wallet, &mint.pubkey()),
+                &admin.pubkey(),
+                &[],
+                CURRENCY_BALANCE,
+            )
+            .unwrap(),
+        );
+    }
+    process(env, &ixs, &[&mint, &admin]).await.unwrap();
+}
+
+/// Sends `ixs` in one transaction paid by the test payer.
+pub async fn process(
+    env: &mut TestEnv,
+    ixs: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let blockhash = env.context.get_new_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&env.context.payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&env.context.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    env.context
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .map_err(|e: BanksClientError| e.unwrap())
+}
+
+pub fn assert_token_error(result: Result<(), TransactionError>, expected: TokenError) {
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(expected as u32)),
+    );
+}
+
+pub fn assert_instruction_error(result: Result<(), TransactionError>, expected: InstructionError) {
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(0, expected),
+    );
+}
+
+/// Deposits `env.nft` and returns the `NftDetails` data account.
+pub async fn deposit(env: &mut TestEnv, duration: u64) -> Keypair {
+    deposit_in(env, duration, None).await
+}
+
+/// Deposits `env.nft` asking for a loan in `currency_mint`.
+pub async fn deposit_in(env: &mut TestEnv, duration: u64, currency_mint: Option<&Pubkey>) -> Keypair {
+    let data_account = Keypair::new();
+    let ix = instruction::deposit(
+        &env.program_id,
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_account.pubkey(),
+        &env.creators[0],
+        duration,
+        currency_mint,
+    )
+    .unwrap();
+    let borrower = env.borrower.insecure_clone();
+    process(env, &[ix], &[&borrower, &data_account]).await.unwrap();
+    data_account
+}
+
+pub fn offer_ix(env: &TestEnv, lender: &Pubkey, data_account: &Pubkey, amount: u64) -> Instruction {
+    instruction::offer(
+        &env.program_id,
+        lender,
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        data_account,
+        &env.creators[0],
+        &env.price_account,
+        amount,
+        0,
+        None,
+    )
+    .unwrap()
+}
+
+/// Offer in the SPL loan currency.
+pub fn currency_offer_ix(env: &TestEnv, lender: &Pubkey, data_account: &Pubkey, amount: u64) -> Instruction {
+    instruction::offer(
+        &env.program_id,
+        lender,
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        data_account,
+        &env.creators[0],
+        &env.currency_price_account,
+        amount,
+        0,
+        Some(&env.currency_mint.pubkey()),
+    )
+    .unwrap()
+}
+
+pub async fn offer(env: &mut TestEnv, lender: &Keypair, data_account: &Pubkey) {
+    let ix = offer_ix(env, &lender.pubkey(), data_account, LOAN_AMOUNT);
+    process(env, &[ix], &[lender]).await.unwrap();
+}
+
+pub fn select_ix(env: &TestEnv, lender: &Pubkey, data_account: &Pubkey) -> Instruction {
+    instruction::select(
+        &env.program_id,
+        lender,
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        data_account,
+        None,
+    )
+    .unwrap()
+}
+
+pub async fn select(env: &mut TestEnv, lender: &Pubkey, data_account: &Pubkey) {
+    let ix = select_ix(env, lender, data_account);
+    let borrower = env.borrower.insecure_clone();
+    process(env, &[ix], &[&borrower]).await.unwrap();
+}
+
+/// Deposit, offer by `env.lender` and select: returns the running loan.
+pub async fn start_loan(env: &mut TestEnv) -> Keypair {
+    let data_account = deposit(env, LOAN_DURATION).await;
+    let lender = env.lender.insecure_clone();
+    offer(env, &lender, &data_account.pubkey()).await;
+    select(env, &lender.pubkey(), &data_account.pubkey()).await;
+    data_account
+}
+
+pub fn repay_ix(env: &TestEnv, data_account: &Pubkey, amount: u64) -> Instruction {
+    instruction::repay(
+        &env.program_id,
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        data_account,
+        &env.creators[0],
+        &env.creators,
+        &env.lender.pubkey(),
+        amount,
+        None,
+    )
+    .unwrap()
+}
+
+pub fn foreclose_ix(env: &TestEnv, data_account: &Pubkey) -> Instruction {
+    instruction::foreclose(
+        &env.program_id,
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        data_account,
+        &env.lender.pubkey(),
+        &whitelist_address(env),
+    )
+    .unwrap()
+}
+
+/// Whitelist of the test collection
+pub fn whitelist_address(env: &TestEnv) -> Pubkey {
+    derive_whitelist_address(&env.creators[0], &env.program_id).0
+}
+
+pub async fn nft_details(env: &mut TestEnv, data_account: &Pubkey) -> NftDetails {
+    let account = env
+        .context
+        .banks_client
+        .get_account(*data_account)
+        .await
+        .unwrap()
+        .unwrap();
+    try_from_slice_unchecked::<NftDetails>(&account.data).unwrap()
+}
+
+pub async fn lamports(env: &mut TestEnv, address: &Pubkey) -> u64 {
+    env.context.banks_client.get_balance(*address).await.unwrap()
+}
+
+pub async fn token_balance(env: &mut TestEnv, token_account: &Pubkey) -> u64 {
+    match env.context.banks_client.get_account(*token_account).await.unwrap() {
+        Some(account) => spl_token::state::Account::unpack(&account.data).unwrap().amount,
+        None => 0,
+    }
+}
+
+/// Associated token account of `wallet` for the SPL loan currency.
+pub fn currency_account(env: &TestEnv, wallet: &Pubkey) -> Pubkey {
+    get_associated_token_address(wallet, &env.currency_mint.pubkey())
+}
+
+pub fn vault_token_account(env: &TestEnv, data_account: &Pubkey) -> Pubkey {
+    let (nft_vault, _) = generate_pda_and_bump_seed(
+        NFTPREFIX,
+        &env.borrower.pubkey(),
+        data_account,
+        &env.program_id,
+    );
+    get_associated_token_address(&nft_vault, &env.nft.mint.pubkey())
+}
+
+pub async fn now(env: &mut TestEnv) -> u64 {
+    let clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp as u64
+}
+
+/// Sets the clock to `seconds` after `start`.
+pub async fn set_time(env: &mut TestEnv, start: u64, seconds: u64) {
+    let mut clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = (start + seconds) as i64;
+    env.context.set_sysvar(&clock);
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use borrow_lend::{
+    error::TokenError,
+    instruction,
+    state::{LoanCurrency, PriceConfig, PriceSource},
+    utils::{calculate_repayment, generate_pda_and_bump_seed},
+    OFFERPREFIX, SECONDS_PER_DAY,
+};
+use common::*;
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+
+fn offer_token_account(env: &TestEnv, lender: &Pubkey, data_account: &Pubkey) -> Pubkey {
+    let (offer, _) = generate_pda_and_bump_seed(OFFERPREFIX, lender, data_account, &env.program_id);
+    currency_account(env, &offer)
+}
+
+#[tokio::test]
+async fn spl_loan_offer_select_repay() {
+    let mut env = setup().await;
+    let currency_mint = env.currency_mint.pubkey();
+    let data_key = deposit_in(&mut env, LOAN_DURATION, Some(&currency_mint)).await.pubkey();
+    assert_eq!(nft_details(&mut env, &data_key).await.currency_mint, Some(currency_mint));
+
+    let lender = env.lender.insecure_clone();
+    let lender_tokens = currency_account(&env, &lender.pubkey());
+    let borrower_tokens = currency_account(&env, &env.borrower.pubkey());
+    let escrow = offer_token_account(&env, &lender.pubkey(), &data_key);
+    let ix = currency_offer_ix(&env, &lender.pubkey(), &data_key, CURRENCY_LOAN_AMOUNT + 1);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::OfferTooHigh);
+    let ix = currency_offer_ix(&env, &lender.pubkey(), &data_key, CURRENCY_LOAN_AMOUNT);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    assert_eq!(token_balance(&mut env, &escrow).await, CURRENCY_LOAN_AMOUNT);
+    assert_eq!(token_balance(&mut env, &lender_tokens).await, CURRENCY_BALANCE - CURRENCY_LOAN_AMOUNT);
+
+    let borrower = env.borrower.insecure_clone();
+    let ix = instruction::select(
+        &env.program_id,
+        &lender.pubkey(),
+        &borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_key,
+        Some(&currency_mint),
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(token_balance(&mut env, &escrow).await, 0);
+    assert_eq!(token_balance(&mut env, &borrower_tokens).await, CURRENCY_BALANCE + CURRENCY_LOAN_AMOUNT);
+
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    let elapsed = 5 * SECONDS_PER_DAY;
+    set_time(&mut env, start, elapsed).await;
+    let quote = calculate_repayment(CURRENCY_LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap();
+    let ix = instruction::repay(
+        &env.program_id,
+        &borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_key,
+        &env.creators[0],
+        &env.creators,
+        &lender.pubkey(),
+        quote,
+        Some(&currency_mint),
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut env, &lender_tokens).await,
+        CURRENCY_BALANCE - CURRENCY_LOAN_AMOUNT + quote,
+    );
+    assert_eq!(token_balance(&mut env, &env.nft.owner_token_account.clone()).await, 1);
+
+    // closing the settled offer also closes its token account
+    let ix = instruction::withdraw_offer(&env.program_id, &lender.pubkey(), &data_key, Some(&currency_mint)).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    assert!(env.context.banks_client.get_account(escrow).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn withdraw_open_spl_offer() {
+    let mut env = setup().await;
+    let currency_mint = env.currency_mint.pubkey();
+    let data_key = deposit_in(&mut env, LOAN_DURATION, Some(&currency_mint)).await.pubkey();
+    let lender = env.lender.insecure_clone();
+    let ix = currency_offer_ix(&env, &lender.pubkey(), &data_key, CURRENCY_LOAN_AMOUNT / 2);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+
+    let ix = instruction::withdraw_offer(&env.program_id, &lender.pubkey(), &data_key, Some(&currency_mint)).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    let lender_tokens = currency_account(&env, &lender.pubkey());
+    assert_eq!(token_balance(&mut env, &lender_tokens).await, CURRENCY_BALANCE);
+    assert_eq!(nft_details(&mut env, &data_key).await.offer_count, 0);
+}
+
+#[tokio::test]
+async fn native_offer_on_spl_deposit_is_rejected() {
+    let mut env = setup().await;
+    let currency_mint = env.currency_mint.pubkey();
+    let data_key = deposit_in(&mut env, LOAN_DURATION, Some(&currency_mint)).await.pubkey();
+    let lender = env.lender.insecure_clone();
+
+    // the native offer lacks the currency accounts
+    let ix = offer_ix(&env, &lender.pubkey(), &data_key, LOAN_AMOUNT);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_instruction_error(result, solana_sdk::instruction::InstructionError::NotEnoughAccountKeys);
+
+    let mut ix = currency_offer_ix(&env, &lender.pubkey(), &data_key, CURRENCY_LOAN_AMOUNT);
+    ix.accounts[11].pubkey = env.nft.mint.pubkey();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::CurrencyMismatch);
+}
+
+#[tokio::test]
+async fn loan_currency_validation() {
+    let mut env = setup().await;
+    let borrower = env.borrower.insecure_clone();
+    let data_account = solana_sdk::signature::Keypair::new();
+    let ix = instruction::deposit(
+        &env.program_id,
+        &borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_account.pubkey(),
+        &env.creators[0],
+        LOAN_DURATION,
+        Some(&Pubkey::new_unique()),
+    )
+    .unwrap();
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::CurrencyNotAllowed);
+
+    let admin = env.admin.insecure_clone();
+    let currency = LoanCurrency {
+        mint: Pubkey::new_unique(),
+        price: PriceConfig {
+            source: PriceSource::Manual,
+            max_age: MAX_PRICE_AGE,
+            max_confidence_bps: 0,
+        },
+    };
+    let ix = instruction::update_loan_mints(
+        &env.program_id,
+        &admin.pubkey(),
+        &env.creators[0],
+        vec![currency; borrow_lend::MAX_LOAN_MINTS + 1],
+    )
+    .unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::TooManyLoanMints);
+
+    let lender = env.lender.insecure_clone();
+    let ix = instruction::update_loan_mints(&env.program_id, &lender.pubkey(), &env.creators[0], vec![]).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+}
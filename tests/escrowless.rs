@@ -0,0 +1,116 @@
+mod common;
+
+use borrow_lend::{
+    instruction,
+    utils::{calculate_repayment, generate_pda_and_bump_seed},
+    NFTPREFIX, SECONDS_PER_DAY,
+};
+use common::*;
+use solana_program::program_option::COption;
+use solana_sdk::{
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Account as TokenAccount;
+
+async fn token_account(env: &mut TestEnv, address: &Pubkey) -> TokenAccount {
+    let account = env.context.banks_client.get_account(*address).await.unwrap().unwrap();
+    TokenAccount::unpack(&account.data).unwrap()
+}
+
+async fn deposit_escrowless(env: &mut TestEnv) -> Keypair {
+    let data_account = Keypair::new();
+    let ix = instruction::deposit_escrowless(
+        &env.program_id,
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_account.pubkey(),
+        &env.creators[0],
+        LOAN_DURATION,
+        None,
+        vec![],
+    )
+    .unwrap();
+    let borrower = env.borrower.insecure_clone();
+    process(env, &[ix], &[&borrower, &data_account]).await.unwrap();
+    data_account
+}
+
+fn escrowless_ix(env: &TestEnv, ix: Instruction, data_account: &Pubkey) -> Instruction {
+    instruction::escrowless(ix, &env.borrower.pubkey(), &env.nft.mint.pubkey(), data_account)
+}
+
+/// Offer by `env.lender` and select on an escrowless deposit.
+async fn take_loan(env: &mut TestEnv, data_key: &Pubkey) {
+    let lender = env.lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+    let ix = escrowless_ix(env, offer_ix(env, &lender.pubkey(), data_key, LOAN_AMOUNT), data_key);
+    process(env, &[ix], &[&lender]).await.unwrap();
+    let ix = escrowless_ix(env, select_ix(env, &lender.pubkey(), data_key), data_key);
+    process(env, &[ix], &[&borrower]).await.unwrap();
+}
+
+#[tokio::test]
+async fn escrowless_loan_keeps_the_nft_frozen_in_the_wallet() {
+    let mut env = setup().await;
+    let borrower = env.borrower.insecure_clone();
+    let owner_token_account = env.nft.owner_token_account;
+
+    let data_key = deposit_escrowless(&mut env).await.pubkey();
+    let (nft_vault, _) = generate_pda_and_bump_seed(NFTPREFIX, &borrower.pubkey(), &data_key, &env.program_id);
+    let account = token_account(&mut env, &owner_token_account).await;
+    assert_eq!(account.amount, 1);
+    assert!(account.is_frozen());
+    assert_eq!(account.delegate, COption::Some(nft_vault));
+    assert!(nft_details(&mut env, &data_key).await.escrowless);
+    // the owner can't take back the delegation while the NFT is frozen
+    let ix = spl_token::instruction::revoke(&spl_token::id(), &owner_token_account, &borrower.pubkey(), &[]).unwrap();
+    assert!(process(&mut env, &[ix], &[&borrower]).await.is_err());
+
+    // canceling thaws the NFT
+    let ix = instruction::cancel(&env.program_id, &borrower.pubkey(), &env.nft.mint.pubkey(), &data_key).unwrap();
+    let ix = escrowless_ix(&env, ix, &data_key);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    let account = token_account(&mut env, &owner_token_account).await;
+    assert!(!account.is_frozen());
+    assert_eq!(account.delegate, COption::None);
+
+    let data_key = deposit_escrowless(&mut env).await.pubkey();
+    let before = lamports(&mut env, &borrower.pubkey()).await;
+    take_loan(&mut env, &data_key).await;
+    assert_eq!(lamports(&mut env, &borrower.pubkey()).await, before + LOAN_AMOUNT);
+    assert!(token_account(&mut env, &owner_token_account).await.is_frozen());
+
+    let details = nft_details(&mut env, &data_key).await;
+    let elapsed = 4 * SECONDS_PER_DAY;
+    set_time(&mut env, details.loan_start, elapsed).await;
+    let quote = calculate_repayment(LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap();
+    let ix = escrowless_ix(&env, repay_ix(&env, &data_key, quote), &data_key);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    let account = token_account(&mut env, &owner_token_account).await;
+    assert_eq!(account.amount, 1);
+    assert!(!account.is_frozen());
+    assert_eq!(account.delegate, COption::None);
+    assert!(nft_details(&mut env, &data_key).await.canceled);
+}
+
+#[tokio::test]
+async fn defaulted_escrowless_nft_moves_to_the_lender() {
+    let mut env = setup().await;
+    let lender = env.lender.insecure_clone();
+    let owner_token_account = env.nft.owner_token_account;
+    let data_key = deposit_escrowless(&mut env).await.pubkey();
+    take_loan(&mut env, &data_key).await;
+
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    set_time(&mut env, start, LOAN_DURATION + 1).await;
+    let ix = escrowless_ix(&env, foreclose_ix(&env, &data_key), &data_key);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    let lender_token_account = get_associated_token_address(&lender.pubkey(), &env.nft.mint.pubkey());
+    assert_eq!(token_balance(&mut env, &lender_token_account).await, 1);
+    assert_eq!(token_balance(&mut env, &owner_token_account).await, 0);
+    assert!(!token_account(&mut env, &lender_token_account).await.is_frozen());
+}
@@ -0,0 +1,262 @@
+mod common;
+
+use borrow_lend::{
+    error::TokenError,
+    instruction,
+    utils::{calculate_creator_share, calculate_interest_share, calculate_repayment, derive_metadata_address, derive_treasury_address},
+    BASIS_POINTS, SECONDS_PER_DAY,
+};
+use common::*;
+use solana_sdk::{account::AccountSharedData, signature::Signer};
+
+const FEE_BPS: u64 = 1000;
+
+async fn set_fee(env: &mut TestEnv, fee_bps: u64) {
+    let admin = env.admin.insecure_clone();
+    let ix = instruction::update_config(
+        &env.program_id,
+        &admin.pubkey(),
+        instruction::ProcessUpdateConfig {
+            fee_bps,
+            deposits_paused: false,
+            loans_paused: false,
+            new_admin: None,
+            clear_pending_admin: false,
+        },
+    )
+    .unwrap();
+    process(env, &[ix], &[&admin]).await.unwrap();
+}
+
+#[tokio::test]
+async fn fee_on_interest_goes_to_the_treasury() {
+    let mut env = setup().await;
+    set_fee(&mut env, FEE_BPS).await;
+    let data_key = start_loan(&mut env).await.pubkey();
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    let elapsed = 5 * SECONDS_PER_DAY;
+    set_time(&mut env, start, elapsed).await;
+
+    let quote = calculate_repayment(LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap();
+    let fee = calculate_interest_share(quote - LOAN_AMOUNT, FEE_BPS).unwrap();
+    assert!(fee > 0);
+    let (treasury, _) = derive_treasury_address(&env.program_id);
+    let lender = env.lender.pubkey();
+    let lender_before = lamports(&mut env, &lender).await;
+    let treasury_before = lamports(&mut env, &treasury).await;
+    let borrower = env.borrower.insecure_clone();
+    let ix = repay_ix(&env, &data_key, quote);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(lamports(&mut env, &lender).await, lender_before + quote - fee);
+    assert_eq!(lamports(&mut env, &treasury).await, treasury_before + fee);
+
+    let admin = env.admin.insecure_clone();
+    let destination = env.second_lender.pubkey();
+    let destination_before = lamports(&mut env, &destination).await;
+    let ix = instruction::withdraw_fees(&env.program_id, &borrower.pubkey(), &destination, fee, None).unwrap();
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+    // the rent exempt reserve stays in the treasury
+    let ix = instruction::withdraw_fees(&env.program_id, &admin.pubkey(), &destination, fee + 1, None).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InsufficientTreasury);
+    let ix = instruction::withdraw_fees(&env.program_id, &admin.pubkey(), &destination, fee, None).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+    assert_eq!(lamports(&mut env, &destination).await, destination_before + fee);
+    assert_eq!(lamports(&mut env, &treasury).await, treasury_before);
+}
+
+#[tokio::test]
+async fn spl_fee_on_interest() {
+    let mut env = setup().await;
+    set_fee(&mut env, FEE_BPS).await;
+    let currency_mint = env.currency_mint.pubkey();
+    let data_key = deposit_in(&mut env, LOAN_DURATION, Some(&currency_mint)).await.pubkey();
+    let lender = env.lender.insecure_clone();
+    let ix = currency_offer_ix(&env, &lender.pubkey(), &data_key, CURRENCY_LOAN_AMOUNT);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    let borrower = env.borrower.insecure_clone();
+    let ix = instruction::select(
+        &env.program_id,
+        &lender.pubkey(),
+        &borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_key,
+        Some(&currency_mint),
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    let elapsed = 5 * SECONDS_PER_DAY;
+    set_time(&mut env, start, elapsed).await;
+    let quote = calculate_repayment(CURRENCY_LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap();
+    let fee = calculate_interest_share(quote - CURRENCY_LOAN_AMOUNT, FEE_BPS).unwrap();
+    let ix = instruction::repay(
+        &env.program_id,
+        &borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_key,
+        &env.creators[0],
+        &env.creators,
+        &lender.pubkey(),
+        quote,
+        Some(&currency_mint),
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    let (treasury, _) = derive_treasury_address(&env.program_id);
+    let treasury_tokens = currency_account(&env, &treasury);
+    assert_eq!(token_balance(&mut env, &treasury_tokens).await, fee);
+    let lender_tokens = currency_account(&env, &lender.pubkey());
+    assert_eq!(
+        token_balance(&mut env, &lender_tokens).await,
+        CURRENCY_BALANCE - CURRENCY_LOAN_AMOUNT + quote - fee,
+    );
+
+    let admin = env.admin.insecure_clone();
+    let destination = env.second_lender.pubkey();
+    let destination_tokens = currency_account(&env, &destination);
+    let ix = instruction::withdraw_fees(&env.program_id, &admin.pubkey(), &destination, fee + 1, Some(&currency_mint)).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InsufficientTreasury);
+    let ix = instruction::withdraw_fees(&env.program_id, &admin.pubkey(), &destination, fee, Some(&currency_mint)).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+    assert_eq!(token_balance(&mut env, &destination_tokens).await, CURRENCY_BALANCE + fee);
+    assert_eq!(token_balance(&mut env, &treasury_tokens).await, 0);
+}
+
+#[tokio::test]
+async fn creator_royalty_on_interest() {
+    let mut env = setup().await;
+    set_fee(&mut env, FEE_BPS).await;
+    let admin = env.admin.insecure_clone();
+    let royalty_bps = 5000;
+    let ix = instruction::update_royalty(&env.program_id, &admin.pubkey(), &env.creators[0], BASIS_POINTS + 1).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidRoyalty);
+    let lender = env.lender.insecure_clone();
+    let ix = instruction::update_royalty(&env.program_id, &lender.pubkey(), &env.creators[0], royalty_bps).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+    let ix = instruction::update_royalty(&env.program_id, &admin.pubkey(), &env.creators[0], royalty_bps).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+
+    let data_key = start_loan(&mut env).await.pubkey();
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    let elapsed = 5 * SECONDS_PER_DAY;
+    set_time(&mut env, start, elapsed).await;
+    let quote = calculate_repayment(LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap();
+    let interest = quote - LOAN_AMOUNT;
+    let fee = calculate_interest_share(interest, FEE_BPS).unwrap();
+    // the royalty is taken from the interest left after the fee
+    let royalty = calculate_interest_share(interest - fee, royalty_bps).unwrap();
+    let creator_share = calculate_creator_share(royalty, (100 / env.creators.len()) as u8).unwrap();
+
+    // creators must be passed in metadata order
+    let borrower = env.borrower.insecure_clone();
+    let mut ix = repay_ix(&env, &data_key, quote);
+    let count = ix.accounts.len();
+    ix.accounts.swap(count - 1, count - 2);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::CreatorMismatch);
+
+    let creators = env.creators.clone();
+    let mut before = Vec::new();
+    for creator in creators.iter() {
+        before.push(lamports(&mut env, creator).await);
+    }
+    let lender_before = lamports(&mut env, &lender.pubkey()).await;
+    let ix = repay_ix(&env, &data_key, quote);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    for (creator, before) in creators.iter().zip(before) {
+        assert_eq!(lamports(&mut env, creator).await, before + creator_share);
+    }
+    assert_eq!(
+        lamports(&mut env, &lender.pubkey()).await,
+        lender_before + quote - fee - creator_share * creators.len() as u64,
+    );
+}
+
+#[tokio::test]
+async fn unverified_creator_gets_no_royalty() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    let royalty_bps = 5000;
+    let ix = instruction::update_royalty(&env.program_id, &admin.pubkey(), &env.creators[0], royalty_bps).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+    let data_key = start_loan(&mut env).await.pubkey();
+
+    // the metadata loses the second creator's signature after the deposit
+    let mint = env.nft.mint.pubkey();
+    let creators = [(env.creators[0], true), (env.creators[1], false)];
+    env.context.set_account(
+        &derive_metadata_address(&mint).0,
+        &AccountSharedData::from(collection_metadata_account(&mint, &creators, None)),
+    );
+
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    let elapsed = 5 * SECONDS_PER_DAY;
+    set_time(&mut env, start, elapsed).await;
+    let quote = calculate_repayment(LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap();
+    let royalty = calculate_interest_share(quote - LOAN_AMOUNT, royalty_bps).unwrap();
+    let creator_share = calculate_creator_share(royalty, (100 / creators.len()) as u8).unwrap();
+
+    let lender = env.lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+    let (creator, unverified) = (env.creators[0], env.creators[1]);
+    let creator_before = lamports(&mut env, &creator).await;
+    let unverified_before = lamports(&mut env, &unverified).await;
+    let lender_before = lamports(&mut env, &lender.pubkey()).await;
+    let ix = instruction::repay(
+        &env.program_id,
+        &borrower.pubkey(),
+        &mint,
+        &data_key,
+        &env.creators[0],
+        &env.creators[..1],
+        &lender.pubkey(),
+        quote,
+        None,
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(lamports(&mut env, &creator).await, creator_before + creator_share);
+    assert_eq!(lamports(&mut env, &unverified).await, unverified_before);
+    assert_eq!(lamports(&mut env, &lender.pubkey()).await, lender_before + quote - creator_share);
+}
+
+#[tokio::test]
+async fn maximum_fee_and_royalty_leave_the_principal() {
+    let mut env = setup().await;
+    set_fee(&mut env, BASIS_POINTS).await;
+    let admin = env.admin.insecure_clone();
+    let ix = instruction::update_royalty(&env.program_id, &admin.pubkey(), &env.creators[0], BASIS_POINTS).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+
+    let data_key = start_loan(&mut env).await.pubkey();
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    let elapsed = 5 * SECONDS_PER_DAY;
+    set_time(&mut env, start, elapsed).await;
+    let quote = calculate_repayment(LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap();
+    assert!(quote > LOAN_AMOUNT);
+
+    // the fee takes all of the interest and nothing is left for the creators
+    let creators = env.creators.clone();
+    let mut before = Vec::new();
+    for creator in creators.iter() {
+        before.push(lamports(&mut env, creator).await);
+    }
+    let (treasury, _) = derive_treasury_address(&env.program_id);
+    let lender = env.lender.pubkey();
+    let lender_before = lamports(&mut env, &lender).await;
+    let treasury_before = lamports(&mut env, &treasury).await;
+    let borrower = env.borrower.insecure_clone();
+    let ix = repay_ix(&env, &data_key, quote);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(lamports(&mut env, &lender).await, lender_before + LOAN_AMOUNT);
+    assert_eq!(lamports(&mut env, &treasury).await, treasury_before + quote - LOAN_AMOUNT);
+    for (creator, before) in creators.iter().zip(before) {
+        assert_eq!(lamports(&mut env, creator).await, before);
+    }
+}
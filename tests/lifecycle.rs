@@ -0,0 +1,460 @@
+mod common;
+
+use borrow_lend::{
+    error::TokenError, instruction, utils::calculate_repayment, BASIS_POINTS, MAX_INTEREST_RATE_BPS, MAX_LOAN_DURATION,
+    SECONDS_PER_DAY,
+};
+use common::*;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn deposit_offer_select_repay() {
+    let mut env = setup().await;
+    let data_account = deposit(&mut env, LOAN_DURATION).await;
+    let data_key = data_account.pubkey();
+    let vault_token_account = vault_token_account(&env, &data_key);
+    let owner_token_account = env.nft.owner_token_account;
+
+    assert_eq!(token_balance(&mut env, &owner_token_account).await, 0);
+    assert_eq!(token_balance(&mut env, &vault_token_account).await, 1);
+    let details = nft_details(&mut env, &data_key).await;
+    assert_eq!(details.nft_owner, env.borrower.pubkey());
+    assert_eq!(details.nft_mint, env.nft.mint.pubkey());
+    assert_eq!(details.loan_duration, LOAN_DURATION);
+
+    let lender = env.lender.insecure_clone();
+    offer(&mut env, &lender, &data_key).await;
+    let details = nft_details(&mut env, &data_key).await;
+    assert!(details.loan_offered);
+    assert_eq!(details.offer_count, 1);
+
+    let borrower = env.borrower.pubkey();
+    let before = lamports(&mut env, &borrower).await;
+    select(&mut env, &lender.pubkey(), &data_key).await;
+    assert_eq!(lamports(&mut env, &borrower).await, before + LOAN_AMOUNT);
+    let details = nft_details(&mut env, &data_key).await;
+    assert!(details.loan_taken);
+    assert_eq!(details.lender, lender.pubkey());
+    assert_eq!(details.loan_amt, LOAN_AMOUNT);
+
+    let elapsed = 3 * SECONDS_PER_DAY + 1234;
+    set_time(&mut env, details.loan_start, elapsed).await;
+    let quote = calculate_repayment(LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap();
+    assert!(quote > LOAN_AMOUNT);
+
+    let lender_before = lamports(&mut env, &lender.pubkey()).await;
+    let ix = repay_ix(&env, &data_key, quote);
+    let borrower = env.borrower.insecure_clone();
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+
+    assert_eq!(lamports(&mut env, &lender.pubkey()).await, lender_before + quote);
+    assert_eq!(token_balance(&mut env, &owner_token_account).await, 1);
+    assert_eq!(token_balance(&mut env, &vault_token_account).await, 0);
+    assert!(nft_details(&mut env, &data_key).await.canceled);
+
+    // the accepted offer account can be closed once the loan is settled
+    let ix = instruction::withdraw_offer(&env.program_id, &lender.pubkey(), &data_key, None).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+}
+
+#[tokio::test]
+async fn deposit_offer_select_foreclose() {
+    let mut env = setup().await;
+    let data_account = start_loan(&mut env).await;
+    let data_key = data_account.pubkey();
+    let details = nft_details(&mut env, &data_key).await;
+
+    set_time(&mut env, details.loan_start, LOAN_DURATION + 1).await;
+    let ix = foreclose_ix(&env, &data_key);
+    let lender = env.lender.insecure_clone();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+
+    let lender_token_account = spl_associated_token_account::get_associated_token_address(
+        &lender.pubkey(),
+        &env.nft.mint.pubkey(),
+    );
+    assert_eq!(token_balance(&mut env, &lender_token_account).await, 1);
+    let vault_token_account = vault_token_account(&env, &data_key);
+    assert_eq!(token_balance(&mut env, &vault_token_account).await, 0);
+    assert!(nft_details(&mut env, &data_key).await.canceled);
+}
+
+#[tokio::test]
+async fn competing_offers_and_refund() {
+    let mut env = setup().await;
+    let data_account = deposit(&mut env, LOAN_DURATION).await;
+    let data_key = data_account.pubkey();
+    let lender = env.lender.insecure_clone();
+    let second_lender = env.second_lender.insecure_clone();
+    offer(&mut env, &lender, &data_key).await;
+    offer(&mut env, &second_lender, &data_key).await;
+    assert_eq!(nft_details(&mut env, &data_key).await.offer_count, 2);
+
+    select(&mut env, &second_lender.pubkey(), &data_key).await;
+    let details = nft_details(&mut env, &data_key).await;
+    assert_eq!(details.lender, second_lender.pubkey());
+    assert_eq!(details.offer_count, 1);
+
+    let before = lamports(&mut env, &lender.pubkey()).await;
+    let ix = instruction::withdraw_offer(&env.program_id, &lender.pubkey(), &data_key, None).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    assert!(lamports(&mut env, &lender.pubkey()).await > before + LOAN_AMOUNT);
+    assert_eq!(nft_details(&mut env, &data_key).await.offer_count, 0);
+}
+
+#[tokio::test]
+async fn withdraw_open_offer() {
+    let mut env = setup().await;
+    let data_account = deposit(&mut env, LOAN_DURATION).await;
+    let data_key = data_account.pubkey();
+    let lender = env.lender.insecure_clone();
+    offer(&mut env, &lender, &data_key).await;
+
+    let ix = instruction::withdraw_offer(&env.program_id, &lender.pubkey(), &data_key, None).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    let details = nft_details(&mut env, &data_key).await;
+    assert!(!details.loan_offered);
+    assert_eq!(details.offer_count, 0);
+}
+
+#[tokio::test]
+async fn cancel_returns_nft() {
+    let mut env = setup().await;
+    let data_account = deposit(&mut env, LOAN_DURATION).await;
+    let data_key = data_account.pubkey();
+    let ix = instruction::cancel(
+        &env.program_id,
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_key,
+    )
+    .unwrap();
+    let borrower = env.borrower.insecure_clone();
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+
+    let owner_token_account = env.nft.owner_token_account;
+    assert_eq!(token_balance(&mut env, &owner_token_account).await, 1);
+    assert!(nft_details(&mut env, &data_key).await.canceled);
+}
+
+#[tokio::test]
+async fn admin_transfer_is_two_step() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    let new_admin = env.lender.insecure_clone();
+    let ix = instruction::update_config(
+        &env.program_id,
+        &admin.pubkey(),
+        instruction::ProcessUpdateConfig {
+            fee_bps: 0,
+            deposits_paused: false,
+            loans_paused: false,
+            new_admin: Some(new_admin.pubkey()),
+            clear_pending_admin: false,
+        },
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+
+    // the old admin stays in charge until the transfer is accepted
+    let ix = instruction::update_interest(&env.program_id, &admin.pubkey(), &env.creators[0], 1).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+
+    let ix = instruction::accept_admin(&env.program_id, &new_admin.pubkey()).unwrap();
+    process(&mut env, &[ix], &[&new_admin]).await.unwrap();
+    let ix = instruction::update_interest(&env.program_id, &new_admin.pubkey(), &env.creators[0], 2).unwrap();
+    process(&mut env, &[ix], &[&new_admin]).await.unwrap();
+}
+
+#[tokio::test]
+async fn admin_can_withdraw_the_nomination() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    let new_admin = env.lender.insecure_clone();
+    let update = |new_admin, clear_pending_admin| instruction::ProcessUpdateConfig {
+        fee_bps: 0,
+        deposits_paused: false,
+        loans_paused: false,
+        new_admin,
+        clear_pending_admin,
+    };
+    let ix = instruction::update_config(&env.program_id, &admin.pubkey(), update(Some(new_admin.pubkey()), false)).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+    let ix = instruction::update_config(&env.program_id, &admin.pubkey(), update(None, true)).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+
+    let ix = instruction::accept_admin(&env.program_id, &new_admin.pubkey()).unwrap();
+    let result = process(&mut env, &[ix], &[&new_admin]).await;
+    assert_token_error(result, TokenError::NotPendingAdmin);
+    let ix = instruction::update_interest(&env.program_id, &admin.pubkey(), &env.creators[0], 1).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+}
+
+#[tokio::test]
+async fn offers_up_to_the_ltv() {
+    let mut env = setup().await;
+    let data_account = deposit(&mut env, LOAN_DURATION).await;
+    let data_key = data_account.pubkey();
+    let admin = env.admin.insecure_clone();
+    let lender = env.lender.insecure_clone();
+
+    // 75% of the collection price
+    let ix = instruction::update_ltv(&env.program_id, &admin.pubkey(), &env.creators[0], 7_500).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+    let amount = 3 * COLLECTION_PRICE / 4;
+    let ix = offer_ix(&env, &lender.pubkey(), &data_key, amount);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+
+    let second_lender = env.second_lender.insecure_clone();
+    let ix = offer_ix(&env, &second_lender.pubkey(), &data_key, LOAN_AMOUNT / 2);
+    process(&mut env, &[ix], &[&second_lender]).await.unwrap();
+
+    let borrower = env.borrower.pubkey();
+    let before = lamports(&mut env, &borrower).await;
+    select(&mut env, &second_lender.pubkey(), &data_key).await;
+    assert_eq!(lamports(&mut env, &borrower).await, before + LOAN_AMOUNT / 2);
+    assert_eq!(nft_details(&mut env, &data_key).await.loan_amt, LOAN_AMOUNT / 2);
+}
+
+#[tokio::test]
+async fn grace_period_with_late_fee() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    let (grace_period, late_fee_bps) = (2 * SECONDS_PER_DAY, 500);
+    let ix = instruction::update_grace(&env.program_id, &admin.pubkey(), &env.creators[0], grace_period, BASIS_POINTS + 1).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidFee);
+    let ix = instruction::update_grace(&env.program_id, &admin.pubkey(), &env.creators[0], grace_period, late_fee_bps).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+
+    let data_key = start_loan(&mut env).await.pubkey();
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    let elapsed = LOAN_DURATION + SECONDS_PER_DAY;
+    set_time(&mut env, start, elapsed).await;
+    let lender = env.lender.insecure_clone();
+    let ix = foreclose_ix(&env, &data_key);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::LoanNotExpired);
+
+    let quote = calculate_repayment(LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap();
+    let late_fee = LOAN_AMOUNT * late_fee_bps / BASIS_POINTS;
+    let borrower = env.borrower.insecure_clone();
+    let ix = repay_ix(&env, &data_key, quote);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::InsufficientRepayment);
+    let lender_before = lamports(&mut env, &lender.pubkey()).await;
+    let ix = repay_ix(&env, &data_key, quote + late_fee);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(lamports(&mut env, &lender.pubkey()).await, lender_before + quote + late_fee);
+}
+
+#[tokio::test]
+async fn foreclose_after_the_grace_period() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    let grace_period = 2 * SECONDS_PER_DAY;
+    let ix = instruction::update_grace(&env.program_id, &admin.pubkey(), &env.creators[0], grace_period, 500).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+
+    let data_key = start_loan(&mut env).await.pubkey();
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    set_time(&mut env, start, LOAN_DURATION + grace_period + 1).await;
+    let borrower = env.borrower.insecure_clone();
+    let ix = repay_ix(&env, &data_key, u64::MAX);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::LoanExpired);
+    let lender = env.lender.insecure_clone();
+    let ix = foreclose_ix(&env, &data_key);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+}
+
+#[tokio::test]
+async fn extend_pays_interest_and_resets_the_loan() {
+    let mut env = setup().await;
+    let data_key = start_loan(&mut env).await.pubkey();
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    let elapsed = 10 * SECONDS_PER_DAY;
+    set_time(&mut env, start, elapsed).await;
+
+    let borrower = env.borrower.insecure_clone();
+    let lender = env.lender.insecure_clone();
+    let (duration, rate) = (60 * SECONDS_PER_DAY, INTEREST_RATE_BPS / 2);
+    let extend_ix = |env: &TestEnv, duration: u64, rate: u64| {
+        instruction::extend(
+            &env.program_id,
+            &borrower.pubkey(),
+            &lender.pubkey(),
+            &env.nft.mint.pubkey(),
+            &data_key,
+            &whitelist_address(env),
+            &env.creators,
+            duration,
+            rate,
+            None,
+        )
+        .unwrap()
+    };
+    let mut ix = extend_ix(&env, duration, rate);
+    ix.accounts[1].is_signer = false;
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_instruction_error(result, solana_sdk::instruction::InstructionError::MissingRequiredSignature);
+    // the new terms are bounded like any other loan
+    for (duration, rate, expected) in [
+        (0, rate, TokenError::InvalidLoanDuration),
+        (MAX_LOAN_DURATION + 1, rate, TokenError::InvalidLoanDuration),
+        (duration, MAX_INTEREST_RATE_BPS + 1, TokenError::InvalidInterestRate),
+    ] {
+        let ix = extend_ix(&env, duration, rate);
+        let result = process(&mut env, &[ix], &[&borrower, &lender]).await;
+        assert_token_error(result, expected);
+    }
+
+    let interest = calculate_repayment(LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap() - LOAN_AMOUNT;
+    let lender_before = lamports(&mut env, &lender.pubkey()).await;
+    let ix = extend_ix(&env, duration, rate);
+    process(&mut env, &[ix], &[&borrower, &lender]).await.unwrap();
+    assert_eq!(lamports(&mut env, &lender.pubkey()).await, lender_before + interest);
+    let details = nft_details(&mut env, &data_key).await;
+    assert_eq!(details.loan_start, start + elapsed);
+    assert_eq!(details.loan_duration, duration);
+    assert_eq!(details.interest_rate_bps, rate);
+    assert!(details.loan_taken);
+
+    // past the original term the loan is still running under the new one
+    let elapsed = 40 * SECONDS_PER_DAY;
+    set_time(&mut env, details.loan_start, elapsed).await;
+    let quote = calculate_repayment(LOAN_AMOUNT, rate, elapsed).unwrap();
+    let ix = repay_ix(&env, &data_key, quote);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(token_balance(&mut env, &env.nft.owner_token_account.clone()).await, 1);
+}
+
+#[tokio::test]
+async fn refinance_with_a_new_lender() {
+    let mut env = setup().await;
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let lender = env.lender.insecure_clone();
+    let ix = offer_ix(&env, &lender.pubkey(), &data_key, LOAN_AMOUNT / 2);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    select(&mut env, &lender.pubkey(), &data_key).await;
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    let elapsed = 7 * SECONDS_PER_DAY;
+    set_time(&mut env, start, elapsed).await;
+
+    let second_lender = env.second_lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+    let refinance_ix = |env: &TestEnv| {
+        instruction::refinance(
+            &env.program_id,
+            &borrower.pubkey(),
+            &second_lender.pubkey(),
+            &lender.pubkey(),
+            &env.nft.mint.pubkey(),
+            &data_key,
+            &whitelist_address(env),
+            &env.creators,
+            None,
+        )
+        .unwrap()
+    };
+    // the offer has to cover the principal and the accrued interest
+    let ix = offer_ix(&env, &second_lender.pubkey(), &data_key, LOAN_AMOUNT / 2);
+    process(&mut env, &[ix], &[&second_lender]).await.unwrap();
+    let ix = refinance_ix(&env);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::InsufficientRepayment);
+    let ix = instruction::withdraw_offer(&env.program_id, &second_lender.pubkey(), &data_key, None).unwrap();
+    process(&mut env, &[ix], &[&second_lender]).await.unwrap();
+
+    offer(&mut env, &second_lender, &data_key).await;
+    // a later change of the collection rate doesn't touch the escrowed terms
+    let admin = env.admin.insecure_clone();
+    let ix = instruction::update_interest(&env.program_id, &admin.pubkey(), &env.creators[0], 2 * INTEREST_RATE_BPS).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+    let debt = calculate_repayment(LOAN_AMOUNT / 2, INTEREST_RATE_BPS, elapsed).unwrap();
+    let lender_before = lamports(&mut env, &lender.pubkey()).await;
+    let borrower_before = lamports(&mut env, &borrower.pubkey()).await;
+    let ix = refinance_ix(&env);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(lamports(&mut env, &lender.pubkey()).await, lender_before + debt);
+    assert_eq!(lamports(&mut env, &borrower.pubkey()).await, borrower_before + LOAN_AMOUNT - debt);
+    assert_eq!(token_balance(&mut env, &vault_token_account(&env, &data_key)).await, 1);
+    let details = nft_details(&mut env, &data_key).await;
+    assert_eq!(details.lender, second_lender.pubkey());
+    assert_eq!(details.loan_amt, LOAN_AMOUNT);
+    assert_eq!(details.loan_start, start + elapsed);
+    assert_eq!(details.loan_duration, LOAN_DURATION);
+    assert_eq!(details.interest_rate_bps, INTEREST_RATE_BPS);
+    assert_eq!(details.offer_count, 0);
+
+    // the old lender's offer is settled, the new one is still running
+    let ix = instruction::withdraw_offer(&env.program_id, &lender.pubkey(), &data_key, None).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    let ix = instruction::withdraw_offer(&env.program_id, &second_lender.pubkey(), &data_key, None).unwrap();
+    let result = process(&mut env, &[ix], &[&second_lender]).await;
+    assert_token_error(result, TokenError::LoanStillActive);
+
+    let ix = instruction::repay(
+        &env.program_id,
+        &borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_key,
+        &env.creators[0],
+        &env.creators,
+        &second_lender.pubkey(),
+        LOAN_AMOUNT,
+        None,
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(token_balance(&mut env, &env.nft.owner_token_account.clone()).await, 1);
+}
+
+#[tokio::test]
+async fn partial_repayments_pay_interest_then_principal() {
+    let mut env = setup().await;
+    let data_key = start_loan(&mut env).await.pubkey();
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    let borrower = env.borrower.insecure_clone();
+    let lender = env.lender.insecure_clone();
+    let partial_repay_ix = |env: &TestEnv, amount: u64| {
+        instruction::partial_repay(
+            &env.program_id,
+            &borrower.pubkey(),
+            &env.nft.mint.pubkey(),
+            &data_key,
+            &env.creators[0],
+            &env.creators,
+            &lender.pubkey(),
+            amount,
+            None,
+        )
+        .unwrap()
+    };
+
+    let elapsed = 5 * SECONDS_PER_DAY;
+    set_time(&mut env, start, elapsed).await;
+    let interest = calculate_repayment(LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap() - LOAN_AMOUNT;
+    let ix = partial_repay_ix(&env, interest - 1);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::InsufficientRepayment);
+    let lender_before = lamports(&mut env, &lender.pubkey()).await;
+    let ix = partial_repay_ix(&env, interest + LOAN_AMOUNT / 2);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(lamports(&mut env, &lender.pubkey()).await, lender_before + interest + LOAN_AMOUNT / 2);
+    let details = nft_details(&mut env, &data_key).await;
+    let outstanding = LOAN_AMOUNT - LOAN_AMOUNT / 2;
+    assert_eq!(details.outstanding_principal, outstanding);
+    assert_eq!(details.interest_start, start + elapsed);
+    assert_eq!(details.loan_start, start);
+
+    // interest keeps accruing on the lower principal until the balance is paid
+    set_time(&mut env, start, 2 * elapsed).await;
+    let balance = calculate_repayment(outstanding, INTEREST_RATE_BPS, elapsed).unwrap();
+    let ix = partial_repay_ix(&env, balance);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(token_balance(&mut env, &env.nft.owner_token_account.clone()).await, 1);
+    let details = nft_details(&mut env, &data_key).await;
+    assert!(details.canceled);
+    assert_eq!(details.outstanding_principal, 0);
+}
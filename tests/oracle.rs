@@ -0,0 +1,172 @@
+mod common;
+
+use borrow_lend::{
+    error::TokenError,
+    instruction,
+    state::{FloorPrice, PriceConfig, PriceSource},
+    utils::{derive_manual_price_address, derive_whitelist_address},
+};
+use borsh::BorshSerialize;
+use common::*;
+use solana_sdk::{
+    instruction::Instruction,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::Signer,
+};
+
+fn offer_with_price_account(env: &TestEnv, data_account: &Pubkey, price_account: &Pubkey, amount: u64) -> Instruction {
+    let mut ix = offer_ix(env, &env.lender.pubkey(), data_account, amount);
+    ix.accounts[10].pubkey = *price_account;
+    ix
+}
+
+async fn set_price_config(env: &mut TestEnv, price: PriceConfig) {
+    let admin = env.admin.insecure_clone();
+    let ix = instruction::update_price_source(&env.program_id, &admin.pubkey(), &env.creators[0], price).unwrap();
+    process(env, &[ix], &[&admin]).await.unwrap();
+}
+
+#[tokio::test]
+async fn stale_mokshya_price_fails_the_offer() {
+    let mut env = setup().await;
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let now = now(&mut env).await;
+    set_mokshya_price(&mut env, COLLECTION_PRICE, now - MAX_PRICE_AGE - 1);
+
+    let lender = env.lender.insecure_clone();
+    let ix = offer_ix(&env, &lender.pubkey(), &data_key, LOAN_AMOUNT);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::StalePrice);
+
+    set_mokshya_price(&mut env, COLLECTION_PRICE, now - MAX_PRICE_AGE);
+    offer(&mut env, &lender, &data_key).await;
+}
+
+#[tokio::test]
+async fn mokshya_price_account_must_be_owned_by_the_feed() {
+    let mut env = setup().await;
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let (address, owner) = (env.price_account, Pubkey::new_unique());
+    let data = env.context.banks_client.get_account(address).await.unwrap().unwrap().data;
+    set_price_account(&mut env, &address, &owner, data);
+
+    let lender = env.lender.insecure_clone();
+    let ix = offer_ix(&env, &lender.pubkey(), &data_key, LOAN_AMOUNT);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::InvalidPriceAccount);
+}
+
+#[tokio::test]
+async fn manual_price() {
+    let mut env = setup().await;
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    set_price_config(
+        &mut env,
+        PriceConfig {
+            source: PriceSource::Manual,
+            max_age: MAX_PRICE_AGE,
+            max_confidence_bps: 0,
+        },
+    )
+    .await;
+    let (whitelist, _) = derive_whitelist_address(&env.creators[0], &env.program_id);
+    let (manual, _) = derive_manual_price_address(&whitelist, None, &env.program_id);
+    let lender = env.lender.insecure_clone();
+
+    // nothing is posted yet
+    let ix = offer_with_price_account(&env, &data_key, &manual, LOAN_AMOUNT);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::InvalidPriceAccount);
+
+    let admin = env.admin.insecure_clone();
+    let ix = instruction::post_price(&env.program_id, &lender.pubkey(), &env.creators[0], COLLECTION_PRICE, None).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+    let ix = instruction::post_price(&env.program_id, &admin.pubkey(), &env.creators[0], 0, None).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidPrice);
+
+    let ix = instruction::post_price(&env.program_id, &admin.pubkey(), &env.creators[0], 2 * COLLECTION_PRICE, None).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+    let ix = offer_with_price_account(&env, &data_key, &manual, COLLECTION_PRICE + 1);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::OfferTooHigh);
+    let ix = offer_with_price_account(&env, &data_key, &env.price_account, COLLECTION_PRICE);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::InvalidPriceAccount);
+
+    let ix = offer_with_price_account(&env, &data_key, &manual, COLLECTION_PRICE);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    select(&mut env, &lender.pubkey(), &data_key).await;
+    assert_eq!(nft_details(&mut env, &data_key).await.loan_amt, COLLECTION_PRICE);
+}
+
+#[tokio::test]
+async fn stale_manual_price_fails_the_offer() {
+    let mut env = setup().await;
+    set_price_config(
+        &mut env,
+        PriceConfig {
+            source: PriceSource::Manual,
+            max_age: MAX_PRICE_AGE,
+            max_confidence_bps: 0,
+        },
+    )
+    .await;
+    let admin = env.admin.insecure_clone();
+    let ix = instruction::post_price(&env.program_id, &admin.pubkey(), &env.creators[0], COLLECTION_PRICE, None).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+
+    let now = now(&mut env).await;
+    set_time(&mut env, now, MAX_PRICE_AGE + 1).await;
+    let (whitelist, _) = derive_whitelist_address(&env.creators[0], &env.program_id);
+    let (manual, _) = derive_manual_price_address(&whitelist, None, &env.program_id);
+    let lender = env.lender.insecure_clone();
+    let ix = offer_with_price_account(&env, &data_key, &manual, LOAN_AMOUNT);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::StalePrice);
+}
+
+#[tokio::test]
+async fn floor_price_confidence() {
+    let mut env = setup().await;
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let (floor_program, floor_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+    set_price_config(
+        &mut env,
+        PriceConfig {
+            source: PriceSource::Floor {
+                program: floor_program,
+                account: floor_account,
+            },
+            max_age: MAX_PRICE_AGE,
+            max_confidence_bps: 100,
+        },
+    )
+    .await;
+    // 3.5 SOL, +-2.9%
+    let now = now(&mut env).await;
+    let mut feed = FloorPrice {
+        price: 350_000_000,
+        conf: 10_000_000,
+        expo: -8,
+        publish_time: now as i64,
+    };
+    set_price_account(&mut env, &floor_account, &floor_program, feed.try_to_vec().unwrap());
+    let loan_amt = 35 * LAMPORTS_PER_SOL / 20;
+    let lender = env.lender.insecure_clone();
+    let ix = offer_with_price_account(&env, &data_key, &floor_account, loan_amt);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::PriceConfidenceTooWide);
+
+    // +-0.29%
+    feed.conf = 1_000_000;
+    set_price_account(&mut env, &floor_account, &floor_program, feed.try_to_vec().unwrap());
+    let ix = offer_with_price_account(&env, &data_key, &floor_account, loan_amt + 1);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::OfferTooHigh);
+    let ix = offer_with_price_account(&env, &data_key, &floor_account, loan_amt);
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+}
@@ -0,0 +1,300 @@
+mod common;
+
+use borrow_lend::{
+    error::TokenError,
+    instruction,
+    state::{AuctionConfig, LendingPool, PoolShare},
+    utils::{calculate_repayment, derive_pool_address, generate_pda_and_bump_seed},
+    MAX_POOL_AUCTION_ROUNDS, MAX_POOL_LOAN_DURATION, POOL, POOL_VIRTUAL_SHARES, SECONDS_PER_DAY,
+};
+use borsh::BorshDeserialize;
+use common::*;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+};
+
+async fn enable_auction(env: &mut TestEnv) {
+    let admin = env.admin.insecure_clone();
+    let config = AuctionConfig {
+        duration: SECONDS_PER_DAY,
+        start_premium_bps: 2_000,
+        end_discount_bps: 5_000,
+    };
+    let ix = instruction::update_auction(&env.program_id, &admin.pubkey(), &env.creators[0], Some(config)).unwrap();
+    process(env, &[ix], &[&admin]).await.unwrap();
+}
+
+async fn pool(env: &mut TestEnv) -> LendingPool {
+    let (address, _) = derive_pool_address(&whitelist_address(env), &env.program_id);
+    let account = env.context.banks_client.get_account(address).await.unwrap().unwrap();
+    LendingPool::try_from_slice(&account.data).unwrap()
+}
+
+async fn shares(env: &mut TestEnv, lender: &Pubkey) -> u64 {
+    let (pool, _) = derive_pool_address(&whitelist_address(env), &env.program_id);
+    let (address, _) = generate_pda_and_bump_seed(POOL, &pool, lender, &env.program_id);
+    let account = env.context.banks_client.get_account(address).await.unwrap().unwrap();
+    PoolShare::try_from_slice(&account.data).unwrap().shares
+}
+
+fn borrow_ix(env: &TestEnv, data_account: &Pubkey, amount: u64) -> Instruction {
+    instruction::pool_borrow(
+        &env.program_id,
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        data_account,
+        &whitelist_address(env),
+        &env.price_account,
+        amount,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn pool_lends_and_earns_the_interest() {
+    let mut env = setup().await;
+    let lender = env.lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+    let whitelist = whitelist_address(&env);
+    let ix = instruction::pool_deposit(&env.program_id, &lender.pubkey(), &whitelist, 2 * LOAN_AMOUNT).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    let lender_shares = 2 * LOAN_AMOUNT * POOL_VIRTUAL_SHARES;
+    assert_eq!(shares(&mut env, &lender.pubkey()).await, lender_shares);
+
+    // pools only lend where defaults can be auctioned
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let ix = borrow_ix(&env, &data_key, LOAN_AMOUNT);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::AuctionNotEnabled);
+    enable_auction(&mut env).await;
+    let ix = borrow_ix(&env, &data_key, LOAN_AMOUNT + 1);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::OfferTooHigh);
+    let before = lamports(&mut env, &borrower.pubkey()).await;
+    let ix = borrow_ix(&env, &data_key, LOAN_AMOUNT);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(lamports(&mut env, &borrower.pubkey()).await, before + LOAN_AMOUNT);
+    let (pool_key, _) = derive_pool_address(&whitelist, &env.program_id);
+    let details = nft_details(&mut env, &data_key).await;
+    assert_eq!(details.lender, pool_key);
+    assert_eq!(details.outstanding_principal, LOAN_AMOUNT);
+    assert_eq!(pool(&mut env).await.borrowed, LOAN_AMOUNT);
+
+    // lent out lamports can't be withdrawn
+    let ix = instruction::pool_withdraw(&env.program_id, &lender.pubkey(), &whitelist, lender_shares).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::InsufficientLiquidity);
+    let ix = instruction::pool_withdraw(&env.program_id, &lender.pubkey(), &whitelist, lender_shares + 1).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::InsufficientShares);
+
+    let elapsed = 10 * SECONDS_PER_DAY;
+    set_time(&mut env, details.loan_start, elapsed).await;
+    let quote = calculate_repayment(LOAN_AMOUNT, INTEREST_RATE_BPS, elapsed).unwrap();
+    let ix = instruction::repay(
+        &env.program_id,
+        &borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_key,
+        &env.creators[0],
+        &env.creators,
+        &pool_key,
+        quote,
+        None,
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+    assert_eq!(pool(&mut env).await.borrowed, 0);
+
+    // a later deposit buys shares at the higher share price
+    let second_lender = env.second_lender.insecure_clone();
+    let ix = instruction::pool_deposit(&env.program_id, &second_lender.pubkey(), &whitelist, LOAN_AMOUNT).unwrap();
+    process(&mut env, &[ix], &[&second_lender]).await.unwrap();
+    assert!(shares(&mut env, &second_lender.pubkey()).await < LOAN_AMOUNT * POOL_VIRTUAL_SHARES);
+
+    let before = lamports(&mut env, &lender.pubkey()).await;
+    let ix = instruction::pool_withdraw(&env.program_id, &lender.pubkey(), &whitelist, lender_shares).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    let redeemed = lamports(&mut env, &lender.pubkey()).await - before;
+    let interest = quote - LOAN_AMOUNT;
+    // the virtual shares keep under a lamport of the earlier lender's part
+    assert!(redeemed + 1 >= 2 * LOAN_AMOUNT + interest && redeemed <= 2 * LOAN_AMOUNT + interest + 1);
+}
+
+#[tokio::test]
+async fn anyone_can_auction_a_defaulted_pool_loan() {
+    let mut env = setup().await;
+    enable_auction(&mut env).await;
+    let lender = env.lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+    let whitelist = whitelist_address(&env);
+    let ix = instruction::pool_deposit(&env.program_id, &lender.pubkey(), &whitelist, LOAN_AMOUNT).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let ix = borrow_ix(&env, &data_key, LOAN_AMOUNT);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    set_time(&mut env, start, LOAN_DURATION + 1).await;
+    let (pool_key, _) = derive_pool_address(&whitelist, &env.program_id);
+    let mut ix = instruction::start_auction(&env.program_id, &pool_key, &data_key, &whitelist).unwrap();
+    ix.accounts[0].is_signer = false;
+    process(&mut env, &[ix], &[]).await.unwrap();
+    let auction = nft_details(&mut env, &data_key).await.auction.unwrap();
+    assert_eq!(pool(&mut env).await.borrowed, 0);
+
+    let bidder = env.second_lender.insecure_clone();
+    let ix = instruction::bid(
+        &env.program_id,
+        &bidder.pubkey(),
+        &borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_key,
+        &env.creators[0],
+        &env.creators,
+        &pool_key,
+        auction.start_price,
+    )
+    .unwrap();
+    process(&mut env, &[ix], &[&bidder]).await.unwrap();
+    assert_eq!(pool(&mut env).await.borrowed, 0);
+    let ix = instruction::pool_withdraw(&env.program_id, &lender.pubkey(), &whitelist, LOAN_AMOUNT * POOL_VIRTUAL_SHARES).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+}
+
+#[tokio::test]
+async fn defaulted_pool_loan_is_written_off_for_every_lender() {
+    let mut env = setup().await;
+    enable_auction(&mut env).await;
+    let lender = env.lender.insecure_clone();
+    let second_lender = env.second_lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+    let whitelist = whitelist_address(&env);
+    for depositor in [&lender, &second_lender] {
+        let ix = instruction::pool_deposit(&env.program_id, &depositor.pubkey(), &whitelist, LOAN_AMOUNT).unwrap();
+        process(&mut env, &[ix], &[depositor]).await.unwrap();
+    }
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let ix = borrow_ix(&env, &data_key, LOAN_AMOUNT);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    set_time(&mut env, start, LOAN_DURATION + 1).await;
+    let (pool_key, _) = derive_pool_address(&whitelist, &env.program_id);
+    let start_auction_ix = |env: &TestEnv| {
+        let mut ix = instruction::start_auction(&env.program_id, &pool_key, &data_key, &whitelist).unwrap();
+        ix.accounts[0].is_signer = false;
+        ix
+    };
+    let ix = start_auction_ix(&env);
+    process(&mut env, &[ix], &[]).await.unwrap();
+    assert_eq!(pool(&mut env).await.borrowed, 0);
+
+    // the loss is shared, the first lender out can't redeem at the old share price
+    for depositor in [&lender, &second_lender] {
+        let before = lamports(&mut env, &depositor.pubkey()).await;
+        let depositor_shares = shares(&mut env, &depositor.pubkey()).await;
+        let ix = instruction::pool_withdraw(&env.program_id, &depositor.pubkey(), &whitelist, depositor_shares).unwrap();
+        process(&mut env, &[ix], &[depositor]).await.unwrap();
+        let redeemed = lamports(&mut env, &depositor.pubkey()).await - before;
+        assert!(redeemed.abs_diff(LOAN_AMOUNT / 2) <= 1);
+    }
+
+    // unsold rounds restart until the last one, which ends at a price of zero
+    for round in 1..MAX_POOL_AUCTION_ROUNDS {
+        let auction = nft_details(&mut env, &data_key).await.auction.unwrap();
+        set_time(&mut env, auction.end, 0).await;
+        let ix = start_auction_ix(&env);
+        process(&mut env, &[ix], &[]).await.unwrap();
+        let restarted = nft_details(&mut env, &data_key).await.auction.unwrap();
+        assert_eq!(restarted.round, round);
+        assert_eq!(restarted.debt, auction.debt);
+    }
+    let auction = nft_details(&mut env, &data_key).await.auction.unwrap();
+    assert_eq!(auction.end_price, 0);
+    set_time(&mut env, auction.end, 0).await;
+    let ix = start_auction_ix(&env);
+    let result = process(&mut env, &[ix], &[]).await;
+    assert_token_error(result, TokenError::AuctionRoundsExhausted);
+}
+
+#[tokio::test]
+async fn donations_dont_inflate_the_share_price() {
+    let mut env = setup().await;
+    let attacker = env.lender.insecure_clone();
+    let depositor = env.second_lender.insecure_clone();
+    let whitelist = whitelist_address(&env);
+    let (pool_key, _) = derive_pool_address(&whitelist, &env.program_id);
+    let ixs = vec![
+        instruction::pool_deposit(&env.program_id, &attacker.pubkey(), &whitelist, 1).unwrap(),
+        system_instruction::transfer(&attacker.pubkey(), &pool_key, LOAN_AMOUNT),
+    ];
+    process(&mut env, &ixs, &[&attacker]).await.unwrap();
+    assert_eq!(shares(&mut env, &attacker.pubkey()).await, POOL_VIRTUAL_SHARES);
+
+    // a deposit rounding to no shares is rejected instead of feeding the pool
+    let ix = instruction::pool_deposit(&env.program_id, &depositor.pubkey(), &whitelist, LOAN_AMOUNT / 10_000).unwrap();
+    let result = process(&mut env, &[ix], &[&depositor]).await;
+    assert_token_error(result, TokenError::PoolAmountTooSmall);
+
+    // the virtual shares own half of the donation, which the attacker can't redeem
+    let before = lamports(&mut env, &attacker.pubkey()).await;
+    let ix = instruction::pool_withdraw(&env.program_id, &attacker.pubkey(), &whitelist, POOL_VIRTUAL_SHARES).unwrap();
+    process(&mut env, &[ix], &[&attacker]).await.unwrap();
+    assert!(lamports(&mut env, &attacker.pubkey()).await - before <= LOAN_AMOUNT / 2 + 1);
+}
+
+#[tokio::test]
+async fn deposit_and_borrow_from_the_pool_at_once() {
+    let mut env = setup().await;
+    enable_auction(&mut env).await;
+    let lender = env.lender.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+    let whitelist = whitelist_address(&env);
+    let ix = instruction::pool_deposit(&env.program_id, &lender.pubkey(), &whitelist, LOAN_AMOUNT).unwrap();
+    process(&mut env, &[ix], &[&lender]).await.unwrap();
+
+    let data_account = Keypair::new();
+    let data_key = data_account.pubkey();
+    let borrow_ix = |env: &TestEnv, nft_mint: &Pubkey, duration: u64, amount: u64| {
+        instruction::deposit_and_borrow(
+            &env.program_id,
+            &borrower.pubkey(),
+            nft_mint,
+            &data_key,
+            &env.creators[0],
+            &env.price_account,
+            duration,
+            amount,
+            vec![],
+        )
+        .unwrap()
+    };
+    // the deposit validation still applies
+    let ix = borrow_ix(&env, &env.fake_nft.mint.pubkey(), LOAN_DURATION, LOAN_AMOUNT);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::CollectionNotWhitelisted);
+    // a failed borrow leaves the NFT with the borrower
+    let ix = borrow_ix(&env, &env.nft.mint.pubkey(), LOAN_DURATION, LOAN_AMOUNT + 1);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::OfferTooHigh);
+    let ix = borrow_ix(&env, &env.nft.mint.pubkey(), MAX_POOL_LOAN_DURATION + 1, LOAN_AMOUNT);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::InvalidLoanDuration);
+
+    let before = lamports(&mut env, &borrower.pubkey()).await;
+    let ix = borrow_ix(&env, &env.nft.mint.pubkey(), LOAN_DURATION, LOAN_AMOUNT);
+    process(&mut env, &[ix], &[&borrower, &data_account]).await.unwrap();
+    let rent = lamports(&mut env, &data_key).await;
+    assert_eq!(lamports(&mut env, &borrower.pubkey()).await, before + LOAN_AMOUNT - rent);
+    assert_eq!(token_balance(&mut env, &vault_token_account(&env, &data_key)).await, 1);
+    let (pool_key, _) = derive_pool_address(&whitelist, &env.program_id);
+    let details = nft_details(&mut env, &data_key).await;
+    assert!(details.loan_taken);
+    assert_eq!(details.lender, pool_key);
+    assert_eq!(details.loan_duration, LOAN_DURATION);
+    assert_eq!(pool(&mut env).await.borrowed, LOAN_AMOUNT);
+}
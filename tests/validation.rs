@@ -0,0 +1,616 @@
+mod common;
+
+use borrow_lend::{error::TokenError, instruction, MAX_INTEREST_RATE_BPS, MAX_LOAN_DURATION, SECONDS_PER_DAY};
+use common::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Replaces the account at `index` with `key`.
+fn with_account(mut ix: Instruction, index: usize, key: Pubkey) -> Instruction {
+    ix.accounts[index].pubkey = key;
+    ix
+}
+
+/// Drops the signer flag of the account at `index`.
+fn unsigned(mut ix: Instruction, index: usize) -> Instruction {
+    ix.accounts[index].is_signer = false;
+    ix
+}
+
+fn deposit_ix(env: &TestEnv, data_account: &Pubkey, duration: u64) -> Instruction {
+    instruction::deposit(
+        &env.program_id,
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        data_account,
+        &env.creators[0],
+        duration,
+        None,
+    )
+    .unwrap()
+}
+
+fn cancel_ix(env: &TestEnv, nft_owner: &Pubkey, mint: &Pubkey, data_account: &Pubkey) -> Instruction {
+    instruction::cancel(&env.program_id, nft_owner, mint, data_account).unwrap()
+}
+
+fn set_paused(env: &TestEnv, deposits_paused: bool, loans_paused: bool) -> Instruction {
+    instruction::update_config(
+        &env.program_id,
+        &env.admin.pubkey(),
+        instruction::ProcessUpdateConfig {
+            fee_bps: 0,
+            deposits_paused,
+            loans_paused,
+            new_admin: None,
+            clear_pending_admin: false,
+        },
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn deposit_validation() {
+    let mut env = setup().await;
+    let borrower = env.borrower.insecure_clone();
+    let cases: Vec<(Box<dyn Fn(&TestEnv, &Pubkey) -> Instruction>, TokenError)> = vec![
+        (Box::new(|env, data| deposit_ix(env, data, 0)), TokenError::InvalidLoanDuration),
+        (Box::new(|env, data| deposit_ix(env, data, MAX_LOAN_DURATION + 1)), TokenError::InvalidLoanDuration),
+        (
+            Box::new(|env, data| with_account(deposit_ix(env, data, LOAN_DURATION), 10, Pubkey::new_unique())),
+            TokenError::InvalidMetadataAccount,
+        ),
+        (
+            Box::new(|env, data| with_account(deposit_ix(env, data, LOAN_DURATION), 11, Pubkey::new_unique())),
+            TokenError::InvalidWhitelistAccount,
+        ),
+        (
+            // owned by the program but not the collection's whitelist
+            Box::new(|env, data| {
+                let ix = deposit_ix(env, data, LOAN_DURATION);
+                let config_account = ix.accounts[12].pubkey;
+                with_account(ix, 11, config_account)
+            }),
+            TokenError::InvalidWhitelistAccount,
+        ),
+        (
+            Box::new(|env, data| {
+                instruction::deposit(
+                    &env.program_id,
+                    &env.borrower.pubkey(),
+                    &env.fake_nft.mint.pubkey(),
+                    data,
+                    &env.creators[0],
+                    LOAN_DURATION,
+                    None,
+                )
+                .unwrap()
+            }),
+            TokenError::CollectionNotWhitelisted,
+        ),
+        (
+            Box::new(|env, data| with_account(deposit_ix(env, data, LOAN_DURATION), 2, Pubkey::new_unique())),
+            TokenError::InvalidTokenAccount,
+        ),
+        (
+            Box::new(|env, data| with_account(deposit_ix(env, data, LOAN_DURATION), 5, Pubkey::new_unique())),
+            TokenError::InvalidVaultAddress,
+        ),
+        (
+            Box::new(|env, data| with_account(deposit_ix(env, data, LOAN_DURATION), 6, Pubkey::new_unique())),
+            TokenError::InvalidTokenAccount,
+        ),
+        (
+            Box::new(|env, data| with_account(deposit_ix(env, data, LOAN_DURATION), 12, Pubkey::new_unique())),
+            TokenError::InvalidConfigAccount,
+        ),
+    ];
+    for (build, expected) in cases {
+        let data_account = Keypair::new();
+        let ix = build(&env, &data_account.pubkey());
+        let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+        assert_token_error(result, expected);
+    }
+
+    let data_account = Keypair::new();
+    let ix = unsigned(deposit_ix(&env, &data_account.pubkey(), LOAN_DURATION), 0);
+    let result = process(&mut env, &[ix], &[&data_account]).await;
+    assert_instruction_error(result, InstructionError::MissingRequiredSignature);
+}
+
+#[tokio::test]
+async fn deposit_rejects_paused_and_removed_collections() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    let borrower = env.borrower.insecure_clone();
+
+    let ix = set_paused(&env, true, false);
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+    let data_account = Keypair::new();
+    let ix = deposit_ix(&env, &data_account.pubkey(), LOAN_DURATION);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::ProtocolPaused);
+
+    let ixs = vec![
+        set_paused(&env, false, false),
+        instruction::remove_whitelist(&env.program_id, &admin.pubkey(), &env.creators[0]).unwrap(),
+    ];
+    process(&mut env, &ixs, &[&admin]).await.unwrap();
+    let data_account = Keypair::new();
+    let ix = deposit_ix(&env, &data_account.pubkey(), LOAN_DURATION);
+    let result = process(&mut env, &[ix], &[&borrower, &data_account]).await;
+    assert_token_error(result, TokenError::CollectionNotWhitelisted);
+}
+
+#[tokio::test]
+async fn offer_validation() {
+    let mut env = setup().await;
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let lender = env.lender.insecure_clone();
+    let lender_key = lender.pubkey();
+
+    let cases = vec![
+        (with_account(offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT), 7, Pubkey::new_unique()), TokenError::InvalidDataAccount),
+        (with_account(offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT), 1, lender_key), TokenError::OwnerMismatch),
+        (with_account(offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT), 3, Pubkey::new_unique()), TokenError::MintMismatch),
+        (with_account(offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT), 2, Pubkey::new_unique()), TokenError::InvalidVaultAddress),
+        (with_account(offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT), 9, Pubkey::new_unique()), TokenError::InvalidOfferAccount),
+        (with_account(offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT), 4, Pubkey::new_unique()), TokenError::InvalidTokenAccount),
+        (with_account(offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT), 8, Pubkey::new_unique()), TokenError::InvalidConfigAccount),
+        (with_account(offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT), 6, Pubkey::new_unique()), TokenError::InvalidWhitelistAccount),
+        (with_account(offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT), 10, Pubkey::new_unique()), TokenError::InvalidPriceAccount),
+        (offer_ix(&env, &lender_key, &data_key, 0), TokenError::OfferTooLow),
+        (offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT + 1), TokenError::OfferTooHigh),
+        (
+            instruction::offer(
+                &env.program_id,
+                &lender_key,
+                &env.borrower.pubkey(),
+                &env.nft.mint.pubkey(),
+                &data_key,
+                &env.creators[0],
+                &env.price_account,
+                LOAN_AMOUNT,
+                MAX_LOAN_DURATION + 1,
+                None,
+            )
+            .unwrap(),
+            TokenError::InvalidLoanDuration,
+        ),
+    ];
+    for (ix, expected) in cases {
+        let result = process(&mut env, &[ix], &[&lender]).await;
+        assert_token_error(result, expected);
+    }
+
+    let ix = unsigned(offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT), 0);
+    let result = process(&mut env, &[ix], &[]).await;
+    assert_instruction_error(result, InstructionError::MissingRequiredSignature);
+
+    offer(&mut env, &lender, &data_key).await;
+    let ix = offer_ix(&env, &lender_key, &data_key, LOAN_AMOUNT + 1);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::OfferAlreadyExists);
+
+    let admin = env.admin.insecure_clone();
+    let ix = set_paused(&env, false, true);
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+    let second_lender = env.second_lender.insecure_clone();
+    let ix = offer_ix(&env, &second_lender.pubkey(), &data_key, LOAN_AMOUNT);
+    let result = process(&mut env, &[ix], &[&second_lender]).await;
+    assert_token_error(result, TokenError::ProtocolPaused);
+    let ix = set_paused(&env, false, false);
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+
+    select(&mut env, &lender_key, &data_key).await;
+    // an offer on a running loan can only refinance it
+    let ix = offer_ix(&env, &second_lender.pubkey(), &data_key, LOAN_AMOUNT);
+    process(&mut env, &[ix], &[&second_lender]).await.unwrap();
+    let borrower = env.borrower.insecure_clone();
+    let ix = select_ix(&env, &second_lender.pubkey(), &data_key);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::LoanAlreadyTaken);
+}
+
+#[tokio::test]
+async fn offer_on_canceled_deposit() {
+    let mut env = setup().await;
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let borrower = env.borrower.insecure_clone();
+    let ix = cancel_ix(&env, &borrower.pubkey(), &env.nft.mint.pubkey(), &data_key);
+    process(&mut env, &[ix], &[&borrower]).await.unwrap();
+
+    let lender = env.lender.insecure_clone();
+    let ix = offer_ix(&env, &lender.pubkey(), &data_key, LOAN_AMOUNT);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::LoanCanceled);
+}
+
+#[tokio::test]
+async fn select_validation() {
+    let mut env = setup().await;
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let borrower = env.borrower.insecure_clone();
+    let lender_key = env.lender.pubkey();
+
+    let ix = select_ix(&env, &lender_key, &data_key);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::LoanNotOffered);
+
+    let lender = env.lender.insecure_clone();
+    offer(&mut env, &lender, &data_key).await;
+    let second_lender = env.second_lender.insecure_clone();
+
+    let cases = vec![
+        (with_account(select_ix(&env, &lender_key, &data_key), 6, Pubkey::new_unique()), TokenError::InvalidDataAccount),
+        (with_account(select_ix(&env, &lender_key, &data_key), 3, Pubkey::new_unique()), TokenError::MintMismatch),
+        (with_account(select_ix(&env, &lender_key, &data_key), 2, Pubkey::new_unique()), TokenError::InvalidVaultAddress),
+        (with_account(select_ix(&env, &lender_key, &data_key), 8, Pubkey::new_unique()), TokenError::InvalidOfferAccount),
+        // the second lender has no offer on this NFT
+        (select_ix(&env, &second_lender.pubkey(), &data_key), TokenError::InvalidOfferAccount),
+        (with_account(select_ix(&env, &lender_key, &data_key), 4, Pubkey::new_unique()), TokenError::InvalidTokenAccount),
+        (with_account(select_ix(&env, &lender_key, &data_key), 7, Pubkey::new_unique()), TokenError::InvalidConfigAccount),
+    ];
+    for (ix, expected) in cases {
+        let result = process(&mut env, &[ix], &[&borrower]).await;
+        assert_token_error(result, expected);
+    }
+
+    let ix = with_account(select_ix(&env, &lender_key, &data_key), 1, second_lender.pubkey());
+    let result = process(&mut env, &[ix], &[&second_lender]).await;
+    assert_token_error(result, TokenError::OwnerMismatch);
+
+    let ix = unsigned(select_ix(&env, &lender_key, &data_key), 1);
+    let result = process(&mut env, &[ix], &[]).await;
+    assert_instruction_error(result, InstructionError::MissingRequiredSignature);
+
+    select(&mut env, &lender_key, &data_key).await;
+    let ix = select_ix(&env, &lender_key, &data_key);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::LoanAlreadyTaken);
+}
+
+#[tokio::test]
+async fn cancel_validation() {
+    let mut env = setup().await;
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let borrower = env.borrower.insecure_clone();
+    let owner = borrower.pubkey();
+    let mint = env.nft.mint.pubkey();
+
+    let cases = vec![
+        (with_account(cancel_ix(&env, &owner, &mint, &data_key), 2, Pubkey::new_unique()), TokenError::InvalidTokenAccount),
+        (with_account(cancel_ix(&env, &owner, &mint, &data_key), 5, Pubkey::new_unique()), TokenError::InvalidVaultAddress),
+        (with_account(cancel_ix(&env, &owner, &mint, &data_key), 6, Pubkey::new_unique()), TokenError::InvalidTokenAccount),
+        (cancel_ix(&env, &owner, &mint, &Pubkey::new_unique()), TokenError::InvalidDataAccount),
+        (cancel_ix(&env, &owner, &env.fake_nft.mint.pubkey(), &data_key), TokenError::MintMismatch),
+    ];
+    for (ix, expected) in cases {
+        let result = process(&mut env, &[ix], &[&borrower]).await;
+        assert_token_error(result, expected);
+    }
+
+    let lender = env.lender.insecure_clone();
+    let ix = cancel_ix(&env, &lender.pubkey(), &mint, &data_key);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::OwnerMismatch);
+
+    let ix = unsigned(cancel_ix(&env, &owner, &mint, &data_key), 0);
+    let result = process(&mut env, &[ix], &[]).await;
+    assert_instruction_error(result, InstructionError::MissingRequiredSignature);
+
+    offer(&mut env, &lender, &data_key).await;
+    select(&mut env, &lender.pubkey(), &data_key).await;
+    let ix = cancel_ix(&env, &owner, &mint, &data_key);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::LoanAlreadyTaken);
+}
+
+#[tokio::test]
+async fn repay_validation() {
+    let mut env = setup().await;
+    let borrower = env.borrower.insecure_clone();
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let ix = repay_ix(&env, &data_key, u64::MAX);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::LoanNotTaken);
+
+    let lender = env.lender.insecure_clone();
+    offer(&mut env, &lender, &data_key).await;
+    select(&mut env, &lender.pubkey(), &data_key).await;
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    set_time(&mut env, start, SECONDS_PER_DAY).await;
+
+    let repay_with = |env: &TestEnv, nft_owner: &Pubkey, mint: &Pubkey, data: &Pubkey| {
+        instruction::repay(
+            &env.program_id,
+            nft_owner,
+            mint,
+            data,
+            &env.creators[0],
+            &env.creators,
+            &env.lender.pubkey(),
+            u64::MAX,
+            None,
+        )
+        .unwrap()
+    };
+    let owner = borrower.pubkey();
+    let mint = env.nft.mint.pubkey();
+    let cases = vec![
+        (with_account(repay_ix(&env, &data_key, u64::MAX), 2, Pubkey::new_unique()), TokenError::InvalidTokenAccount),
+        (with_account(repay_ix(&env, &data_key, u64::MAX), 5, Pubkey::new_unique()), TokenError::InvalidVaultAddress),
+        (with_account(repay_ix(&env, &data_key, u64::MAX), 6, Pubkey::new_unique()), TokenError::InvalidTokenAccount),
+        (repay_with(&env, &owner, &mint, &Pubkey::new_unique()), TokenError::InvalidDataAccount),
+        (repay_with(&env, &owner, &env.fake_nft.mint.pubkey(), &data_key), TokenError::MintMismatch),
+        (with_account(repay_ix(&env, &data_key, u64::MAX), 8, Pubkey::new_unique()), TokenError::InvalidWhitelistAccount),
+        (
+            // owned by the program but not the loan's whitelist
+            {
+                let ix = repay_ix(&env, &data_key, u64::MAX);
+                let config_account = ix.accounts[10].pubkey;
+                with_account(ix, 8, config_account)
+            },
+            TokenError::InvalidWhitelistAccount,
+        ),
+        (repay_ix(&env, &data_key, LOAN_AMOUNT), TokenError::InsufficientRepayment),
+        (with_account(repay_ix(&env, &data_key, u64::MAX), 9, env.second_lender.pubkey()), TokenError::LenderMismatch),
+        (with_account(repay_ix(&env, &data_key, u64::MAX), 11, Pubkey::new_unique()), TokenError::InvalidTreasuryAccount),
+    ];
+    for (ix, expected) in cases {
+        let result = process(&mut env, &[ix], &[&borrower]).await;
+        assert_token_error(result, expected);
+    }
+
+    let ix = repay_with(&env, &lender.pubkey(), &mint, &data_key);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::OwnerMismatch);
+
+    let ix = unsigned(repay_ix(&env, &data_key, u64::MAX), 0);
+    let result = process(&mut env, &[ix], &[]).await;
+    assert_instruction_error(result, InstructionError::MissingRequiredSignature);
+
+    set_time(&mut env, start, LOAN_DURATION + 1).await;
+    let ix = repay_ix(&env, &data_key, u64::MAX);
+    let result = process(&mut env, &[ix], &[&borrower]).await;
+    assert_token_error(result, TokenError::LoanExpired);
+}
+
+#[tokio::test]
+async fn foreclose_validation() {
+    let mut env = setup().await;
+    let lender = env.lender.insecure_clone();
+    let data_key = deposit(&mut env, LOAN_DURATION).await.pubkey();
+    let ix = foreclose_ix(&env, &data_key);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::LoanNotTaken);
+
+    offer(&mut env, &lender, &data_key).await;
+    select(&mut env, &lender.pubkey(), &data_key).await;
+    let ix = foreclose_ix(&env, &data_key);
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::LoanNotExpired);
+
+    let start = nft_details(&mut env, &data_key).await.loan_start;
+    set_time(&mut env, start, LOAN_DURATION + 1).await;
+    let cases = vec![
+        (
+            instruction::foreclose(
+                &env.program_id,
+                &env.borrower.pubkey(),
+                &env.nft.mint.pubkey(),
+                &Pubkey::new_unique(),
+                &lender.pubkey(),
+                &whitelist_address(&env),
+            )
+            .unwrap(),
+            TokenError::InvalidDataAccount,
+        ),
+        (with_account(foreclose_ix(&env, &data_key), 0, Pubkey::new_unique()), TokenError::OwnerMismatch),
+        (with_account(foreclose_ix(&env, &data_key), 1, Pubkey::new_unique()), TokenError::MintMismatch),
+        (with_account(foreclose_ix(&env, &data_key), 8, Pubkey::new_unique()), TokenError::InvalidTokenAccount),
+        (with_account(foreclose_ix(&env, &data_key), 4, Pubkey::new_unique()), TokenError::InvalidVaultAddress),
+        (with_account(foreclose_ix(&env, &data_key), 5, Pubkey::new_unique()), TokenError::InvalidTokenAccount),
+        (with_account(foreclose_ix(&env, &data_key), 11, Pubkey::new_unique()), TokenError::InvalidWhitelistAccount),
+    ];
+    for (ix, expected) in cases {
+        let result = process(&mut env, &[ix], &[&lender]).await;
+        assert_token_error(result, expected);
+    }
+
+    let second_lender = env.second_lender.insecure_clone();
+    let ix = instruction::foreclose(
+        &env.program_id,
+        &env.borrower.pubkey(),
+        &env.nft.mint.pubkey(),
+        &data_key,
+        &second_lender.pubkey(),
+        &whitelist_address(&env),
+    )
+    .unwrap();
+    let result = process(&mut env, &[ix], &[&second_lender]).await;
+    assert_token_error(result, TokenError::LenderMismatch);
+
+    let ix = unsigned(foreclose_ix(&env, &data_key), 7);
+    let result = process(&mut env, &[ix], &[]).await;
+    assert_instruction_error(result, InstructionError::MissingRequiredSignature);
+}
+
+#[tokio::test]
+async fn withdraw_offer_validation() {
+    let mut env = setup().await;
+    let data_key = start_loan(&mut env).await.pubkey();
+    let lender = env.lender.insecure_clone();
+
+    let ix = instruction::withdraw_offer(&env.program_id, &lender.pubkey(), &data_key, None).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::LoanStillActive);
+
+    let ix = with_account(
+        instruction::withdraw_offer(&env.program_id, &lender.pubkey(), &data_key, None).unwrap(),
+        2,
+        Pubkey::new_unique(),
+    );
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::InvalidOfferAccount);
+
+    let ix = with_account(
+        instruction::withdraw_offer(&env.program_id, &lender.pubkey(), &data_key, None).unwrap(),
+        1,
+        Pubkey::new_unique(),
+    );
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::InvalidDataAccount);
+
+    let ix = unsigned(instruction::withdraw_offer(&env.program_id, &lender.pubkey(), &data_key, None).unwrap(), 0);
+    let result = process(&mut env, &[ix], &[]).await;
+    assert_instruction_error(result, InstructionError::MissingRequiredSignature);
+}
+
+#[tokio::test]
+async fn admin_validation() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    let lender = env.lender.insecure_clone();
+    let new_creators = vec![Pubkey::new_unique()];
+    let price = mokshya_price_config(&env);
+
+    let ix = instruction::whitelist(&env.program_id, &lender.pubkey(), &new_creators, price.clone(), MAX_LTV_BPS).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+
+    let ix = with_account(
+        instruction::whitelist(&env.program_id, &admin.pubkey(), &new_creators, price.clone(), MAX_LTV_BPS).unwrap(),
+        1,
+        Pubkey::new_unique(),
+    );
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidConfigAccount);
+
+    let ix = with_account(
+        instruction::whitelist(&env.program_id, &admin.pubkey(), &new_creators, price.clone(), MAX_LTV_BPS).unwrap(),
+        3,
+        Pubkey::new_unique(),
+    );
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidWhitelistAccount);
+
+    let ix = unsigned(instruction::whitelist(&env.program_id, &admin.pubkey(), &new_creators, price.clone(), MAX_LTV_BPS).unwrap(), 0);
+    let result = process(&mut env, &[ix], &[]).await;
+    assert_instruction_error(result, InstructionError::MissingRequiredSignature);
+
+    let ix = instruction::remove_whitelist(&env.program_id, &lender.pubkey(), &env.creators[0]).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+
+    let ix = with_account(
+        instruction::remove_whitelist(&env.program_id, &admin.pubkey(), &env.creators[0]).unwrap(),
+        2,
+        Pubkey::new_unique(),
+    );
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidWhitelistAccount);
+
+    // the derived whitelist of an unknown creator isn't owned by the program
+    let ix = instruction::remove_whitelist(&env.program_id, &admin.pubkey(), &new_creators[0]).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidWhitelistAccount);
+
+    let ix = instruction::update_interest(&env.program_id, &lender.pubkey(), &env.creators[0], 1).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+
+    let ix = instruction::update_interest(&env.program_id, &admin.pubkey(), &new_creators[0], 1).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidWhitelistAccount);
+
+    let ix = instruction::remove_whitelist(&env.program_id, &admin.pubkey(), &env.creators[0]).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+    let ix = instruction::update_interest(&env.program_id, &admin.pubkey(), &env.creators[0], 1).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::CollectionNotWhitelisted);
+}
+
+#[tokio::test]
+async fn config_validation() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    let lender = env.lender.insecure_clone();
+
+    // only the upgrade authority can initialize the config
+    let ix = instruction::initialize_config(&env.program_id, &lender.pubkey(), 0).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+    let ix = instruction::initialize_config(&env.program_id, &admin.pubkey(), 0).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_instruction_error(result, InstructionError::AccountAlreadyInitialized);
+
+    let update = |fee_bps| instruction::ProcessUpdateConfig {
+        fee_bps,
+        deposits_paused: false,
+        loans_paused: false,
+        new_admin: None,
+        clear_pending_admin: false,
+    };
+    let ix = instruction::update_config(&env.program_id, &admin.pubkey(), update(10_001)).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidFee);
+
+    // a nomination can't be proposed and withdrawn at once
+    let update_both = instruction::ProcessUpdateConfig {
+        new_admin: Some(lender.pubkey()),
+        clear_pending_admin: true,
+        ..update(0)
+    };
+    let ix = instruction::update_config(&env.program_id, &admin.pubkey(), update_both).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidInstruction);
+
+    let ix = instruction::update_config(&env.program_id, &lender.pubkey(), update(0)).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+
+    let ix = instruction::accept_admin(&env.program_id, &lender.pubkey()).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::NotPendingAdmin);
+}
+
+#[tokio::test]
+async fn ltv_validation() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    let lender = env.lender.insecure_clone();
+
+    let ix = instruction::update_ltv(&env.program_id, &lender.pubkey(), &env.creators[0], MAX_LTV_BPS).unwrap();
+    let result = process(&mut env, &[ix], &[&lender]).await;
+    assert_token_error(result, TokenError::Unauthorized);
+
+    let ix = instruction::update_ltv(&env.program_id, &admin.pubkey(), &env.creators[0], 10_001).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidLtv);
+
+    let ix = instruction::whitelist(
+        &env.program_id,
+        &admin.pubkey(),
+        &[Pubkey::new_unique()],
+        mokshya_price_config(&env),
+        10_001,
+    )
+    .unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidLtv);
+}
+
+#[tokio::test]
+async fn interest_rate_validation() {
+    let mut env = setup().await;
+    let admin = env.admin.insecure_clone();
+    let ix = instruction::update_interest(&env.program_id, &admin.pubkey(), &env.creators[0], MAX_INTEREST_RATE_BPS + 1).unwrap();
+    let result = process(&mut env, &[ix], &[&admin]).await;
+    assert_token_error(result, TokenError::InvalidInterestRate);
+    let ix = instruction::update_interest(&env.program_id, &admin.pubkey(), &env.creators[0], MAX_INTEREST_RATE_BPS).unwrap();
+    process(&mut env, &[ix], &[&admin]).await.unwrap();
+}